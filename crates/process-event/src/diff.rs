@@ -0,0 +1,425 @@
+//! Comparison of symbolication results between two Symbolicators.
+//!
+//! This is used to verify that an upgrade of Symbolicator does not regress symbolication.  Each
+//! input file is symbolicated by a baseline and a candidate, and the results are compared
+//! frame by frame.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use reqwest::blocking as reqwest;
+use serde_json::Value;
+
+use crate::RequestOptions;
+
+/// The fields of frames that are compared.
+const FRAME_FIELDS: &[&str] = &["function", "filename", "lineno", "status"];
+
+/// The fields of modules that are compared.
+const MODULE_FIELDS: &[&str] = &["debug_status"];
+
+/// The number of seconds Symbolicator may hold a poll request before responding as pending.
+///
+/// This stays below the default timeout of the HTTP client.
+const POLL_TIMEOUT: u64 = 20;
+
+/// The source of symbolication results for one side of the comparison.
+#[derive(Debug)]
+pub enum Target {
+    /// A running Symbolicator that the inputs are sent to.
+    Symbolicator(String),
+    /// A directory of saved results, named after the input file with a `.json` extension.
+    Saved(PathBuf),
+}
+
+impl Target {
+    /// Parses a target, which is a URL for Symbolicators and a path otherwise.
+    pub fn parse(target: &str) -> Self {
+        if target.starts_with("http://") || target.starts_with("https://") {
+            Target::Symbolicator(target.trim_end_matches('/').to_owned())
+        } else {
+            Target::Saved(PathBuf::from(target))
+        }
+    }
+
+    /// Returns the symbolication result for the given input file.
+    fn result(
+        &self,
+        client: &reqwest::Client,
+        input: &Path,
+        options: &RequestOptions,
+    ) -> Result<Value, anyhow::Error> {
+        match self {
+            Target::Symbolicator(url) => {
+                let response = crate::symbolicate(client, url, input, options)?;
+                let response = response.error_for_status()?.json()?;
+                poll_response(client, url, response)
+            }
+            Target::Saved(dir) => {
+                let mut file_name = input.file_name().unwrap_or_default().to_owned();
+                file_name.push(".json");
+                let path = dir.join(file_name);
+                let file = File::open(&path)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                Ok(serde_json::from_reader(file)?)
+            }
+        }
+    }
+}
+
+/// Polls a pending symbolication response until Symbolicator has finished processing it.
+fn poll_response(
+    client: &reqwest::Client,
+    url: &str,
+    mut response: Value,
+) -> Result<Value, anyhow::Error> {
+    while response.get("status").and_then(Value::as_str) == Some("pending") {
+        let request_id = response
+            .get("request_id")
+            .and_then(Value::as_str)
+            .context("pending response without request id")?;
+        response = client
+            .get(format!(
+                "{}/requests/{}?timeout={}",
+                url, request_id, POLL_TIMEOUT
+            ))
+            .send()?
+            .error_for_status()?
+            .json()?;
+    }
+
+    Ok(response)
+}
+
+/// A single field that differs between the baseline and the candidate.
+#[derive(Debug)]
+struct Difference {
+    /// The frame or module the field belongs to.
+    location: String,
+    /// The name of the field.
+    field: &'static str,
+    baseline: Value,
+    candidate: Value,
+}
+
+impl Difference {
+    fn new(
+        location: String,
+        field: &'static str,
+        baseline: Option<&Value>,
+        candidate: Option<&Value>,
+    ) -> Self {
+        Self {
+            location,
+            field,
+            baseline: baseline.cloned().unwrap_or_default(),
+            candidate: candidate.cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Compares the given fields of two objects.
+fn diff_fields(
+    differences: &mut Vec<Difference>,
+    location: impl Fn() -> String,
+    fields: &[&'static str],
+    baseline: Option<&Value>,
+    candidate: Option<&Value>,
+) {
+    for field in fields {
+        let baseline = baseline.and_then(|value| value.get(field));
+        let candidate = candidate.and_then(|value| value.get(field));
+        if baseline != candidate {
+            differences.push(Difference::new(location(), field, baseline, candidate));
+        }
+    }
+}
+
+/// Returns the elements of an array in the response, or an empty slice if it is missing.
+fn array<'a>(response: &'a Value, key: &str) -> &'a [Value] {
+    response
+        .get(key)
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Returns the modules of a response keyed by their debug id or code file.
+fn modules_by_id(response: &Value) -> BTreeMap<String, &Value> {
+    array(response, "modules")
+        .iter()
+        .filter_map(|module| {
+            let id = module
+                .get("debug_id")
+                .or_else(|| module.get("code_file"))?
+                .as_str()?;
+            Some((id.to_owned(), module))
+        })
+        .collect()
+}
+
+/// Compares two symbolication responses.
+fn diff_responses(baseline: &Value, candidate: &Value) -> Vec<Difference> {
+    let mut differences = Vec::new();
+
+    let baseline_status = baseline.get("status");
+    let candidate_status = candidate.get("status");
+    if baseline_status != candidate_status {
+        // Failed or pending responses do not contain any stack traces to compare.
+        differences.push(Difference::new(
+            "response".into(),
+            "status",
+            baseline_status,
+            candidate_status,
+        ));
+        return differences;
+    }
+
+    let baseline_stacktraces = array(baseline, "stacktraces");
+    let candidate_stacktraces = array(candidate, "stacktraces");
+    for index in 0..baseline_stacktraces.len().max(candidate_stacktraces.len()) {
+        let baseline_frames = baseline_stacktraces.get(index).map(|s| array(s, "frames"));
+        let candidate_frames = candidate_stacktraces.get(index).map(|s| array(s, "frames"));
+
+        let baseline_len = baseline_frames.map(<[_]>::len);
+        let candidate_len = candidate_frames.map(<[_]>::len);
+        if baseline_len != candidate_len {
+            differences.push(Difference {
+                location: format!("thread {}", index),
+                field: "frames",
+                baseline: baseline_len.into(),
+                candidate: candidate_len.into(),
+            });
+        }
+
+        let baseline_frames = baseline_frames.unwrap_or_default();
+        let candidate_frames = candidate_frames.unwrap_or_default();
+        for (frame, (b, c)) in baseline_frames.iter().zip(candidate_frames).enumerate() {
+            diff_fields(
+                &mut differences,
+                || format!("thread {} frame {}", index, frame),
+                FRAME_FIELDS,
+                Some(b),
+                Some(c),
+            );
+        }
+    }
+
+    let baseline_modules = modules_by_id(baseline);
+    let candidate_modules = modules_by_id(candidate);
+    let mut ids: Vec<_> = baseline_modules
+        .keys()
+        .chain(candidate_modules.keys())
+        .collect();
+    ids.sort();
+    ids.dedup();
+    for id in ids {
+        let baseline = baseline_modules.get(id).copied();
+        let candidate = candidate_modules.get(id).copied();
+        let name = baseline
+            .or(candidate)
+            .and_then(|module| module.get("code_file"))
+            .and_then(Value::as_str)
+            .unwrap_or(id);
+        diff_fields(
+            &mut differences,
+            || format!("module {}", name),
+            MODULE_FIELDS,
+            baseline,
+            candidate,
+        );
+    }
+
+    differences
+}
+
+/// Returns the input files to compare.
+///
+/// For a directory, these are all files directly inside it in alphabetical order.
+fn list_inputs(input: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    if !input.is_dir() {
+        return Ok(vec![input.to_path_buf()]);
+    }
+
+    let mut inputs = Vec::new();
+    for entry in std::fs::read_dir(input)? {
+        let path = entry?.path();
+        if path.is_file() {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Symbolicates all inputs with both targets and prints the differences.
+///
+/// Returns an error if any of the results differ, or if an input could not be symbolicated.
+pub fn compare(
+    input: &Path,
+    baseline: &Target,
+    candidate: &Target,
+    options: &RequestOptions,
+) -> Result<(), anyhow::Error> {
+    let client = reqwest::Client::new();
+
+    let mut identical = 0;
+    let mut different = 0;
+    let mut failed = 0;
+    let mut frame_differences = 0;
+    let mut module_differences = 0;
+
+    for input in list_inputs(input)? {
+        let name = input.display();
+        let results = baseline
+            .result(&client, &input, options)
+            .context("baseline failed")
+            .and_then(|baseline| {
+                let candidate = candidate
+                    .result(&client, &input, options)
+                    .context("candidate failed")?;
+                Ok((baseline, candidate))
+            });
+
+        let (baseline, candidate) = match results {
+            Ok(results) => results,
+            Err(error) => {
+                println!("{name}: {error:#}");
+                failed += 1;
+                continue;
+            }
+        };
+
+        let differences = diff_responses(&baseline, &candidate);
+        if differences.is_empty() {
+            println!("{name}: identical");
+            identical += 1;
+            continue;
+        }
+
+        println!("{name}: {} differences", differences.len());
+        for difference in &differences {
+            let Difference {
+                location,
+                field,
+                baseline,
+                candidate,
+            } = difference;
+            println!("  {location} {field}: {baseline} -> {candidate}");
+
+            if location.starts_with("module") {
+                module_differences += 1;
+            } else {
+                frame_differences += 1;
+            }
+        }
+        different += 1;
+    }
+
+    println!();
+    println!(
+        "{} identical, {} different, {} failed",
+        identical, different, failed
+    );
+    println!(
+        "{} frame differences, {} module differences",
+        frame_differences, module_differences
+    );
+
+    if different > 0 || failed > 0 {
+        anyhow::bail!("symbolication results differ");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn response(function: &str, debug_status: &str) -> Value {
+        json!({
+            "status": "completed",
+            "stacktraces": [{
+                "frames": [
+                    {"function": "main", "filename": "main.c", "lineno": 10, "status": "symbolicated"},
+                    {"function": function, "filename": "foo.c", "lineno": 20, "status": "symbolicated"},
+                ],
+            }],
+            "modules": [{
+                "debug_id": "b8e26ba9-7b8f-4d89-8e3b-6d7c1f5d0fd1",
+                "code_file": "/usr/lib/libfoo.so",
+                "debug_status": debug_status,
+            }],
+        })
+    }
+
+    fn summary(differences: &[Difference]) -> Vec<String> {
+        differences
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} {}: {} -> {}",
+                    d.location, d.field, d.baseline, d.candidate
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_identical() {
+        let baseline = response("foo", "found");
+        assert!(diff_responses(&baseline, &baseline.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_frame_difference() {
+        let baseline = response("foo", "found");
+        let candidate = response("bar", "found");
+        assert_eq!(
+            summary(&diff_responses(&baseline, &candidate)),
+            ["thread 0 frame 1 function: \"foo\" -> \"bar\""]
+        );
+    }
+
+    #[test]
+    fn test_frame_count_difference() {
+        let baseline = response("foo", "found");
+        let mut candidate = response("foo", "found");
+        candidate["stacktraces"][0]["frames"]
+            .as_array_mut()
+            .unwrap()
+            .pop();
+        assert_eq!(
+            summary(&diff_responses(&baseline, &candidate)),
+            ["thread 0 frames: 2 -> 1"]
+        );
+    }
+
+    #[test]
+    fn test_module_status_difference() {
+        let baseline = response("foo", "found");
+        let candidate = response("foo", "missing");
+        assert_eq!(
+            summary(&diff_responses(&baseline, &candidate)),
+            ["module /usr/lib/libfoo.so debug_status: \"found\" -> \"missing\""]
+        );
+    }
+
+    #[test]
+    fn test_pending() {
+        let baseline = response("foo", "found");
+        let candidate = json!({
+            "status": "pending",
+            "request_id": "5f8d6c0a-3b4e-4a57-9d43-6e0b1f0c9a21",
+            "retry_after": 30,
+        });
+        assert_eq!(
+            summary(&diff_responses(&baseline, &candidate)),
+            ["response status: \"completed\" -> \"pending\""]
+        );
+    }
+}
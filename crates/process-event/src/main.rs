@@ -1,25 +1,27 @@
-//! Tool to run Minidumps or Sentry Events through a local Symbolicator.
+//! Tool to run Minidumps, Apple crash reports or Sentry Events through a local Symbolicator.
 
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use ::reqwest::blocking::multipart;
 use reqwest::blocking as reqwest;
-use serde_json::{to_string, Map, Value};
+use serde_json::{json, to_string, Map, Value};
 use structopt::StructOpt;
 use symbolic_common::split_path;
 
 #[path = "../../symbolicator/src/utils/hex.rs"]
 mod hex;
 
+mod diff;
+
 use hex::HexValue;
 
-/// Runs Minidumps or Sentry Events through Symbolicator.
+/// Runs Minidumps, Apple crash reports or Sentry Events through Symbolicator.
 #[derive(Debug, StructOpt)]
 struct Cli {
-    /// Path to the input Minidump or Event JSON.
+    /// Path to the input Minidump, Apple crash report or Event JSON.
+    ///
+    /// When comparing, this can also be a directory of inputs.
     input: PathBuf,
 
     /// The URL of the Symbolicator to use.
@@ -35,55 +37,201 @@ struct Cli {
     /// Pretty-print the crashing thread in a human readable format.
     #[structopt(short, long)]
     pretty: bool,
+
+    /// A source to use instead of the default sources of the Symbolicator.
+    ///
+    /// This is a path on the Symbolicator's file system, an HTTP(S) URL, an S3 bucket in the form
+    /// `s3://bucket/prefix` or a full source definition as JSON object.  S3 credentials and the
+    /// region are read from the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_REGION`
+    /// environment variables.  Can be repeated.
+    #[structopt(long = "source", value_name = "SOURCE")]
+    sources: Vec<String>,
+
+    /// The directory layout of all `--source` definitions that are not JSON.
+    #[structopt(long, value_name = "LAYOUT", default_value = "native")]
+    source_layout: String,
+
+    /// Compare the results of a baseline and a candidate Symbolicator.
+    ///
+    /// This is either the URL of a Symbolicator, or a directory containing saved results
+    /// named after each input file with an additional `.json` extension.
+    #[structopt(long, requires = "candidate", conflicts_with_all = &["symbolicator", "pretty"])]
+    baseline: Option<String>,
+
+    /// The candidate Symbolicator URL or saved results to compare against the baseline.
+    #[structopt(long, requires = "baseline")]
+    candidate: Option<String>,
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    let Cli {
-        input,
-        symbolicator,
-        dif_candidates,
-        pretty,
-    } = Cli::from_args();
+/// Options sent along with every symbolication request.
+#[derive(Debug)]
+struct RequestOptions {
+    /// Whether to include DIF candidate information.
+    dif_candidates: bool,
+    /// Sources overriding the default sources of the Symbolicator.
+    sources: Option<Value>,
+}
 
-    let client = reqwest::Client::new();
-    let symbolicator = symbolicator.as_deref().unwrap_or("http://127.0.0.1:3021");
+/// Converts a `--source` argument into a source definition.
+fn parse_source(index: usize, source: &str, layout: &str) -> Result<Value, anyhow::Error> {
+    if source.starts_with('{') {
+        return Ok(serde_json::from_str(source)?);
+    }
 
-    let mut file = File::open(&input)?;
+    let id = format!("process-event-{}", index);
+    let layout = json!({ "type": layout });
+
+    let source = if source.starts_with("http://") || source.starts_with("https://") {
+        json!({ "id": id, "type": "http", "url": source, "layout": layout })
+    } else if let Some(bucket) = source.strip_prefix("s3://") {
+        let (bucket, prefix) = bucket.split_once('/').unwrap_or((bucket, ""));
+        let env = |key: &str| std::env::var(key).unwrap_or_default();
+        let region = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".into());
+        json!({
+            "id": id,
+            "type": "s3",
+            "bucket": bucket,
+            "prefix": prefix,
+            "region": region,
+            "access_key": env("AWS_ACCESS_KEY_ID"),
+            "secret_key": env("AWS_SECRET_ACCESS_KEY"),
+            "layout": layout,
+        })
+    } else {
+        let path = source.strip_prefix("file://").unwrap_or(source);
+        json!({ "id": id, "type": "filesystem", "path": path, "layout": layout })
+    };
 
-    let mut magic = [0; 4];
-    file.read_exact(&mut magic)?;
-    file.seek(SeekFrom::Start(0))?;
+    Ok(source)
+}
 
-    let req = if &magic == b"MDMP" || &magic == b"PMDM" {
-        let req = client.post(&format!("{}/minidump", symbolicator));
+/// The kind of an input file, which determines the endpoint it is sent to.
+#[derive(Debug, PartialEq, Eq)]
+enum InputKind {
+    Minidump,
+    AppleCrashReport,
+    Event,
+}
 
-        let mut options = Map::new();
-        options.insert("dif_candidates".into(), Value::Bool(dif_candidates));
+/// Detects the kind of an input file from its contents.
+fn detect_input_kind(data: &[u8]) -> InputKind {
+    if data.starts_with(b"MDMP") || data.starts_with(b"PMDM") {
+        return InputKind::Minidump;
+    }
 
-        let mut form = multipart::Form::new();
-        form = form.file("upload_file_minidump", input)?;
-        form = form.text("options", to_string(&options).unwrap());
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    let text = &data[start..];
+    if text.starts_with(b"Incident Identifier:") || text.starts_with(b"Process:") {
+        return InputKind::AppleCrashReport;
+    }
 
-        req.multipart(form).send()
-    } else {
-        let req = client.post(&format!("{}/symbolicate", symbolicator));
+    // JSON `.ips` crash reports consist of a header line with a `bug_type`, followed by the
+    // report itself.  Sentry Events are a single JSON document.
+    let mut documents = serde_json::Deserializer::from_slice(text).into_iter::<Value>();
+    if let Some(Ok(header)) = documents.next() {
+        if header.get("bug_type").is_some() && documents.next().is_some() {
+            return InputKind::AppleCrashReport;
+        }
+    }
 
-        let event = serde_json::from_reader(file)?;
-        let mut json = event::massage_event_json(event);
-        if dif_candidates {
+    InputKind::Event
+}
+
+/// Sends a Minidump, Apple crash report or Event to Symbolicator.
+fn symbolicate(
+    client: &reqwest::Client,
+    symbolicator: &str,
+    input: &Path,
+    options: &RequestOptions,
+) -> Result<reqwest::Response, anyhow::Error> {
+    let data = std::fs::read(input)?;
+    let file_name = input
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let (endpoint, field) = match detect_input_kind(&data) {
+        InputKind::Minidump => ("minidump", "upload_file_minidump"),
+        InputKind::AppleCrashReport => ("applecrashreport", "apple_crash_report"),
+        InputKind::Event => {
+            let req = client.post(format!("{}/symbolicate", symbolicator));
+
+            let event = serde_json::from_slice(&data)?;
+            let mut json = event::massage_event_json(event);
             if let Some(obj) = json.as_object_mut() {
-                obj.insert(
-                    String::from("options"),
-                    serde_json::json!({ "dif_candidates": true }),
-                );
+                if options.dif_candidates {
+                    obj.insert(
+                        String::from("options"),
+                        serde_json::json!({ "dif_candidates": true }),
+                    );
+                }
+                if let Some(ref sources) = options.sources {
+                    obj.insert(String::from("sources"), sources.clone());
+                }
             }
+
+            return Ok(req.json(&json).send()?);
         }
+    };
+
+    let req = client.post(format!("{}/{}", symbolicator, endpoint));
+
+    let mut request_options = Map::new();
+    request_options.insert("dif_candidates".into(), Value::Bool(options.dif_candidates));
+
+    let mut form = multipart::Form::new();
+    form = form.part(field, multipart::Part::bytes(data).file_name(file_name));
+    form = form.text("options", to_string(&request_options).unwrap());
+    if let Some(ref sources) = options.sources {
+        form = form.text("sources", to_string(sources).unwrap());
+    }
+
+    Ok(req.multipart(form).send()?)
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    let Cli {
+        input,
+        symbolicator,
+        dif_candidates,
+        pretty,
+        sources,
+        source_layout,
+        baseline,
+        candidate,
+    } = Cli::from_args();
 
-        req.json(&json).send()
+    let sources = if sources.is_empty() {
+        None
+    } else {
+        let sources = sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| parse_source(index, source, &source_layout))
+            .collect::<Result<Vec<_>, _>>()?;
+        Some(Value::Array(sources))
+    };
+    let options = RequestOptions {
+        dif_candidates,
+        sources,
     };
 
+    if let (Some(baseline), Some(candidate)) = (baseline, candidate) {
+        let baseline = diff::Target::parse(&baseline);
+        let candidate = diff::Target::parse(&candidate);
+        return diff::compare(&input, &baseline, &candidate, &options);
+    }
+
+    let client = reqwest::Client::new();
+    let symbolicator = symbolicator.as_deref().unwrap_or("http://127.0.0.1:3021");
+
+    let mut response = symbolicate(&client, symbolicator, &input, &options)?;
+
     if pretty {
-        let payload: event::Payload = req?.json()?;
+        let payload: event::Payload = response.json()?;
         let module_addr_by_code_file: HashMap<_, _> = payload
             .modules
             .into_iter()
@@ -147,7 +295,7 @@ fn main() -> Result<(), anyhow::Error> {
             }
         }
     } else {
-        req?.copy_to(&mut std::io::stdout())?;
+        response.copy_to(&mut std::io::stdout())?;
     }
 
     Ok(())
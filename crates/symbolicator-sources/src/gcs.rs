@@ -1,4 +1,8 @@
 //! Access to Google Cloud Storeage
+//!
+//! This contains the OAuth flow and URLs shared by everything accessing a [`GcsSourceConfig`].
+//!
+//! [`GcsSourceConfig`]: crate::GcsSourceConfig
 
 use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::EncodingKey;
@@ -7,7 +11,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use url::Url;
 
-use symbolicator_sources::GcsSourceKey;
+use crate::GcsSourceKey;
 
 /// A JWT token usable for GCS.
 #[derive(Debug)]
@@ -28,6 +32,25 @@ impl GcsToken {
     }
 }
 
+/// The level of access granted by a [`GcsToken`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcsAccess {
+    /// Only read objects from the bucket.
+    ReadOnly,
+    /// Read and write objects in the bucket.
+    ReadWrite,
+}
+
+impl GcsAccess {
+    /// Returns the OAuth scope for this level of access.
+    fn scope(self) -> &'static str {
+        match self {
+            GcsAccess::ReadOnly => "https://www.googleapis.com/auth/devstorage.read_only",
+            GcsAccess::ReadWrite => "https://www.googleapis.com/auth/devstorage.read_write",
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct JwtClaims {
     #[serde(rename = "iss")]
@@ -52,14 +75,19 @@ struct GcsTokenResponse {
     access_token: String,
 }
 
+/// Errors accessing GCS.
 #[derive(Debug, Error)]
 pub enum GcsError {
+    /// The private key could not be decoded.
     #[error("failed decoding key")]
     Base64(#[from] base64::DecodeError),
+    /// The bucket or object name do not form a valid URL.
     #[error("failed to construct URL")]
     InvalidUrl,
+    /// The authentication assertion could not be signed.
     #[error("failed encoding JWT")]
     Jwt(#[from] jsonwebtoken::errors::Error),
+    /// The OAuth token could not be requested.
     #[error("failed to send authentication request")]
     Auth(#[source] reqwest::Error),
 }
@@ -95,12 +123,16 @@ pub fn download_url(bucket: &str, object: &str) -> Result<Url, GcsError> {
 }
 
 /// Computes a JWT authentication assertion for the given GCS bucket.
-fn get_auth_jwt(source_key: &GcsSourceKey, expiration: i64) -> Result<String, GcsError> {
+fn get_auth_jwt(
+    source_key: &GcsSourceKey,
+    access: GcsAccess,
+    expiration: i64,
+) -> Result<String, GcsError> {
     let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
 
     let jwt_claims = JwtClaims {
         issuer: source_key.client_email.clone(),
-        scope: "https://www.googleapis.com/auth/devstorage.read_only".into(),
+        scope: access.scope().into(),
         audience: "https://www.googleapis.com/oauth2/v4/token".into(),
         expiration,
         issued_at: Utc::now().timestamp(),
@@ -111,13 +143,14 @@ fn get_auth_jwt(source_key: &GcsSourceKey, expiration: i64) -> Result<String, Gc
     Ok(jsonwebtoken::encode(&header, &jwt_claims, &key)?)
 }
 
-/// Requests a new GCS OAuth token.
+/// Requests a new GCS OAuth token with the given level of access.
 pub async fn request_new_token(
     client: &Client,
     source_key: &GcsSourceKey,
+    access: GcsAccess,
 ) -> Result<GcsToken, GcsError> {
     let expires_at = Utc::now() + Duration::minutes(58);
-    let auth_jwt = get_auth_jwt(source_key, expires_at.timestamp() + 30)?;
+    let auth_jwt = get_auth_jwt(source_key, access, expires_at.timestamp() + 30)?;
 
     let request = client
         .post("https://www.googleapis.com/oauth2/v4/token")
@@ -146,11 +179,18 @@ pub async fn request_new_token(
 mod tests {
     use super::*;
 
-    use crate::test;
-
     #[test]
     fn test_key_from_string() {
-        let creds = test::gcs_source_key!();
+        let creds = match std::env::var("SENTRY_SYMBOLICATOR_GCS_PRIVATE_KEY") {
+            Ok(private_key) if !private_key.is_empty() => GcsSourceKey {
+                private_key,
+                client_email: String::new(),
+            },
+            _ => {
+                println!("Skipping due to missing SENTRY_SYMBOLICATOR_GCS_PRIVATE_KEY");
+                return;
+            }
+        };
 
         let key = key_from_string(&creds.private_key);
         assert!(key.is_ok());
@@ -6,6 +6,7 @@
 #![warn(missing_docs)]
 
 mod filetype;
+pub mod gcs;
 mod paths;
 mod sources;
 mod types;
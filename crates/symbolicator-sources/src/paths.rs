@@ -549,6 +549,248 @@ pub fn parse_symstore_path(path: &str) -> Option<(&'static [FileType], ObjectId)
     }
 }
 
+/// Returns `true` if the string consists of hexadecimal digits only.
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Parses a GDB style path of ELF and WASM files, such as `df/b85de42daffd.debug`.
+fn parse_gdb_path(prefix: &str, rest: &str) -> Option<(FileType, ObjectId)> {
+    let rest_lower = rest.to_lowercase();
+    let (filetype, code_id) = if let Some(code_id) = rest_lower.strip_suffix(".src.zip") {
+        (FileType::SourceBundle, code_id)
+    } else if let Some(code_id) = rest_lower.strip_suffix(".debug") {
+        (FileType::ElfDebug, code_id)
+    } else {
+        (FileType::ElfCode, rest_lower.as_str())
+    };
+
+    let code_id = format!("{}{}", prefix, code_id).to_lowercase();
+    if prefix.len() != 2 || !is_hex(&code_id) {
+        return None;
+    }
+
+    Some((
+        filetype,
+        ObjectId {
+            code_id: Some(CodeId::new(code_id)),
+            object_type: ObjectType::Elf,
+            ..ObjectId::default()
+        },
+    ))
+}
+
+/// Parses an LLDB style path of MachO files, such as `67E9/247C/814E/392B/A027/DBDE6748FCBF.app`.
+fn parse_lldb_path(components: &[&str]) -> Option<(FileType, ObjectId)> {
+    let (last, groups) = components.split_last()?;
+    let last_lower = last.to_lowercase();
+    let (filetype, last) = if let Some(last) = last_lower.strip_suffix(".src.zip") {
+        (FileType::SourceBundle, last)
+    } else if let Some(last) = last_lower.strip_suffix(".app") {
+        (FileType::MachCode, last)
+    } else {
+        (FileType::MachDebug, last_lower.as_str())
+    };
+
+    if groups.iter().any(|group| group.len() != 4) || last.len() != 12 {
+        return None;
+    }
+
+    let uuid = Uuid::parse_str(&format!("{}{}", groups.concat(), last)).ok()?;
+    Some((
+        filetype,
+        ObjectId {
+            code_id: Some(CodeId::new(uuid.as_simple().to_string())),
+            debug_id: Some(DebugId::from_uuid(uuid)),
+            object_type: ObjectType::Macho,
+            ..ObjectId::default()
+        },
+    ))
+}
+
+/// Parses a path in the Microsoft symbol server convention of `name/signature/name`.
+///
+/// In the native layout, this also covers Breakpad symbols and source bundles which are stored
+/// next to the debug file with a different extension.  Compressed files, where the last character
+/// of the trailing name is replaced with an underscore, are supported as well.
+fn parse_symstore_components(
+    leading_fn: &str,
+    signature: &str,
+    trailing_fn: &str,
+    native: bool,
+) -> Option<(FileType, ObjectId)> {
+    let leading_lower = leading_fn.to_lowercase();
+    let trailing_lower = trailing_fn.to_lowercase();
+
+    let auxiliary = [
+        (".src.zip", FileType::SourceBundle),
+        (".sym", FileType::Breakpad),
+    ];
+    for (extension, filetype) in auxiliary {
+        let stem = match trailing_lower.strip_suffix(extension) {
+            Some(stem) => stem,
+            None => continue,
+        };
+
+        let is_pdb = leading_lower.ends_with(".pdb");
+        if !native && (filetype == FileType::Breakpad || !is_pdb) {
+            return None;
+        }
+
+        let leading_stem = leading_lower.rsplit_once('.').map(|(stem, _)| stem);
+        if stem != leading_lower && Some(stem) != leading_stem {
+            return None;
+        }
+
+        let debug_id = DebugId::from_breakpad(signature).ok()?;
+        let object_type = if !is_pdb {
+            ObjectType::Unknown
+        } else if debug_id.appendix() == u32::MAX {
+            ObjectType::PeDotnet
+        } else {
+            ObjectType::Pe
+        };
+        return Some((
+            filetype,
+            ObjectId {
+                debug_id: Some(debug_id),
+                debug_file: Some(leading_fn.into()),
+                object_type,
+                ..ObjectId::default()
+            },
+        ));
+    }
+
+    // Restore the name of compressed files, such as `crash.pd_`.
+    let trailing_fn = match trailing_lower.strip_suffix('_') {
+        Some(stem)
+            if leading_lower.len() == trailing_lower.len() && leading_lower.starts_with(stem) =>
+        {
+            leading_fn
+        }
+        _ => trailing_fn,
+    };
+
+    let (filetypes, mut identifier) =
+        parse_symstore_path(&format!("/{}/{}/{}", leading_fn, signature, trailing_fn))?;
+    let mut filetype = *filetypes.first()?;
+
+    // Portable PDBs use `u32::MAX` in place of the age, see `get_pdb_symstore_path`.
+    if filetype == FileType::Pdb && identifier.debug_id?.appendix() == u32::MAX {
+        filetype = FileType::PortablePdb;
+        identifier.object_type = ObjectType::PeDotnet;
+    }
+
+    Some((filetype, identifier))
+}
+
+/// Parses a path in the unified layout, such as `32/49d99d0c4049318610f4e4fb0b69361/debuginfo`.
+///
+/// The unified layout does not encode the object type, so it is guessed from the length of the
+/// identifier: UUIDs are assumed to be MachO, Breakpad identifiers PE and everything else ELF.
+fn parse_unified_path(prefix: &str, rest: &str, suffix: &str) -> Option<(FileType, ObjectId)> {
+    let id = format!("{}{}", prefix, rest).to_lowercase();
+    if prefix.len() != 2 || !is_hex(&id) {
+        return None;
+    }
+
+    let (object_type, code_id, debug_id) = match id.len() {
+        32 => {
+            let uuid = Uuid::parse_str(&id).ok()?;
+            (ObjectType::Macho, Some(id), Some(DebugId::from_uuid(uuid)))
+        }
+        33..=39 => (
+            ObjectType::Pe,
+            None,
+            Some(DebugId::from_breakpad(&id).ok()?),
+        ),
+        _ => (ObjectType::Elf, Some(id), None),
+    };
+
+    let filetype = match (suffix.to_lowercase().as_str(), object_type) {
+        // PE and PDB files may be stored compressed, with an underscore as last character.
+        ("executable" | "executabl_", ObjectType::Pe) => FileType::Pe,
+        ("executable", ObjectType::Macho) => FileType::MachCode,
+        ("executable", _) => FileType::ElfCode,
+        ("debuginfo" | "debuginf_", ObjectType::Pe) => FileType::Pdb,
+        ("debuginfo", ObjectType::Macho) => FileType::MachDebug,
+        ("debuginfo", _) => FileType::ElfDebug,
+        ("breakpad", _) => FileType::Breakpad,
+        ("sourcebundle", _) => FileType::SourceBundle,
+        ("uuidmap", _) => FileType::UuidMap,
+        ("bcsymbolmap", _) => FileType::BcSymbolMap,
+        ("il2cpp", _) => FileType::Il2cpp,
+        _ => return None,
+    };
+
+    Some((
+        filetype,
+        ObjectId {
+            code_id: code_id.map(CodeId::new),
+            debug_id,
+            object_type,
+            ..ObjectId::default()
+        },
+    ))
+}
+
+/// Parses a path relative to the root of a symbol server with the given layout.
+///
+/// This is the inverse of [`get_directory_paths`] and returns the [`FileType`] and the
+/// identifiers encoded in the path.  Since layouts do not encode all identifiers, the returned
+/// [`ObjectId`] is incomplete and the file type is a best guess where a layout uses the same
+/// path for several file types, such as ELF and WASM files in the native layout.
+///
+/// Returns `None` if the path does not belong to a file in this layout.
+pub fn parse_directory_path(
+    layout_type: DirectoryLayoutType,
+    path: &str,
+) -> Option<(FileType, ObjectId)> {
+    let components: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+    match (layout_type, components.as_slice()) {
+        (DirectoryLayoutType::Native, [prefix, rest]) => parse_gdb_path(prefix, rest),
+        (DirectoryLayoutType::Native, [_, _, _, _, _, _]) => parse_lldb_path(&components),
+        (DirectoryLayoutType::Native, [leading_fn, signature, trailing_fn]) => {
+            parse_symstore_components(leading_fn, signature, trailing_fn, true)
+        }
+        (
+            DirectoryLayoutType::Symstore | DirectoryLayoutType::Ssqp,
+            [leading_fn, signature, trailing_fn],
+        ) => parse_symstore_components(leading_fn, signature, trailing_fn, false),
+        (DirectoryLayoutType::SymstoreIndex2, [index, leading_fn, signature, trailing_fn]) => {
+            // The index consists of the first two characters of the file name, or only the
+            // first one if the second is a separator.
+            if index.is_empty() || index.len() > 2 || !leading_fn.starts_with(index) {
+                return None;
+            }
+            parse_symstore_components(leading_fn, signature, trailing_fn, false)
+        }
+        (DirectoryLayoutType::Debuginfod, [code_id, kind]) => {
+            let filetype = match kind.to_lowercase().as_str() {
+                "executable" => FileType::ElfCode,
+                "debuginfo" => FileType::ElfDebug,
+                _ => return None,
+            };
+            if !is_hex(code_id) {
+                return None;
+            }
+            Some((
+                filetype,
+                ObjectId {
+                    code_id: Some(CodeId::new(code_id.to_lowercase())),
+                    object_type: ObjectType::Elf,
+                    ..ObjectId::default()
+                },
+            ))
+        }
+        (DirectoryLayoutType::Unified, [prefix, rest, suffix]) => {
+            parse_unified_path(prefix, rest, suffix)
+        }
+        _ => None,
+    }
+}
+
 /// Checks whether an [`ObjectId`] matches any of the [`Glob`] patterns.
 pub fn matches_path_patterns(object_id: &ObjectId, patterns: &[Glob]) -> bool {
     fn canonicalize_path(s: &str) -> String {
@@ -717,6 +959,172 @@ mod tests {
         path_test!(FileType::ElfDebug, ELF_OBJECT_ID, @"_/_.debug/elf-buildid-sym-dfb85de42daffd09640c8fe377d572de3e168920/_.debug");
     }
 
+    #[test]
+    fn test_parse_directory_path_roundtrip() {
+        let cases = [
+            (DirectoryLayoutType::Native, FileType::Pdb, &*PE_OBJECT_ID),
+            (DirectoryLayoutType::Native, FileType::Pe, &*PE_OBJECT_ID),
+            (
+                DirectoryLayoutType::Native,
+                FileType::Breakpad,
+                &*PE_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Native,
+                FileType::SourceBundle,
+                &*PE_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Native,
+                FileType::MachCode,
+                &*MACHO_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Native,
+                FileType::MachDebug,
+                &*MACHO_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Native,
+                FileType::ElfCode,
+                &*ELF_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Native,
+                FileType::ElfDebug,
+                &*ELF_OBJECT_ID,
+            ),
+            (DirectoryLayoutType::Symstore, FileType::Pdb, &*PE_OBJECT_ID),
+            (DirectoryLayoutType::Symstore, FileType::Pe, &*PE_OBJECT_ID),
+            (
+                DirectoryLayoutType::Symstore,
+                FileType::SourceBundle,
+                &*PE_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Symstore,
+                FileType::MachCode,
+                &*MACHO_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Symstore,
+                FileType::MachDebug,
+                &*MACHO_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Symstore,
+                FileType::ElfCode,
+                &*ELF_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Symstore,
+                FileType::ElfDebug,
+                &*ELF_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::SymstoreIndex2,
+                FileType::Pdb,
+                &*PE_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::SymstoreIndex2,
+                FileType::MachDebug,
+                &*MACHO_OBJECT_ID,
+            ),
+            (DirectoryLayoutType::Ssqp, FileType::Pe, &*PE_OBJECT_ID),
+            (
+                DirectoryLayoutType::Ssqp,
+                FileType::ElfCode,
+                &*ELF_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Debuginfod,
+                FileType::ElfCode,
+                &*ELF_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Debuginfod,
+                FileType::ElfDebug,
+                &*ELF_OBJECT_ID,
+            ),
+            (DirectoryLayoutType::Unified, FileType::Pdb, &*PE_OBJECT_ID),
+            (
+                DirectoryLayoutType::Unified,
+                FileType::Breakpad,
+                &*PE_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Unified,
+                FileType::MachCode,
+                &*MACHO_OBJECT_ID,
+            ),
+            (
+                DirectoryLayoutType::Unified,
+                FileType::ElfDebug,
+                &*ELF_OBJECT_ID,
+            ),
+        ];
+
+        for (ty, filetype, identifier) in cases {
+            let layout = DirectoryLayout {
+                ty,
+                casing: FilenameCasing::Default,
+            };
+
+            for path in get_directory_paths(layout, filetype, identifier) {
+                let (parsed_filetype, parsed) = parse_directory_path(ty, &path)
+                    .unwrap_or_else(|| panic!("failed to parse {}", path));
+
+                assert_eq!(parsed_filetype, filetype, "{}", path);
+                assert_eq!(
+                    get_directory_paths(layout, filetype, &parsed),
+                    get_directory_paths(layout, filetype, identifier),
+                    "{}",
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_directory_path() {
+        let (filetype, identifier) = parse_directory_path(
+            DirectoryLayoutType::Ssqp,
+            "foo.pdb/3249d99d0c4049318610f4e4fb0b6936FFFFFFFF/foo.pdb",
+        )
+        .unwrap();
+        assert_eq!(filetype, FileType::PortablePdb);
+        assert_eq!(identifier.object_type, ObjectType::PeDotnet);
+
+        let (filetype, identifier) = parse_directory_path(
+            DirectoryLayoutType::Native,
+            "libm-2.23.so/E45DB8DFAF2D09FD640C8FE377D572DE0/libm-2.23.so.src.zip",
+        )
+        .unwrap();
+        assert_eq!(filetype, FileType::SourceBundle);
+        assert_eq!(identifier.debug_id, ELF_OBJECT_ID.debug_id);
+
+        // Breakpad files are only stored in the native layout.
+        assert!(parse_directory_path(
+            DirectoryLayoutType::Symstore,
+            "crash.pdb/3249D99D0C4049318610F4E4FB0B69361/crash.sym"
+        )
+        .is_none());
+
+        // Administrative files of symbol servers and the unified layout are not debug files.
+        assert!(
+            parse_directory_path(DirectoryLayoutType::Symstore, "000Admin/server.txt").is_none()
+        );
+        assert!(parse_directory_path(DirectoryLayoutType::Symstore, "pingme.txt").is_none());
+        assert!(parse_directory_path(
+            DirectoryLayoutType::Unified,
+            "32/49d99d0c4049318610f4e4fb0b69361/meta"
+        )
+        .is_none());
+        assert!(parse_directory_path(DirectoryLayoutType::Unified, "bundles/foo").is_none());
+        assert!(parse_directory_path(DirectoryLayoutType::Native, "df/not-a-build-id").is_none());
+    }
+
     #[test]
     fn test_matches_path_patterns_empty() {
         assert!(matches_path_patterns(
@@ -248,6 +248,13 @@ pub struct GcsSourceConfig {
     #[serde(flatten)]
     pub source_key: Arc<GcsSourceKey>,
 
+    /// Whether requests referencing this source by id may override its `prefix`.
+    ///
+    /// This only applies to sources configured on the server, and is disabled by default so
+    /// that references cannot read from arbitrary paths in the bucket.
+    #[serde(default)]
+    pub allow_prefix_override: bool,
+
     /// Configuration common to all sources.
     #[serde(flatten)]
     pub files: CommonSourceConfig,
@@ -270,6 +277,13 @@ pub struct S3SourceConfig {
     #[serde(flatten)]
     pub source_key: Arc<S3SourceKey>,
 
+    /// Whether requests referencing this source by id may override its `prefix`.
+    ///
+    /// This only applies to sources configured on the server, and is disabled by default so
+    /// that references cannot read from arbitrary paths in the bucket.
+    #[serde(default)]
+    pub allow_prefix_override: bool,
+
     /// Configuration common to all sources.
     #[serde(flatten)]
     pub files: CommonSourceConfig,
@@ -0,0 +1,437 @@
+//! Authentication and authorization of API requests.
+//!
+//! Callers authenticate with a bearer token in the `Authorization` header, which is either one
+//! of the configured API keys or a JSON Web Token signed with the configured key.  Each caller is
+//! then restricted by the [`AuthRules`] of its API key or the claims of its token.
+
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::http::{header, HeaderMap};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use thiserror::Error;
+
+use crate::config::{ApiKey, Auth, AuthRules, Config, JwtAuth};
+use crate::types::{RequestSource, Scope};
+
+/// Errors authenticating or authorizing a caller.
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("missing bearer token")]
+    MissingToken,
+
+    #[error("invalid bearer token")]
+    InvalidToken,
+
+    #[error("{0} is not permitted")]
+    Forbidden(String),
+}
+
+struct JwtVerifier {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtVerifier {
+    fn new(config: &JwtAuth) -> Result<Self> {
+        let pem = config.key.as_bytes();
+        let key = match config.algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => DecodingKey::from_secret(pem),
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => DecodingKey::from_rsa_pem(pem)?,
+            Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(pem)?,
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(pem)?,
+        };
+
+        let mut validation = Validation::new(config.algorithm);
+        if let Some(ref issuer) = config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        if let Some(ref audience) = config.audience {
+            validation.set_audience(&[audience]);
+        }
+
+        Ok(Self { key, validation })
+    }
+
+    fn verify(&self, token: &str) -> Result<Caller, AuthError> {
+        // The rules are read from the claims, all other claims are ignored.
+        let data = jsonwebtoken::decode::<AuthRules>(token, &self.key, &self.validation)
+            .map_err(|_| AuthError::InvalidToken)?;
+        Ok(Caller::authenticated(data.claims))
+    }
+}
+
+/// Authenticates callers by the bearer token of their requests.
+#[derive(Clone)]
+pub struct Authenticator {
+    api_keys: Arc<[ApiKey]>,
+    jwt: Option<Arc<JwtVerifier>>,
+}
+
+impl Authenticator {
+    /// Creates an authenticator for the given configuration.
+    ///
+    /// Returns `None` if neither API keys nor JWT verification are configured.
+    pub fn new(config: &Auth) -> Result<Option<Self>> {
+        if config.api_keys.is_empty() && config.jwt.is_none() {
+            return Ok(None);
+        }
+
+        let jwt = match config.jwt {
+            Some(ref jwt) => Some(Arc::new(
+                JwtVerifier::new(jwt).context("invalid JWT verification key")?,
+            )),
+            None => None,
+        };
+
+        Ok(Some(Self {
+            api_keys: config.api_keys.clone().into(),
+            jwt,
+        }))
+    }
+
+    /// Authenticates the caller of a request by its `Authorization` header.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Caller, AuthError> {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthError::MissingToken)?;
+
+        let api_key = self
+            .api_keys
+            .iter()
+            .find(|api_key| constant_time_eq(api_key.key.as_bytes(), token.as_bytes()));
+        if let Some(api_key) = api_key {
+            return Ok(Caller::authenticated(api_key.rules.clone()));
+        }
+
+        match self.jwt {
+            Some(ref jwt) => jwt.verify(token),
+            None => Err(AuthError::InvalidToken),
+        }
+    }
+}
+
+impl fmt::Debug for Authenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authenticator")
+            .field("api_keys", &self.api_keys)
+            .field(
+                "jwt",
+                &self.jwt.as_ref().map(|jwt| jwt.validation.algorithms[0]),
+            )
+            .finish()
+    }
+}
+
+/// Compares two byte strings in time independent of their contents.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Endpoints which authenticated callers may only use if they are granted explicitly.
+const PRIVILEGED_ENDPOINTS: &[&str] = &["admin"];
+
+/// An authenticated caller, along with what it may do.
+///
+/// The default caller is not restricted at all, which applies if authentication is disabled.
+#[derive(Clone, Debug, Default)]
+pub struct Caller {
+    rules: AuthRules,
+    authenticated: bool,
+}
+
+impl Caller {
+    fn authenticated(rules: AuthRules) -> Self {
+        Self {
+            rules,
+            authenticated: true,
+        }
+    }
+
+    /// Checks that the caller may use the endpoint with the given name.
+    ///
+    /// Privileged endpoints are denied to authenticated callers unless their rules list them,
+    /// even if the rules do not restrict endpoints otherwise.
+    pub fn check_endpoint(&self, endpoint: &str) -> Result<(), AuthError> {
+        if self.authenticated && PRIVILEGED_ENDPOINTS.contains(&endpoint) {
+            let granted = self.rules.endpoints.iter().flatten().any(|e| e == endpoint);
+            if !granted {
+                return Err(AuthError::Forbidden(format!("endpoint `{}`", endpoint)));
+            }
+        }
+        check(&self.rules.endpoints, "endpoint", endpoint)
+    }
+
+    /// Checks that the caller may symbolicate in the given scope.
+    pub fn check_scope(&self, scope: &Scope) -> Result<(), AuthError> {
+        check(&self.rules.scopes, "scope", scope.as_ref())
+    }
+
+    /// Checks that the caller may send along the given sources.
+    ///
+    /// References to sources configured on the server are checked against the allowed source
+    /// ids, and the sources they resolve to against the allowed source types.
+    pub fn check_sources(
+        &self,
+        sources: &[RequestSource],
+        config: &Config,
+    ) -> Result<(), AuthError> {
+        for source in sources {
+            match source {
+                RequestSource::Config(source) => {
+                    check(&self.rules.source_types, "source type", source.type_name())?;
+                }
+                RequestSource::Ref(reference) => {
+                    check(&self.rules.source_ids, "source", reference.id.as_str())?;
+                    if reference.prefix.is_some() && self.rules.allow_prefix_override == Some(false)
+                    {
+                        return Err(AuthError::Forbidden(format!(
+                            "overriding the prefix of source `{}`",
+                            reference.id
+                        )));
+                    }
+                    // Invalid references are rejected when the sources are resolved.
+                    if let Ok(source) = config.resolve_source(reference) {
+                        check(&self.rules.source_types, "source type", source.type_name())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check(allowed: &Option<Vec<String>>, kind: &str, value: &str) -> Result<(), AuthError> {
+    match allowed {
+        Some(allowed) if !allowed.iter().any(|allowed| allowed == value) => {
+            Err(AuthError::Forbidden(format!("{} `{}`", kind, value)))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use axum::http::HeaderValue;
+    use jsonwebtoken::{EncodingKey, Header};
+    use reqwest::{Client, StatusCode};
+
+    use crate::services::Service;
+    use crate::test;
+
+    use super::*;
+
+    fn headers(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let value = HeaderValue::from_str(&format!("Bearer {}", token)).unwrap();
+        headers.insert(header::AUTHORIZATION, value);
+        headers
+    }
+
+    fn auth_config() -> Auth {
+        Auth {
+            api_keys: vec![ApiKey {
+                key: "s3cr3t".into(),
+                rules: AuthRules {
+                    scopes: Some(vec!["42".into()]),
+                    endpoints: Some(vec!["symbolicate".into(), "requests".into()]),
+                    source_types: Some(vec!["sentry".into()]),
+                    ..AuthRules::default()
+                },
+            }],
+            jwt: Some(JwtAuth {
+                algorithm: Algorithm::HS256,
+                key: "jwt-secret".into(),
+                issuer: Some("sentry".into()),
+                audience: None,
+            }),
+        }
+    }
+
+    fn jwt(claims: serde_json::Value) -> String {
+        let key = EncodingKey::from_secret(b"jwt-secret");
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &key).unwrap()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert!(Authenticator::new(&Auth::default()).unwrap().is_none());
+
+        let caller = Caller::default();
+        assert!(caller.check_endpoint("minidump").is_ok());
+        assert!(caller.check_endpoint("admin").is_ok());
+        assert!(caller.check_scope(&Scope::Global).is_ok());
+    }
+
+    #[test]
+    fn test_api_key() {
+        let auth = Authenticator::new(&auth_config()).unwrap().unwrap();
+
+        assert!(matches!(
+            auth.authenticate(&HeaderMap::new()),
+            Err(AuthError::MissingToken)
+        ));
+        assert!(matches!(
+            auth.authenticate(&headers("wrong")),
+            Err(AuthError::InvalidToken)
+        ));
+
+        let caller = auth.authenticate(&headers("s3cr3t")).unwrap();
+        assert!(caller.check_endpoint("symbolicate").is_ok());
+        assert!(caller.check_endpoint("admin").is_err());
+        assert!(caller.check_scope(&Scope::Scoped("42".into())).is_ok());
+        assert!(caller.check_scope(&Scope::Global).is_err());
+        let config = Config::default();
+        assert!(caller.check_sources(&[], &config).is_ok());
+        let local = RequestSource::Config(test::local_source());
+        assert!(caller.check_sources(&[local], &config).is_err());
+    }
+
+    #[test]
+    fn test_source_refs() {
+        let named_sources: Vec<_> = serde_json::from_value(serde_json::json!([
+            {"id": "ios", "type": "gcs", "bucket": "symbols", "private_key": "", "client_email": "", "allow_prefix_override": true},
+            {"id": "android", "type": "gcs", "bucket": "symbols", "private_key": "", "client_email": ""},
+            {"id": "private", "type": "s3", "bucket": "symbols", "region": "us-east-1", "access_key": "", "secret_key": ""},
+        ]))
+        .unwrap();
+        let config = Config {
+            named_sources: named_sources.into(),
+            ..Default::default()
+        };
+        let caller = Caller::authenticated(AuthRules {
+            source_types: Some(vec!["gcs".into()]),
+            source_ids: Some(vec!["ios".into(), "private".into()]),
+            allow_prefix_override: Some(false),
+            ..AuthRules::default()
+        });
+        let check = |source: serde_json::Value| {
+            let source: RequestSource = serde_json::from_value(source).unwrap();
+            caller.check_sources(&[source], &config)
+        };
+
+        assert!(check(serde_json::json!({"type": "ref", "id": "ios"})).is_ok());
+        // The source is not in the allowed ids.
+        assert!(check(serde_json::json!({"type": "ref", "id": "android"})).is_err());
+        // The referenced source is of a forbidden type.
+        assert!(check(serde_json::json!({"type": "ref", "id": "private"})).is_err());
+        // The caller may not override prefixes, even though the source allows it.
+        assert!(check(serde_json::json!({"type": "ref", "id": "ios", "prefix": "/16"})).is_err());
+    }
+
+    #[test]
+    fn test_jwt() {
+        let auth = Authenticator::new(&auth_config()).unwrap().unwrap();
+
+        let token = jwt(serde_json::json!({
+            "iss": "sentry",
+            "exp": now() + 60,
+            "scopes": ["42"],
+        }));
+        let caller = auth.authenticate(&headers(&token)).unwrap();
+        assert!(caller.check_endpoint("symbolicate").is_ok());
+        assert!(caller.check_scope(&Scope::Global).is_err());
+
+        // Admin endpoints must be granted explicitly.
+        assert!(caller.check_endpoint("admin").is_err());
+        let admin = jwt(serde_json::json!({
+            "iss": "sentry",
+            "exp": now() + 60,
+            "endpoints": ["admin"],
+        }));
+        let caller = auth.authenticate(&headers(&admin)).unwrap();
+        assert!(caller.check_endpoint("admin").is_ok());
+        assert!(caller.check_endpoint("symbolicate").is_err());
+
+        let expired = jwt(serde_json::json!({ "iss": "sentry", "exp": now() - 3600 }));
+        assert!(auth.authenticate(&headers(&expired)).is_err());
+
+        let wrong_issuer = jwt(serde_json::json!({ "iss": "other", "exp": now() + 60 }));
+        assert!(auth.authenticate(&headers(&wrong_issuer)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_endpoints() {
+        test::setup();
+
+        let mut config = Config {
+            auth: auth_config(),
+            ..Default::default()
+        };
+        config.metrics.prometheus = true;
+        let handle = tokio::runtime::Handle::current();
+        let service = Service::create(config, handle.clone(), handle)
+            .await
+            .unwrap();
+        let server = test::Server::with_service(service);
+
+        let client = Client::new();
+        let body = serde_json::json!({ "stacktraces": [], "modules": [] });
+
+        let response = client
+            .post(server.url("/symbolicate?scope=42"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .post(server.url("/symbolicate?scope=42"))
+            .bearer_auth("s3cr3t")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .post(server.url("/symbolicate?scope=43"))
+            .bearer_auth("s3cr3t")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = client
+            .get(server.url("/admin/requests"))
+            .bearer_auth("s3cr3t")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Metrics are tagged by scopes, so they are only served to authenticated callers.
+        let response = client.get(server.url("/metrics")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = client
+            .get(server.url("/metrics"))
+            .bearer_auth("s3cr3t")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The healthcheck is never authenticated.
+        let response = client.get(server.url("/healthcheck")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
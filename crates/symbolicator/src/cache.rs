@@ -10,13 +10,14 @@ use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, Result};
 use filetime::FileTime;
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use symbolic::common::ByteView;
 use tempfile::NamedTempFile;
 use tokio::fs::File;
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 
-use crate::config::{CacheConfig, Config};
+use crate::config::{CacheConfig, CacheConfigs, Config};
 
 /// Starting content of cache items whose writing failed.
 ///
@@ -119,12 +120,12 @@ impl CacheStatus {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FilesystemSharedCacheConfig {
     pub path: PathBuf,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GcsSharedCacheConfig {
     /// Name of the GCS bucket.
     pub bucket: String,
@@ -140,7 +141,7 @@ pub struct GcsSharedCacheConfig {
 }
 
 /// The backend to use for the shared cache.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SharedCacheBackendConfig {
     Gcs(GcsSharedCacheConfig),
@@ -152,7 +153,7 @@ pub enum SharedCacheBackendConfig {
 /// Any files not in the local cache will be looked up from here before being looked up in
 /// their original source.  Additionally derived caches are also stored in here to save
 /// computations if another symbolicator has already done the computation.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SharedCacheConfig {
     /// The number of allowed concurrent uploads to the shared cache.
     ///
@@ -246,7 +247,10 @@ pub struct Cache {
     start_time: SystemTime,
 
     /// Options intended to be user-configurable.
-    cache_config: CacheConfig,
+    ///
+    /// These are shared by all clones of the cache and may change when the configuration is
+    /// reloaded.
+    cache_config: Arc<RwLock<CacheConfig>>,
 
     /// The maximum number of lazy refreshes of this cache.
     max_lazy_refreshes: Arc<AtomicIsize>,
@@ -268,7 +272,7 @@ impl Cache {
             cache_dir,
             tmp_dir,
             start_time: SystemTime::now(),
-            cache_config,
+            cache_config: Arc::new(RwLock::new(cache_config)),
             max_lazy_refreshes,
         })
     }
@@ -285,6 +289,11 @@ impl Cache {
         self.max_lazy_refreshes.clone()
     }
 
+    /// Replaces the expiry options of this cache and all of its clones.
+    pub fn set_cache_config(&self, cache_config: CacheConfig) {
+        *self.cache_config.write() = cache_config;
+    }
+
     pub fn cleanup(&self) -> Result<()> {
         tracing::info!("Cleaning up cache: {}", self.name);
         let cache_dir = self.cache_dir.as_ref().ok_or_else(|| {
@@ -374,17 +383,17 @@ impl Cache {
 
         let bv = ByteView::open(path)?;
 
-        let expiration_strategy = expiration_strategy(&self.cache_config, &bv)?;
+        let cache_config = *self.cache_config.read();
+        let expiration_strategy = expiration_strategy(&cache_config, &bv)?;
 
         if expiration_strategy == ExpirationStrategy::Malformed {
             // Immediately expire malformed items that have been created before this process started.
             // See docstring of MALFORMED_MARKER
             let created_at = metadata.modified()?;
 
-            let retry_malformed = if let (Ok(elapsed), Some(retry_malformed_after)) = (
-                created_at.elapsed(),
-                self.cache_config.retry_malformed_after(),
-            ) {
+            let retry_malformed = if let (Ok(elapsed), Some(retry_malformed_after)) =
+                (created_at.elapsed(), cache_config.retry_malformed_after())
+            {
                 elapsed > retry_malformed_after
             } else {
                 false
@@ -397,9 +406,9 @@ impl Cache {
         }
 
         let max_mtime = if expiration_strategy == ExpirationStrategy::Negative {
-            self.cache_config.retry_misses_after()
+            cache_config.retry_misses_after()
         } else {
-            self.cache_config.max_unused_for()
+            cache_config.max_unused_for()
         };
 
         let mtime = if let Some(max_mtime) = max_mtime {
@@ -528,6 +537,7 @@ where
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct Caches {
     /// Caches for object files, used by [`crate::services::objects::ObjectsActor`].
     pub objects: Cache,
@@ -645,6 +655,29 @@ impl Caches {
         })
     }
 
+    /// Replaces the expiry options of all caches.
+    pub fn set_cache_configs(&self, config: &CacheConfigs) {
+        let Self {
+            objects,
+            object_meta,
+            auxdifs,
+            il2cpp,
+            symcaches,
+            cficaches,
+            ppdb_caches,
+            diagnostics,
+        } = self;
+
+        objects.set_cache_config(config.downloaded.into());
+        object_meta.set_cache_config(config.derived.into());
+        auxdifs.set_cache_config(config.downloaded.into());
+        il2cpp.set_cache_config(config.downloaded.into());
+        symcaches.set_cache_config(config.derived.into());
+        cficaches.set_cache_config(config.derived.into());
+        ppdb_caches.set_cache_config(config.derived.into());
+        diagnostics.set_cache_config(config.diagnostics.into());
+    }
+
     /// Clear the temporary files.
     ///
     /// We need to do this on startup of the main symbolicator process to avoid accidentally
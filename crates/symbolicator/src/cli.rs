@@ -73,7 +73,7 @@ pub fn execute() -> Result<()> {
             }
         }
     }
-    let sentry = sentry::init(sentry::ClientOptions {
+    let _sentry = sentry::init(sentry::ClientOptions {
         dsn: config.sentry_dsn.clone(),
         release: Some(env!("SYMBOLICATOR_RELEASE").into()),
         session_mode: sentry::SessionMode::Request,
@@ -81,39 +81,14 @@ pub fn execute() -> Result<()> {
         ..Default::default()
     });
 
-    logging::init_logging(&config);
-    if let Some(ref statsd) = config.metrics.statsd {
-        let mut tags = config.metrics.custom_tags.clone();
-
-        if let Some(hostname_tag) = config.metrics.hostname_tag.clone() {
-            if tags.contains_key(&hostname_tag) {
-                tracing::warn!(
-                    "tag {} defined both as hostname tag and as a custom tag",
-                    hostname_tag
-                );
-            }
-            if let Some(hostname) = hostname::get().ok().and_then(|s| s.into_string().ok()) {
-                tags.insert(hostname_tag, hostname);
-            } else {
-                tracing::error!("could not read host name");
-            }
-        };
-        if let Some(environment_tag) = config.metrics.environment_tag.clone() {
-            if tags.contains_key(&environment_tag) {
-                tracing::warn!(
-                    "tag {} defined both as environment tag and as a custom tag",
-                    environment_tag
-                );
-            }
-            if let Some(environment) = sentry.options().environment.as_ref().map(|s| s.to_string())
-            {
-                tags.insert(environment_tag, environment);
-            } else {
-                tracing::error!("environment name not available");
-            }
-        };
-
-        metrics::configure_statsd(&config.metrics.prefix, statsd, tags);
+    let _otlp = logging::init_logging(&config).context("failed to initialize tracing")?;
+    if config.metrics.statsd.is_some() || config.metrics.prometheus {
+        metrics::configure_metrics(
+            &config.metrics.prefix,
+            config.metrics.statsd.as_deref(),
+            config.metrics.prometheus,
+            metrics::tags(&config.metrics),
+        );
     }
 
     match cli.command {
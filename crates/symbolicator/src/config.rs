@@ -11,9 +11,10 @@ use sentry::types::Dsn;
 use serde::{de, Deserialize, Deserializer};
 use tracing::level_filters::LevelFilter;
 
-use symbolicator_sources::SourceConfig;
+use symbolicator_sources::{SourceConfig, SourceId};
 
 use crate::cache::SharedCacheConfig;
+use crate::types::{RequestKind, RequestSource, SourceRef, SourceRefError};
 
 /// Controls the log format
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize)]
@@ -49,7 +50,7 @@ impl Default for HTTPSConfig {
 
 /// Controls the HTTPS server setup
 #[cfg(feature = "https")]
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
 #[serde(default)]
 pub struct ServerConfig {
     /// HTTPS configuration
@@ -85,6 +86,11 @@ impl Default for Logging {
 pub struct Metrics {
     /// host/port of statsd instance
     pub statsd: Option<String>,
+    /// Whether to record metrics in-process and serve them on the `/metrics` endpoint in the
+    /// Prometheus text format.
+    ///
+    /// This can be enabled alongside or instead of `statsd`.
+    pub prometheus: bool,
     /// The prefix that should be added to all metrics.
     pub prefix: String,
     /// A tag name to report the hostname to, for each metric. Defaults to not sending such a tag.
@@ -104,6 +110,7 @@ impl Default for Metrics {
                 Ok(metrics_statsd) => Some(metrics_statsd),
                 Err(_) => None,
             },
+            prometheus: false,
             prefix: "symbolicator".into(),
             hostname_tag: None,
             environment_tag: None,
@@ -112,6 +119,158 @@ impl Default for Metrics {
     }
 }
 
+/// Controls the export of traces to an OpenTelemetry collector.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct OpenTelemetry {
+    /// URL of an OTLP/gRPC collector to export spans to.
+    ///
+    /// Traces are not exported if this is not set.
+    pub otlp_endpoint: Option<String>,
+    /// The service name reported with all spans.
+    pub service_name: String,
+    /// The ratio of traces to sample, between `0.0` and `1.0`.
+    ///
+    /// This only applies to traces started by symbolicator. Requests with an incoming
+    /// `traceparent` header follow the sampling decision of their parent.
+    pub sample_rate: f64,
+}
+
+impl Default for OpenTelemetry {
+    fn default() -> Self {
+        OpenTelemetry {
+            otlp_endpoint: None,
+            service_name: "symbolicator".into(),
+            sample_rate: 1.0,
+        }
+    }
+}
+
+/// Controls how accepted requests are scheduled across scopes.
+///
+/// Requests exceeding the running limits wait in a queue per scope.  Free slots go to the
+/// highest priority class with a waiting request, and within a class scopes take turns in
+/// proportion to their weights.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct Scheduling {
+    /// The quota of scopes which are not listed in `scopes`.
+    pub default_scope: ScopeQuota,
+    /// Quotas of individual scopes, keyed by the scope name.
+    pub scopes: BTreeMap<String, ScopeQuota>,
+    /// Priority classes of request kinds.
+    ///
+    /// Requests with a higher priority start before requests with a lower one.  Kinds which
+    /// are not listed have priority `0`.
+    pub priorities: BTreeMap<RequestKind, u32>,
+}
+
+/// The share of running slots a scope may use.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct ScopeQuota {
+    /// The relative share of running slots the scope gets while other scopes are waiting.
+    pub weight: u32,
+    /// The maximum number of requests of the scope running at the same time.
+    ///
+    /// A value of `None` indicates no limit.
+    pub max_running: Option<usize>,
+    /// The maximum number of requests of the scope waiting to run.
+    ///
+    /// Further requests of the scope are rejected.  Defaults to `0`, so that requests are
+    /// rejected as soon as they cannot run.  A value of `None` indicates no limit.
+    pub max_queued: Option<usize>,
+}
+
+impl Default for ScopeQuota {
+    fn default() -> Self {
+        ScopeQuota {
+            weight: 1,
+            max_running: None,
+            max_queued: Some(0),
+        }
+    }
+}
+
+/// Authentication of API requests.
+///
+/// Requests are not authenticated unless API keys or JWT verification are configured.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Auth {
+    /// Static API keys callers may authenticate with.
+    pub api_keys: Vec<ApiKey>,
+    /// Verification of JSON Web Tokens callers may authenticate with.
+    pub jwt: Option<JwtAuth>,
+}
+
+/// A static API key and the rules applying to its callers.
+#[derive(Clone, Deserialize)]
+pub struct ApiKey {
+    /// The key sent as bearer token.
+    pub key: String,
+    /// The rules applying to callers using this key.
+    #[serde(flatten)]
+    pub rules: AuthRules,
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("key", &"<redacted>")
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+/// Verification of JSON Web Tokens.
+///
+/// The rules applying to callers are taken from the claims of their token.
+#[derive(Clone, Deserialize)]
+pub struct JwtAuth {
+    /// The algorithm tokens are signed with.
+    pub algorithm: jsonwebtoken::Algorithm,
+    /// The secret for HMAC algorithms, or the PEM encoded public key for all others.
+    pub key: String,
+    /// The required `iss` claim, if any.
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// The required `aud` claim, if any.
+    #[serde(default)]
+    pub audience: Option<String>,
+}
+
+impl fmt::Debug for JwtAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtAuth")
+            .field("algorithm", &self.algorithm)
+            .field("key", &"<redacted>")
+            .field("issuer", &self.issuer)
+            .field("audience", &self.audience)
+            .finish()
+    }
+}
+
+/// Restricts what an authenticated caller may do.
+///
+/// Restrictions which are not set allow everything.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthRules {
+    /// The scopes the caller may symbolicate in.
+    pub scopes: Option<Vec<String>>,
+    /// The endpoints the caller may use, named by the first segment of their path.
+    pub endpoints: Option<Vec<String>>,
+    /// The types of sources the caller may send along with requests.
+    ///
+    /// This also applies to the type of configured sources referenced by id.
+    pub source_types: Option<Vec<String>>,
+    /// The ids of configured sources the caller may reference.
+    pub source_ids: Option<Vec<String>>,
+    /// Whether the caller may override the prefix of referenced sources.
+    pub allow_prefix_override: Option<bool>,
+}
+
 /// Fine-tuning downloaded cache expiry.
 ///
 /// These differ from [`DerivedCacheConfig`] in the [`Default`] implementation.
@@ -280,6 +439,12 @@ pub struct Config {
     /// Configuration for reporting metrics to a statsd instance.
     pub metrics: Metrics,
 
+    /// Configuration for exporting traces via OpenTelemetry.
+    pub opentelemetry: OpenTelemetry,
+
+    /// Authentication and authorization of API requests.
+    pub auth: Auth,
+
     #[cfg(feature = "https")]
     pub server_config: ServerConfig,
 
@@ -295,6 +460,9 @@ pub struct Config {
     /// Default list of sources and the sources used for proxy mode.
     pub sources: Arc<[SourceConfig]>,
 
+    /// Sources which requests may reference by id, without being used by default.
+    pub named_sources: Arc<[SourceConfig]>,
+
     /// Allow reserved IP addresses for requests to sources.
     pub connect_to_reserved_ips: bool,
 
@@ -328,9 +496,13 @@ pub struct Config {
 
     /// The maximum number of requests that symbolicator will process concurrently.
     ///
-    /// A value of `None` indicates no limit.
+    /// Further requests wait to run according to [`Scheduling`].  A value of `None` indicates
+    /// no limit.
     pub max_concurrent_requests: Option<usize>,
 
+    /// Scheduling of accepted requests across scopes.
+    pub scheduling: Scheduling,
+
     /// An optional shared cache between multiple symbolicators.
     ///
     /// If configured this cache location is queried whenever a cache item is not found in
@@ -345,6 +517,20 @@ pub struct Config {
     /// cached on disk. The path is created if it doesn't exist. Path must be UTF-8.
     #[serde(default)]
     pub _crash_db: Option<PathBuf>,
+
+    /// The file this configuration was read from, if any.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+
+    /// The files referenced with `file:` in the configuration.
+    ///
+    /// The secrets are resolved again whenever one of them changes.
+    #[serde(skip)]
+    pub secret_files: Vec<PathBuf>,
+
+    /// The configuration as written, before resolving secrets.
+    #[serde(skip)]
+    pub unresolved: Option<serde_yaml::Value>,
 }
 
 impl Config {
@@ -362,6 +548,120 @@ impl Config {
     pub fn default_sources(&self) -> Arc<[SourceConfig]> {
         self.sources.clone()
     }
+
+    /// Resolves the sources sent along with a request.
+    ///
+    /// References are looked up in `named_sources` first and then in `sources`.
+    pub fn resolve_sources(
+        &self,
+        sources: Vec<RequestSource>,
+    ) -> Result<Arc<[SourceConfig]>, SourceRefError> {
+        sources
+            .into_iter()
+            .map(|source| match source {
+                RequestSource::Config(config) => Ok(config),
+                RequestSource::Ref(reference) => self.resolve_source(&reference),
+            })
+            .collect()
+    }
+
+    /// Returns the settings which differ in `other` and only take effect after a restart.
+    ///
+    /// All other settings can be changed by reloading the configuration.
+    pub fn changes_requiring_restart(&self, other: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        let mut check = |name: &'static str, unchanged: bool| {
+            if !unchanged {
+                changed.push(name);
+            }
+        };
+
+        check("cache_dir", self.cache_dir == other.cache_dir);
+        check("bind", self.bind == other.bind);
+        #[cfg(feature = "https")]
+        {
+            check("bind_https", self.bind_https == other.bind_https);
+            check("server_config", self.server_config == other.server_config);
+        }
+        check(
+            "logging.format",
+            self.logging.format == other.logging.format,
+        );
+        check(
+            "logging.enable_backtraces",
+            self.logging.enable_backtraces == other.logging.enable_backtraces,
+        );
+        check(
+            "metrics.statsd",
+            self.metrics.statsd == other.metrics.statsd,
+        );
+        check(
+            "metrics.prometheus",
+            self.metrics.prometheus == other.metrics.prometheus,
+        );
+        check(
+            "metrics.prefix",
+            self.metrics.prefix == other.metrics.prefix,
+        );
+        check(
+            "opentelemetry",
+            self.opentelemetry.otlp_endpoint == other.opentelemetry.otlp_endpoint
+                && self.opentelemetry.service_name == other.opentelemetry.service_name
+                && self.opentelemetry.sample_rate == other.opentelemetry.sample_rate,
+        );
+        check("sentry_dsn", self.sentry_dsn == other.sentry_dsn);
+        check(
+            "caches.downloaded.max_lazy_redownloads",
+            self.caches.downloaded.max_lazy_redownloads
+                == other.caches.downloaded.max_lazy_redownloads,
+        );
+        check(
+            "caches.derived.max_lazy_recomputations",
+            self.caches.derived.max_lazy_recomputations
+                == other.caches.derived.max_lazy_recomputations,
+        );
+        check(
+            "connect_to_reserved_ips",
+            self.connect_to_reserved_ips == other.connect_to_reserved_ips,
+        );
+        check(
+            "max_download_timeout",
+            self.max_download_timeout == other.max_download_timeout,
+        );
+        check(
+            "connect_timeout",
+            self.connect_timeout == other.connect_timeout,
+        );
+        check(
+            "streaming_timeout",
+            self.streaming_timeout == other.streaming_timeout,
+        );
+        check(
+            "processing_pool_size",
+            self.processing_pool_size == other.processing_pool_size,
+        );
+        check(
+            "max_concurrent_requests",
+            self.max_concurrent_requests == other.max_concurrent_requests,
+        );
+        check("scheduling", self.scheduling == other.scheduling);
+        check("_crash_db", self._crash_db == other._crash_db);
+
+        changed
+    }
+
+    /// Resolves a reference to a configured source, applying its overrides.
+    pub fn resolve_source(&self, reference: &SourceRef) -> Result<SourceConfig, SourceRefError> {
+        reference.apply(self.named_source(&reference.id)?)
+    }
+
+    fn named_source(&self, id: &SourceId) -> Result<&SourceConfig, SourceRefError> {
+        self.named_sources
+            .iter()
+            .chain(self.sources.iter())
+            .find(|source| source.id() == id)
+            .ok_or_else(|| SourceRefError::Unknown(id.clone()))
+    }
 }
 
 /// Checks if we are running in docker.
@@ -406,10 +706,13 @@ impl Default for Config {
             #[cfg(feature = "https")]
             server_config: ServerConfig::default(),
             metrics: Metrics::default(),
+            opentelemetry: OpenTelemetry::default(),
+            auth: Auth::default(),
             sentry_dsn: None,
             caches: CacheConfigs::default(),
             symstore_proxy: true,
             sources: Arc::from(vec![]),
+            named_sources: Arc::from(vec![]),
             connect_to_reserved_ips: false,
             processing_pool_size: num_cpus::get(),
             // Allow a 4MB/s connection to download 2GB without timing out
@@ -418,8 +721,12 @@ impl Default for Config {
             // Allow a 4MB/s connection to download 1GB without timing out
             streaming_timeout: Duration::from_secs(250),
             max_concurrent_requests: Some(120),
+            scheduling: Scheduling::default(),
             shared_cache: None,
             _crash_db: None,
+            path: None,
+            secret_files: Vec::new(),
+            unresolved: None,
         }
     }
 }
@@ -427,16 +734,145 @@ impl Default for Config {
 impl Config {
     pub fn get(path: Option<&Path>) -> Result<Self> {
         match path {
-            Some(path) => Self::from_reader(
-                fs::File::open(path).context("failed to open configuration file")?,
-            ),
+            Some(path) => {
+                let file = fs::File::open(path).context("failed to open configuration file")?;
+                let value = serde_yaml::from_reader(file).context("failed to parse YAML")?;
+                let mut config = Self::from_value(value, path.parent().unwrap_or(Path::new("")))?;
+                config.path = Some(path.to_owned());
+                Ok(config)
+            }
             None => Ok(Config::default()),
         }
     }
 
+    /// Resolves the secrets of this configuration again, without reading the file again.
+    ///
+    /// All other settings of the returned configuration are the same as in this one.
+    pub fn resolve_secrets(&self) -> Result<Self> {
+        let base_dir = self.path.as_deref().and_then(Path::parent);
+        let mut config = match self.unresolved {
+            Some(ref unresolved) => {
+                Self::from_value(unresolved.clone(), base_dir.unwrap_or(Path::new("")))?
+            }
+            None => Config::default(),
+        };
+        config.path = self.path.clone();
+        Ok(config)
+    }
+
+    #[cfg(test)]
     fn from_reader(reader: impl std::io::Read) -> Result<Self> {
-        serde_yaml::from_reader(reader).context("failed to parse YAML")
+        let value = serde_yaml::from_reader(reader).context("failed to parse YAML")?;
+        Self::from_value(value, Path::new(""))
+    }
+
+    /// Creates the configuration from YAML, resolving secrets in files relative to `base_dir`.
+    fn from_value(unresolved: serde_yaml::Value, base_dir: &Path) -> Result<Self> {
+        let mut value = unresolved.clone();
+        let mut secret_files = Vec::new();
+
+        // The DSN only takes effect after a restart, so it cannot come from a rotating file.
+        let dsn_key = serde_yaml::Value::from("sentry_dsn");
+        let dsn = value.as_mapping_mut().and_then(|m| m.remove(&dsn_key));
+        expand_secrets(&mut value, base_dir, &mut secret_files)?;
+        if let Some(mut dsn) = dsn {
+            if let serde_yaml::Value::String(ref mut string) = dsn {
+                *string = expand_env_vars(string)?;
+                if string.starts_with("file:") {
+                    anyhow::bail!("sentry_dsn cannot be read from a file");
+                }
+            }
+            if let Some(mapping) = value.as_mapping_mut() {
+                mapping.insert(dsn_key, dsn);
+            }
+        }
+
+        let mut config: Self = serde_yaml::from_value(value).context("failed to parse YAML")?;
+        config.secret_files = secret_files;
+        config.unresolved = Some(unresolved);
+        Ok(config)
+    }
+}
+
+/// Expands references to secrets in all strings of the configuration.
+///
+/// `${NAME}` anywhere in a string is replaced with the value of the environment variable `NAME`.
+/// Afterwards, a string of the form `file:<path>` is replaced with the contents of that file,
+/// without trailing newlines.  Relative paths are resolved against `base_dir`, and the paths of
+/// all read files are added to `secret_files`.
+///
+/// `$${` is replaced with a literal `${`, and a leading `$file:` with a literal `file:`.
+fn expand_secrets(
+    value: &mut serde_yaml::Value,
+    base_dir: &Path,
+    secret_files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    match value {
+        serde_yaml::Value::String(string) => {
+            *string = if string.starts_with("$file:") {
+                expand_env_vars(&string[1..])?
+            } else {
+                let expanded = expand_env_vars(string)?;
+                match expanded.strip_prefix("file:") {
+                    Some(path) => {
+                        let path = base_dir.join(path);
+                        let contents = fs::read_to_string(&path).with_context(|| {
+                            format!("failed to read secret file `{}`", path.display())
+                        })?;
+                        secret_files.push(path);
+                        contents.trim_end_matches(&['\r', '\n'][..]).to_owned()
+                    }
+                    None => expanded,
+                }
+            };
+        }
+        serde_yaml::Value::Sequence(values) => {
+            for value in values {
+                expand_secrets(value, base_dir, secret_files)?;
+            }
+        }
+        serde_yaml::Value::Mapping(mapping) => {
+            for (_, value) in mapping.iter_mut() {
+                expand_secrets(value, base_dir, secret_files)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Replaces all `${NAME}` in the string with the value of the environment variable `NAME`.
+///
+/// `$${` is replaced with a literal `${`.
+fn expand_env_vars(string: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(string.len());
+    let mut rest = string;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = rest.strip_prefix("${") {
+            let end = reference
+                .find('}')
+                .with_context(|| format!("unterminated environment variable in `{}`", string))?;
+            let name = &reference[..end];
+            let value = env::var(name)
+                .with_context(|| format!("environment variable `{}` is not set", name))?;
+            expanded.push_str(&value);
+            rest = &reference[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = &rest[1..];
+        }
     }
+
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
 #[derive(Debug)]
@@ -574,6 +1010,222 @@ mod tests {
         assert_eq!(cfg.streaming_timeout, Duration::from_secs(0));
     }
 
+    #[test]
+    fn test_scheduling() {
+        let yaml = r#"
+            scheduling:
+              default_scope:
+                max_running: 8
+                max_queued: 16
+              scopes:
+                "42":
+                  weight: 2
+              priorities:
+                symbolicate: 1
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        let scheduling = cfg.scheduling;
+        assert_eq!(scheduling.default_scope.weight, 1);
+        assert_eq!(scheduling.default_scope.max_running, Some(8));
+        assert_eq!(scheduling.default_scope.max_queued, Some(16));
+        assert_eq!(
+            scheduling.scopes["42"],
+            ScopeQuota {
+                weight: 2,
+                max_running: None,
+                max_queued: Some(0),
+            }
+        );
+        assert_eq!(scheduling.priorities[&RequestKind::Symbolicate], 1);
+    }
+
+    #[test]
+    fn test_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("secret-key");
+        fs::write(&key_path, "s3cr3t\n").unwrap();
+        env::set_var("SYMBOLICATOR_TEST_SECRETS_DIR", dir.path());
+        env::set_var("SYMBOLICATOR_TEST_BUCKET", "symbols");
+
+        let yaml = r#"
+            sources:
+              - id: private
+                type: s3
+                bucket: "${SYMBOLICATOR_TEST_BUCKET}"
+                prefix: "/${SYMBOLICATOR_TEST_BUCKET}/ios"
+                region: us-east-1
+                access_key: AKIAEXAMPLE
+                secret_key: "file:${SYMBOLICATOR_TEST_SECRETS_DIR}/secret-key"
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        match cfg.sources[0] {
+            SourceConfig::S3(ref source) => {
+                assert_eq!(source.bucket, "symbols");
+                assert_eq!(source.prefix, "/symbols/ios");
+                assert_eq!(source.source_key.secret_key, "s3cr3t");
+            }
+            ref source => panic!("unexpected source: {:?}", source),
+        }
+        assert_eq!(cfg.secret_files, [key_path]);
+
+        let yaml = r#"
+            sentry_dsn: "${SYMBOLICATOR_TEST_NOT_SET}"
+        "#;
+        let error = Config::from_reader(yaml.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("SYMBOLICATOR_TEST_NOT_SET"));
+    }
+
+    #[test]
+    fn test_secrets_escaped() {
+        env::set_var("SYMBOLICATOR_TEST_ESCAPED", "expanded");
+
+        let yaml = r#"
+            sources:
+              - id: escaped
+                type: http
+                url: "https://example.com/"
+                headers:
+                  x-env: "$${SYMBOLICATOR_TEST_ESCAPED}/${SYMBOLICATOR_TEST_ESCAPED}/$$"
+                  x-file: "$file:/does/not/exist"
+                  x-dollar: "$file:$${SYMBOLICATOR_TEST_ESCAPED}"
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        match cfg.sources[0] {
+            SourceConfig::Http(ref source) => {
+                assert_eq!(
+                    source.headers["x-env"],
+                    "${SYMBOLICATOR_TEST_ESCAPED}/expanded/$$"
+                );
+                assert_eq!(source.headers["x-file"], "file:/does/not/exist");
+                assert_eq!(
+                    source.headers["x-dollar"],
+                    "file:${SYMBOLICATOR_TEST_ESCAPED}"
+                );
+            }
+            ref source => panic!("unexpected source: {:?}", source),
+        }
+        assert!(cfg.secret_files.is_empty());
+    }
+
+    #[test]
+    fn test_secrets_relative_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("secrets")).unwrap();
+        let token_path = dir.path().join("secrets").join("token");
+        fs::write(&token_path, "t0ken").unwrap();
+        let config_path = dir.path().join("config.yml");
+        let yaml = r#"
+            sources:
+              - id: private
+                type: http
+                url: "https://example.com/"
+                headers:
+                  authorization: "file:secrets/token"
+        "#;
+        fs::write(&config_path, yaml).unwrap();
+
+        // Relative paths are resolved against the directory of the configuration file.
+        let cfg = Config::get(Some(&config_path)).unwrap();
+        match cfg.sources[0] {
+            SourceConfig::Http(ref source) => {
+                assert_eq!(source.headers["authorization"], "t0ken");
+            }
+            ref source => panic!("unexpected source: {:?}", source),
+        }
+        assert_eq!(cfg.secret_files, vec![token_path.clone()]);
+
+        fs::write(&token_path, "n3w").unwrap();
+        let resolved = cfg.resolve_secrets().unwrap();
+        match resolved.sources[0] {
+            SourceConfig::Http(ref source) => {
+                assert_eq!(source.headers["authorization"], "n3w");
+            }
+            ref source => panic!("unexpected source: {:?}", source),
+        }
+    }
+
+    #[test]
+    fn test_sentry_dsn_secret() {
+        env::set_var("SYMBOLICATOR_TEST_DSN", "https://key@sentry.example.com/42");
+
+        let yaml = r#"
+            sentry_dsn: "${SYMBOLICATOR_TEST_DSN}"
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        assert_eq!(cfg.sentry_dsn.unwrap().public_key(), "key");
+        assert!(cfg.secret_files.is_empty());
+
+        // The DSN only takes effect after a restart, so it is never read from a file.
+        let dir = tempfile::tempdir().unwrap();
+        let dsn_path = dir.path().join("dsn");
+        fs::write(&dsn_path, "https://key@sentry.example.com/42").unwrap();
+        let yaml = format!("sentry_dsn: \"file:{}\"", dsn_path.display());
+        let error = Config::from_reader(yaml.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("sentry_dsn"), "{}", error);
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let token_path = dir.path().join("token");
+        fs::write(&token_path, "old").unwrap();
+
+        let yaml = format!(
+            r#"
+            processing_pool_size: 3
+            sources:
+              - id: private
+                type: http
+                url: "https://example.com/"
+                headers:
+                  authorization: "file:{}"
+            "#,
+            token_path.display()
+        );
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        let authorization = |cfg: &Config| match cfg.sources[0] {
+            SourceConfig::Http(ref source) => source.headers["authorization"].clone(),
+            ref source => panic!("unexpected source: {:?}", source),
+        };
+        assert_eq!(authorization(&cfg), "old");
+
+        fs::write(&token_path, "new").unwrap();
+        let resolved = cfg.resolve_secrets().unwrap();
+        assert_eq!(authorization(&resolved), "new");
+        assert_eq!(resolved.processing_pool_size, 3);
+        assert_eq!(resolved.secret_files, [token_path]);
+    }
+
+    #[test]
+    fn test_changes_requiring_restart() {
+        let current = Config::from_reader("bind: 127.0.0.1:3021".as_bytes()).unwrap();
+
+        let yaml = r#"
+            bind: 127.0.0.1:3021
+            logging:
+              level: debug
+            metrics:
+              custom_tags:
+                region: eu
+            caches:
+              downloaded:
+                max_unused_for: 1h
+        "#;
+        let reloadable = Config::from_reader(yaml.as_bytes()).unwrap();
+        assert!(current.changes_requiring_restart(&reloadable).is_empty());
+
+        let yaml = r#"
+            bind: 0.0.0.0:3021
+            cache_dir: /tmp/symbolicator
+            processing_pool_size: 1000
+        "#;
+        let restart = Config::from_reader(yaml.as_bytes()).unwrap();
+        assert_eq!(
+            current.changes_requiring_restart(&restart),
+            ["cache_dir", "bind", "processing_pool_size"]
+        );
+    }
+
     #[test]
     fn test_unknown_fields() {
         // Unknown fields should not cause failure
@@ -0,0 +1,164 @@
+use axum::extract;
+use axum::http::StatusCode;
+use axum::response::Json;
+
+use crate::auth::Caller;
+use crate::endpoints::ResponseError;
+use crate::services::Service;
+use crate::types::InFlightRequest;
+
+/// Lists all symbolication requests that are currently being processed, oldest first.
+///
+/// Only requests in scopes the caller may access are listed.
+pub async fn list_requests(
+    extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
+) -> Json<Vec<InFlightRequest>> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("GET /admin/requests"));
+    });
+
+    let mut requests = state.symbolication().in_flight_requests();
+    requests.retain(|request| caller.check_scope(&request.scope).is_ok());
+    Json(requests)
+}
+
+/// Reloads the configuration file, responding with `204 No Content` once it is applied.
+pub async fn reload_config(
+    extract::Extension(state): extract::Extension<Service>,
+) -> Result<StatusCode, ResponseError> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("POST /admin/reload"));
+    });
+
+    state.reload_config().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use reqwest::{Client, StatusCode};
+
+    use crate::config::{ApiKey, Auth, AuthRules, Config};
+    use crate::services::Service;
+    use crate::test;
+    use crate::types::{InFlightRequest, Scope};
+
+    #[tokio::test]
+    async fn test_list_requests() {
+        test::setup();
+
+        let service = test::default_service().await;
+        let server = test::Server::with_service(service);
+
+        let response = Client::new()
+            .get(server.url("/admin/requests"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let requests: Vec<InFlightRequest> = response.json().await.unwrap();
+        assert!(requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_requests_scoped() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let config = Config {
+            cache_dir: Some(cache_dir.path().to_owned()),
+            connect_to_reserved_ips: true,
+            auth: Auth {
+                api_keys: vec![ApiKey {
+                    key: "admin-42".into(),
+                    rules: AuthRules {
+                        scopes: Some(vec!["42".into()]),
+                        endpoints: Some(vec!["admin".into()]),
+                        ..AuthRules::default()
+                    },
+                }],
+                jwt: None,
+            },
+            ..Default::default()
+        };
+        let handle = tokio::runtime::Handle::current();
+        let service = Service::create(config, handle.clone(), handle)
+            .await
+            .unwrap();
+
+        let symbol_server = test::FailingSymbolServer::new();
+        for scope in ["42", "43"] {
+            let request = test::symbolication_request(
+                Scope::Scoped(scope.into()),
+                vec![symbol_server.pending_source.clone()],
+            );
+            service
+                .symbolication()
+                .symbolicate_stacktraces(request)
+                .unwrap();
+        }
+
+        let server = test::Server::with_service(service);
+        let response = Client::new()
+            .get(server.url("/admin/requests"))
+            .bearer_auth("admin-42")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let requests: Vec<InFlightRequest> = response.json().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].scope, Scope::Scoped("42".into()));
+    }
+
+    #[tokio::test]
+    async fn test_reload_config() {
+        test::setup();
+
+        let dir = test::tempdir();
+        let path = dir.path().join("config.yml");
+        let write_config = |yaml: &str| {
+            let yaml = format!("cache_dir: {}\n{}", dir.path().display(), yaml);
+            fs::write(&path, yaml).unwrap();
+        };
+
+        write_config("symstore_proxy: false");
+        let config = Config::get(Some(&path)).unwrap();
+        let handle = tokio::runtime::Handle::current();
+        let service = Service::create(config, handle.clone(), handle)
+            .await
+            .unwrap();
+        let server = test::Server::with_service(service.clone());
+        let client = Client::new();
+
+        write_config("symstore_proxy: true\ncaches:\n  downloaded:\n    max_unused_for: 1h");
+        let response = client
+            .post(server.url("/admin/reload"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(service.config().symstore_proxy);
+
+        // Changing the bind address requires a restart, so the whole change is rejected.
+        write_config("symstore_proxy: false\nbind: 0.0.0.0:1234");
+        let response = client
+            .post(server.url("/admin/reload"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = response.text().await.unwrap();
+        assert!(
+            body.contains("changing bind requires a restart"),
+            "{}",
+            body
+        );
+        assert!(service.config().symstore_proxy);
+    }
+}
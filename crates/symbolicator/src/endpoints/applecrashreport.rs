@@ -3,9 +3,10 @@ use axum::http::StatusCode;
 use axum::response::Json;
 use tokio::fs::File;
 
+use crate::auth::Caller;
 use crate::endpoints::symbolicate::SymbolicationRequestQueryParams;
 use crate::services::Service;
-use crate::types::{RequestOptions, SymbolicationResponse};
+use crate::types::{RequestOptions, RequestSource, SymbolicationResponse};
 use crate::utils::sentry::ConfigureScope;
 
 use super::multipart::{read_multipart_data, stream_multipart_file};
@@ -13,12 +14,14 @@ use super::ResponseError;
 
 pub async fn handle_apple_crash_report_request(
     extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
     extract::Query(params): extract::Query<SymbolicationRequestQueryParams>,
     mut multipart: extract::Multipart,
 ) -> Result<Json<SymbolicationResponse>, ResponseError> {
     sentry::start_session();
 
     params.configure_scope();
+    caller.check_scope(&params.scope)?;
 
     let mut report = None;
     let mut sources = state.config().default_sources();
@@ -33,7 +36,10 @@ pub async fn handle_apple_crash_report_request(
             }
             Some("sources") => {
                 let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
-                sources = serde_json::from_slice(&data)?;
+                let requested: Vec<RequestSource> = serde_json::from_slice(&data)?;
+                let config = state.config();
+                caller.check_sources(&requested, &config)?;
+                sources = config.resolve_sources(requested)?;
             }
             Some("options") => {
                 let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
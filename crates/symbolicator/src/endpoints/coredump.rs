@@ -0,0 +1,124 @@
+use axum::extract;
+use axum::http::StatusCode;
+use axum::response::Json;
+use tokio::fs::File;
+
+use crate::auth::Caller;
+use crate::endpoints::symbolicate::SymbolicationRequestQueryParams;
+use crate::services::Service;
+use crate::types::{RequestOptions, RequestSource, SymbolicationResponse};
+use crate::utils::sentry::ConfigureScope;
+
+use super::multipart::{read_multipart_data, stream_multipart_file};
+use super::ResponseError;
+
+pub async fn handle_coredump_request(
+    extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
+    extract::Query(params): extract::Query<SymbolicationRequestQueryParams>,
+    mut multipart: extract::Multipart,
+) -> Result<Json<SymbolicationResponse>, ResponseError> {
+    sentry::start_session();
+
+    params.configure_scope();
+    caller.check_scope(&params.scope)?;
+
+    let mut coredump = None;
+    let mut sources = state.config().default_sources();
+    let mut options = RequestOptions::default();
+
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("upload_file_coredump") => {
+                let mut coredump_file = tempfile::Builder::new();
+                coredump_file.prefix("coredump").suffix(".core");
+                let coredump_file = if let Some(tmp_dir) = state.config().cache_dir("tmp") {
+                    coredump_file.tempfile_in(tmp_dir)
+                } else {
+                    coredump_file.tempfile()
+                }?;
+                let (file, temp_path) = coredump_file.into_parts();
+                let mut file = File::from_std(file);
+                stream_multipart_file(field, &mut file).await?;
+                coredump = Some(temp_path)
+            }
+            Some("sources") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                let requested: Vec<RequestSource> = serde_json::from_slice(&data)?;
+                let config = state.config();
+                caller.check_sources(&requested, &config)?;
+                sources = config.resolve_sources(requested)?;
+            }
+            Some("options") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                options = serde_json::from_slice(&data)?;
+            }
+            _ => (), // Always ignore unknown fields.
+        }
+    }
+
+    let coredump_file = coredump.ok_or((StatusCode::BAD_REQUEST, "missing coredump"))?;
+
+    let symbolication = state.symbolication();
+    let request_id =
+        symbolication.process_coredump(params.scope, coredump_file, sources, options)?;
+
+    match symbolication.get_response(request_id, params.timeout).await {
+        Some(response) => Ok(Json(response)),
+        None => Err("symbolication request did not start".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{multipart, Client, StatusCode};
+
+    use crate::test;
+    use crate::types::SymbolicationResponse;
+
+    #[tokio::test]
+    async fn test_missing_coredump() {
+        test::setup();
+
+        let service = test::default_service().await;
+        let server = test::Server::with_service(service);
+
+        let form = multipart::Form::new().text("sources", "[]");
+
+        let response = Client::new()
+            .post(server.url("/coredump"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_coredump() {
+        test::setup();
+
+        let service = test::default_service().await;
+        let server = test::Server::with_service(service);
+
+        let file_part = multipart::Part::bytes(b"not a core".to_vec()).file_name("core");
+
+        let form = multipart::Form::new()
+            .part("upload_file_coredump", file_part)
+            .text("sources", "[]");
+
+        let response = Client::new()
+            .post(server.url("/coredump"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.text().await.unwrap();
+        let response = serde_json::from_str::<SymbolicationResponse>(&body).unwrap();
+        assert!(matches!(response, SymbolicationResponse::Failed { .. }));
+    }
+}
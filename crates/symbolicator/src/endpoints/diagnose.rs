@@ -0,0 +1,131 @@
+use axum::extract;
+use axum::response::Json;
+use serde::Deserialize;
+
+use crate::auth::Caller;
+use crate::services::symbolication::DiagnoseModule;
+use crate::services::Service;
+use crate::types::{ModuleDiagnostics, RawObjectInfo, RequestSource, Scope};
+
+use super::ResponseError;
+
+/// Query parameters of the diagnose request.
+#[derive(Deserialize)]
+pub struct DiagnoseRequestQueryParams {
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+/// JSON body of the diagnose request.
+#[derive(Deserialize)]
+pub struct DiagnoseRequestBody {
+    #[serde(default)]
+    pub sources: Option<Vec<RequestSource>>,
+    pub module: RawObjectInfo,
+    #[serde(default)]
+    pub bypass_negative_cache: bool,
+}
+
+pub async fn diagnose_module(
+    extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
+    extract::Query(params): extract::Query<DiagnoseRequestQueryParams>,
+    extract::ContentLengthLimit(extract::Json(body)): extract::ContentLengthLimit<
+        extract::Json<DiagnoseRequestBody>,
+        { 1024 * 1024 }, // ~1MB
+    >,
+) -> Result<Json<ModuleDiagnostics>, ResponseError> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("POST /diagnose"));
+        scope.set_tag("request.scope", &params.scope);
+    });
+    caller.check_scope(&params.scope)?;
+
+    let sources = match body.sources {
+        Some(sources) => {
+            let config = state.config();
+            caller.check_sources(&sources, &config)?;
+            config.resolve_sources(sources)?
+        }
+        None => state.config().default_sources(),
+    };
+
+    let diagnostics = state
+        .symbolication()
+        .diagnose_module(DiagnoseModule {
+            scope: params.scope,
+            sources,
+            module: body.module,
+            bypass_negative_cache: body.bypass_negative_cache,
+        })
+        .await;
+
+    Ok(Json(diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, StatusCode};
+    use serde_json::{json, Value};
+
+    use crate::config::Config;
+    use crate::services::Service;
+    use crate::test;
+    use crate::types::{ModuleDiagnostics, ObjectDownloadInfo, ObjectFileStatus, ProbeCacheStatus};
+
+    async fn diagnose(server: &test::Server, body: &Value) -> ModuleDiagnostics {
+        let response = Client::new()
+            .post(server.url("/diagnose"))
+            .json(body)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_diagnose() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let config = Config {
+            cache_dir: Some(cache_dir.path().to_owned()),
+            ..Default::default()
+        };
+        let handle = tokio::runtime::Handle::current();
+        let service = Service::create(config, handle.clone(), handle)
+            .await
+            .unwrap();
+        let server = test::Server::with_service(service);
+
+        let body = json!({
+            "sources": [test::local_source()],
+            "module": {
+                "type": "macho",
+                "debug_id": "502fc0a5-1ec1-3e47-9998-684fa139dca7",
+                "image_addr": "0x100000000",
+                "image_size": 4096,
+            },
+        });
+
+        let diagnostics = diagnose(&server, &body).await;
+        assert_eq!(diagnostics.debug_status, ObjectFileStatus::Found);
+        assert_eq!(diagnostics.probes.len(), 1);
+
+        let found = diagnostics.probes[0]
+            .locations
+            .iter()
+            .find(|probe| matches!(probe.download, ObjectDownloadInfo::Ok { .. }))
+            .unwrap();
+        assert_eq!(found.cache, ProbeCacheStatus::Miss);
+
+        // Symbolication has fetched the module since, so all locations are cached now.
+        let diagnostics = diagnose(&server, &body).await;
+        assert!(diagnostics.probes[0]
+            .locations
+            .iter()
+            .all(|probe| probe.cache == ProbeCacheStatus::Hit));
+    }
+}
@@ -5,7 +5,10 @@ use axum::Json;
 use sentry::integrations::anyhow::capture_anyhow;
 use serde::{Deserialize, Serialize};
 
+use crate::auth::AuthError;
 use crate::services::symbolication::MaxRequestsError;
+use crate::services::ReloadError;
+use crate::types::SourceRefError;
 
 #[derive(Debug)]
 pub struct ResponseError {
@@ -40,6 +43,41 @@ impl From<MaxRequestsError> for ResponseError {
     }
 }
 
+impl From<SourceRefError> for ResponseError {
+    fn from(err: SourceRefError) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            err: err.into(),
+        }
+    }
+}
+
+impl From<AuthError> for ResponseError {
+    fn from(err: AuthError) -> Self {
+        let status = match err {
+            AuthError::MissingToken | AuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            AuthError::Forbidden(_) => StatusCode::FORBIDDEN,
+        };
+        Self {
+            status,
+            err: err.into(),
+        }
+    }
+}
+
+impl From<ReloadError> for ResponseError {
+    fn from(err: ReloadError) -> Self {
+        let status = match err {
+            ReloadError::NoConfigFile | ReloadError::RestartRequired(_) => StatusCode::CONFLICT,
+            ReloadError::Invalid(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self {
+            status,
+            err: err.into(),
+        }
+    }
+}
+
 impl From<&'static str> for ResponseError {
     fn from(msg: &'static str) -> Self {
         Self {
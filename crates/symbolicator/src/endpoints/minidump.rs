@@ -4,9 +4,10 @@ use axum::response::Json;
 use symbolic::common::ByteView;
 use tokio::fs::File;
 
+use crate::auth::Caller;
 use crate::endpoints::symbolicate::SymbolicationRequestQueryParams;
 use crate::services::Service;
-use crate::types::{RequestOptions, SymbolicationResponse};
+use crate::types::{RequestOptions, RequestSource, SymbolicationResponse};
 use crate::utils::sentry::ConfigureScope;
 
 use super::multipart::{read_multipart_data, stream_multipart_file};
@@ -14,12 +15,14 @@ use super::ResponseError;
 
 pub async fn handle_minidump_request(
     extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
     extract::Query(params): extract::Query<SymbolicationRequestQueryParams>,
     mut multipart: extract::Multipart,
 ) -> Result<Json<SymbolicationResponse>, ResponseError> {
     sentry::start_session();
 
     params.configure_scope();
+    caller.check_scope(&params.scope)?;
 
     let mut minidump = None;
     let mut sources = state.config().default_sources();
@@ -42,7 +45,10 @@ pub async fn handle_minidump_request(
             }
             Some("sources") => {
                 let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
-                sources = serde_json::from_slice(&data)?;
+                let requested: Vec<RequestSource> = serde_json::from_slice(&data)?;
+                let config = state.config();
+                caller.check_sources(&requested, &config)?;
+                sources = config.resolve_sources(requested)?;
             }
             Some("options") => {
                 let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
@@ -1,13 +1,22 @@
+use axum::body::Body;
 use axum::extract::DefaultBodyLimit;
+use axum::http::{header, Request};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use axum::Router;
 use sentry_tower::{NewSentryLayer, SentryHttpLayer};
 use tower::ServiceBuilder;
 
-use crate::metrics::MetricsLayer;
+use crate::auth::Caller;
+use crate::metrics::{self, MetricsLayer};
 use crate::services::Service;
+use crate::telemetry::TraceContextLayer;
 
+mod admin;
 mod applecrashreport;
+mod coredump;
+mod diagnose;
 mod error;
 mod minidump;
 mod multipart;
@@ -17,10 +26,13 @@ mod symbolicate;
 
 pub use error::ResponseError;
 
+use self::admin::{list_requests as admin_requests, reload_config as admin_reload};
+use self::coredump::handle_coredump_request as coredump;
+use self::diagnose::diagnose_module as diagnose;
 use self::minidump::handle_minidump_request as minidump;
 use applecrashreport::handle_apple_crash_report_request as applecrashreport;
 use proxy::proxy_symstore_request as proxy;
-use requests::poll_request as requests;
+use requests::{cancel_request as cancel, poll_request as requests};
 use symbolicate::symbolicate_frames as symbolicate;
 
 pub async fn healthcheck() -> &'static str {
@@ -28,24 +40,79 @@ pub async fn healthcheck() -> &'static str {
     "ok"
 }
 
+/// Serves all metrics recorded so far in the Prometheus text format.
+pub async fn prometheus_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::prometheus::render(),
+    )
+}
+
+/// Authenticates the caller of a request and checks that it may use the requested endpoint.
+///
+/// The [`Caller`] is added to the request extensions for endpoints to check the scope and
+/// sources of the request.  If authentication is disabled, the caller is unrestricted.
+async fn authenticate(
+    service: Service,
+    mut request: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, ResponseError> {
+    // The authenticator is looked up for every request, since it changes on config reloads.
+    let caller = match service.authenticator() {
+        Some(auth) => auth.authenticate(request.headers())?,
+        None => Caller::default(),
+    };
+
+    // Endpoints are named by the first segment of their path.
+    let path = request.uri().path().trim_start_matches('/');
+    let endpoint = path.split('/').next().unwrap_or_default();
+    caller.check_endpoint(endpoint)?;
+
+    request.extensions_mut().insert(caller);
+    Ok(next.run(request).await)
+}
+
 pub fn create_app(service: Service) -> Router {
+    let prometheus = service.config().metrics.prometheus;
+    let auth_service = service.clone();
+    let metrics_auth_service = service.clone();
+
     // The layers here go "top to bottom" according to the reading order here.
     let layer = ServiceBuilder::new()
         .layer(axum::extract::Extension(service))
         .layer(NewSentryLayer::new_from_top())
         .layer(SentryHttpLayer::with_transaction())
+        .layer(TraceContextLayer)
         .layer(MetricsLayer)
+        .layer(middleware::from_fn(move |request, next| {
+            authenticate(auth_service.clone(), request, next)
+        }))
         .layer(DefaultBodyLimit::disable());
     // XXX: Adding a limit would lead to a confusing trait error that I don't know how to solve:
     // > the trait `tower_service::Service<axum::http::Request<http_body::limited::Limited<_>>>` is not implemented for `Route`
     // .layer(RequestBodyLimitLayer::new(100 * 1024 * 1024)) // ~100MB;
-    Router::new()
+    let router = Router::new()
+        .route("/admin/reload", post(admin_reload))
+        .route("/admin/requests", get(admin_requests))
         .route("/proxy/*path", get(proxy).head(proxy))
-        .route("/requests/:request_id", get(requests))
+        .route("/requests/:request_id", get(requests).delete(cancel))
         .route("/applecrashreport", post(applecrashreport))
+        .route("/coredump", post(coredump))
+        .route("/diagnose", post(diagnose))
         .route("/minidump", post(minidump))
         .route("/symbolicate", post(symbolicate))
         .layer(layer)
         // the healthcheck is last, as it will bypass all the middlewares
-        .route("/healthcheck", get(healthcheck))
+        .route("/healthcheck", get(healthcheck));
+
+    // like the healthcheck, scraping metrics should not show up in the metrics themselves, but
+    // the metrics are tagged by scopes and sources, so callers need to be authenticated
+    if prometheus {
+        let metrics = get(prometheus_metrics).layer(middleware::from_fn(move |request, next| {
+            authenticate(metrics_auth_service.clone(), request, next)
+        }));
+        router.route("/metrics", metrics)
+    } else {
+        router
+    }
 }
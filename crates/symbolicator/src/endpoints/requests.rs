@@ -3,6 +3,7 @@ use axum::http::StatusCode;
 use axum::response::Json;
 use serde::Deserialize;
 
+use crate::auth::Caller;
 use crate::services::Service;
 use crate::types::{RequestId, SymbolicationResponse};
 
@@ -13,8 +14,23 @@ pub struct PollSymbolicationRequestQueryParams {
     pub timeout: Option<u64>,
 }
 
+/// Checks that the caller may access the request, if it exists.
+///
+/// Requests of other scopes are reported as unknown, so that their ids are not disclosed.
+fn check_request_scope(
+    state: &Service,
+    caller: &Caller,
+    request_id: RequestId,
+) -> Result<(), StatusCode> {
+    match state.symbolication().request_scope(request_id) {
+        Some(scope) if caller.check_scope(&scope).is_err() => Err(StatusCode::NOT_FOUND),
+        _ => Ok(()),
+    }
+}
+
 pub async fn poll_request(
     extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
     extract::Path(request_id): extract::Path<RequestId>,
     extract::Query(query): extract::Query<PollSymbolicationRequestQueryParams>,
 ) -> Result<Json<SymbolicationResponse>, StatusCode> {
@@ -22,6 +38,8 @@ pub async fn poll_request(
         scope.set_transaction(Some("GET /requests"));
     });
 
+    check_request_scope(&state, &caller, request_id)?;
+
     let response_opt = state
         .symbolication()
         .get_response(request_id, query.timeout)
@@ -32,3 +50,91 @@ pub async fn poll_request(
         None => Err(StatusCode::NOT_FOUND),
     }
 }
+
+/// Cancels a symbolication request that is still being processed.
+///
+/// Responds with `404` if the request is unknown, belongs to a scope the caller may not access,
+/// or has already finished.
+pub async fn cancel_request(
+    extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
+    extract::Path(request_id): extract::Path<RequestId>,
+) -> Result<Json<SymbolicationResponse>, StatusCode> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("DELETE /requests"));
+    });
+
+    check_request_scope(&state, &caller, request_id)?;
+
+    if state.symbolication().cancel_request(request_id) {
+        Ok(Json(SymbolicationResponse::Cancelled))
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, StatusCode};
+
+    use crate::config::{ApiKey, Auth, AuthRules, Config};
+    use crate::services::Service;
+    use crate::test;
+    use crate::types::Scope;
+
+    fn api_key(key: &str, scope: &str) -> ApiKey {
+        ApiKey {
+            key: key.into(),
+            rules: AuthRules {
+                scopes: Some(vec![scope.into()]),
+                ..AuthRules::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_scope() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let config = Config {
+            cache_dir: Some(cache_dir.path().to_owned()),
+            connect_to_reserved_ips: true,
+            auth: Auth {
+                api_keys: vec![api_key("key-42", "42"), api_key("key-43", "43")],
+                jwt: None,
+            },
+            ..Default::default()
+        };
+        let handle = tokio::runtime::Handle::current();
+        let service = Service::create(config, handle.clone(), handle)
+            .await
+            .unwrap();
+
+        // A request that never finishes, since its symbol server never responds.
+        let symbol_server = test::FailingSymbolServer::new();
+        let request = test::symbolication_request(
+            Scope::Scoped("42".into()),
+            vec![symbol_server.pending_source.clone()],
+        );
+        let request_id = service
+            .symbolication()
+            .symbolicate_stacktraces(request)
+            .unwrap();
+
+        let server = test::Server::with_service(service);
+        let client = Client::new();
+        let url = server.url(&format!("/requests/{}?timeout=0", request_id));
+
+        // Requests of other scopes are not disclosed.
+        let response = client.get(url.clone()).bearer_auth("key-43").send();
+        assert_eq!(response.await.unwrap().status(), StatusCode::NOT_FOUND);
+        let response = client.delete(url.clone()).bearer_auth("key-43").send();
+        assert_eq!(response.await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let response = client.get(url.clone()).bearer_auth("key-42").send();
+        assert_eq!(response.await.unwrap().status(), StatusCode::OK);
+        let response = client.delete(url.clone()).bearer_auth("key-42").send();
+        assert_eq!(response.await.unwrap().status(), StatusCode::OK);
+    }
+}
@@ -2,12 +2,12 @@ use axum::extract;
 use axum::response::Json;
 use serde::Deserialize;
 
-use symbolicator_sources::SourceConfig;
-
+use crate::auth::Caller;
 use crate::services::symbolication::{StacktraceOrigin, SymbolicateStacktraces};
 use crate::services::Service;
 use crate::types::{
-    RawObjectInfo, RawStacktrace, RequestOptions, Scope, Signal, SymbolicationResponse,
+    RawObjectInfo, RawStacktrace, RequestOptions, RequestSource, Scope, Signal,
+    SymbolicationResponse,
 };
 use crate::utils::sentry::ConfigureScope;
 
@@ -39,7 +39,7 @@ pub struct SymbolicationRequestBody {
     #[serde(default)]
     pub signal: Option<Signal>,
     #[serde(default)]
-    pub sources: Option<Vec<SourceConfig>>,
+    pub sources: Option<Vec<RequestSource>>,
     #[serde(default)]
     pub stacktraces: Vec<RawStacktrace>,
     #[serde(default)]
@@ -50,6 +50,7 @@ pub struct SymbolicationRequestBody {
 
 pub async fn symbolicate_frames(
     extract::Extension(state): extract::Extension<Service>,
+    extract::Extension(caller): extract::Extension<Caller>,
     extract::Query(params): extract::Query<SymbolicationRequestQueryParams>,
     extract::ContentLengthLimit(extract::Json(body)): extract::ContentLengthLimit<
         extract::Json<SymbolicationRequestBody>,
@@ -59,9 +60,14 @@ pub async fn symbolicate_frames(
     sentry::start_session();
 
     params.configure_scope();
+    caller.check_scope(&params.scope)?;
 
     let sources = match body.sources {
-        Some(sources) => sources.into(),
+        Some(sources) => {
+            let config = state.config();
+            caller.check_sources(&sources, &config)?;
+            config.resolve_sources(sources)?
+        }
         None => state.config().default_sources(),
     };
 
@@ -1,12 +1,28 @@
 use std::env;
 
+use anyhow::{Context, Result};
+use opentelemetry::sdk::trace::Tracer;
+use parking_lot::RwLock;
 use tracing::level_filters::LevelFilter;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
 use tracing_subscriber::fmt::fmt;
 use tracing_subscriber::fmt::time::UtcTime;
 use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter};
 
 use crate::config::{Config, LogFormat};
+use crate::telemetry::{self, OtlpGuard};
+
+/// Replaces the filter of the global subscriber.
+type ReloadFilter = Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>;
+
+lazy_static::lazy_static! {
+    /// Set once logging is initialized.
+    static ref RELOAD_FILTER: RwLock<Option<ReloadFilter>> = RwLock::new(None);
+}
 
 fn get_rust_log(level: LevelFilter) -> &'static str {
     match level {
@@ -32,49 +48,108 @@ fn get_rust_log(level: LevelFilter) -> &'static str {
     }
 }
 
+/// Creates a layer exporting spans to OpenTelemetry, if a tracer is configured.
+fn otel_layer<S>(tracer: &Option<Tracer>) -> Option<OpenTelemetryLayer<S, Tracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracer
+        .clone()
+        .map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
 /// Initializes logging for the symbolicator.
 ///
 /// This considers the `RUST_LOG` environment variable and defaults it to the level specified in the
 /// configuration. Additionally, this toggles `RUST_BACKTRACE` based on the [`enable_stacktraces`]
 /// config value.
 ///
+/// If an OTLP endpoint is configured, spans are also exported to OpenTelemetry.  The returned
+/// guard must be kept alive for as long as spans should be exported.
+///
 /// [`enable_stacktraces`]: crate::config::Logging::enable_backtraces
-pub fn init_logging(config: &Config) {
+pub fn init_logging(config: &Config) -> Result<Option<OtlpGuard>> {
     if config.logging.enable_backtraces {
         env::set_var("RUST_BACKTRACE", "1");
     }
 
-    let rust_log =
-        env::var("RUST_LOG").unwrap_or_else(|_| get_rust_log(config.logging.level).to_string());
-
     let subscriber = fmt()
         .with_timer(UtcTime::rfc_3339())
         .with_target(true)
-        .with_env_filter(rust_log);
+        .with_env_filter(env_filter(config.logging.level));
+
+    let (tracer, guard) = match telemetry::init_tracer(&config.opentelemetry)? {
+        Some((tracer, guard)) => (Some(tracer), Some(guard)),
+        None => (None, None),
+    };
 
     match (config.logging.format, console::user_attended()) {
-        (LogFormat::Auto, true) | (LogFormat::Pretty, _) => subscriber
-            .pretty()
-            .finish()
-            .with(sentry::integrations::tracing::layer())
-            .init(),
-        (LogFormat::Auto, false) | (LogFormat::Simplified, _) => subscriber
-            .compact()
-            .with_ansi(false)
-            .finish()
-            .with(sentry::integrations::tracing::layer())
-            .init(),
-        (LogFormat::Json, _) => subscriber
-            .json()
-            .flatten_event(true)
-            .with_current_span(true)
-            .with_span_list(true)
-            .with_file(true)
-            .with_line_number(true)
-            .finish()
-            .with(sentry::integrations::tracing::layer())
-            .init(),
+        (LogFormat::Auto, true) | (LogFormat::Pretty, _) => {
+            let subscriber = subscriber.pretty().with_filter_reloading();
+            let handle = subscriber.reload_handle();
+            set_reload_filter(Box::new(move |filter| handle.reload(filter)));
+            subscriber
+                .finish()
+                .with(sentry::integrations::tracing::layer())
+                .with(otel_layer(&tracer))
+                .init()
+        }
+        (LogFormat::Auto, false) | (LogFormat::Simplified, _) => {
+            let subscriber = subscriber
+                .compact()
+                .with_ansi(false)
+                .with_filter_reloading();
+            let handle = subscriber.reload_handle();
+            set_reload_filter(Box::new(move |filter| handle.reload(filter)));
+            subscriber
+                .finish()
+                .with(sentry::integrations::tracing::layer())
+                .with(otel_layer(&tracer))
+                .init()
+        }
+        (LogFormat::Json, _) => {
+            let subscriber = subscriber
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_file(true)
+                .with_line_number(true)
+                .with_filter_reloading();
+            let handle = subscriber.reload_handle();
+            set_reload_filter(Box::new(move |filter| handle.reload(filter)));
+            subscriber
+                .finish()
+                .with(sentry::integrations::tracing::layer())
+                .with(otel_layer(&tracer))
+                .init()
+        }
+    }
+
+    Ok(guard)
+}
+
+/// Returns the filter for the given log level.
+///
+/// The `RUST_LOG` environment variable takes precedence over the level.
+fn env_filter(level: LevelFilter) -> EnvFilter {
+    let rust_log = env::var("RUST_LOG").unwrap_or_else(|_| get_rust_log(level).to_string());
+    EnvFilter::new(rust_log)
+}
+
+fn set_reload_filter(reload_filter: ReloadFilter) {
+    *RELOAD_FILTER.write() = Some(reload_filter);
+}
+
+/// Changes the log level of the logging initialized by [`init_logging`].
+///
+/// Like during initialization, the `RUST_LOG` environment variable takes precedence.  This has
+/// no effect if logging has not been initialized.
+pub fn set_level(level: LevelFilter) -> Result<()> {
+    if let Some(ref reload_filter) = *RELOAD_FILTER.read() {
+        reload_filter(env_filter(level)).context("failed to change the log level")?;
     }
+    Ok(())
 }
 
 /// Logs an error to the configured logger or `stderr` if not yet configured.
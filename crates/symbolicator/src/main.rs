@@ -25,6 +25,7 @@ mod macros;
 #[macro_use]
 mod metrics;
 
+mod auth;
 mod cache;
 mod cli;
 mod config;
@@ -32,6 +33,7 @@ mod endpoints;
 mod logging;
 mod server;
 mod services;
+mod telemetry;
 mod types;
 mod utils;
 
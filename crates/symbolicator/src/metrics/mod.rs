@@ -1,9 +1,11 @@
 //! Provides access to the metrics sytem.
+use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::future::Future;
 use std::net::ToSocketAddrs;
-use std::ops::{Deref, DerefMut};
+use std::ops::Deref;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Instant;
@@ -14,12 +16,20 @@ use parking_lot::RwLock;
 use tower_layer::Layer;
 use tower_service::Service as TowerService;
 
+use crate::config::Metrics;
+
+pub mod prometheus;
+
 lazy_static::lazy_static! {
     static ref METRICS_CLIENT: RwLock<Option<Arc<MetricsClient>>> = RwLock::new(None);
 }
 
+/// Incremented whenever the client is replaced, so that threads pick up the new client.
+static CLIENT_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
 thread_local! {
-    static CURRENT_CLIENT: Option<Arc<MetricsClient>> = METRICS_CLIENT.read().clone();
+    static CURRENT_CLIENT: RefCell<(usize, Option<Arc<MetricsClient>>)> =
+        RefCell::new((CLIENT_GENERATION.load(Ordering::Acquire), METRICS_CLIENT.read().clone()));
 }
 
 /// The metrics prelude that is necessary to use the client.
@@ -30,7 +40,7 @@ pub mod prelude {
 #[derive(Debug)]
 pub struct MetricsClient {
     /// The raw statsd client.
-    pub statsd_client: StatsdClient,
+    pub statsd_client: Arc<StatsdClient>,
 
     /// A collection of tags and values that will be sent with every metric.
     tags: BTreeMap<String, String>,
@@ -57,27 +67,90 @@ impl Deref for MetricsClient {
     }
 }
 
-impl DerefMut for MetricsClient {
-    fn deref_mut(&mut self) -> &mut StatsdClient {
-        &mut self.statsd_client
-    }
-}
-
 /// Set a new statsd client.
 pub fn set_client(client: MetricsClient) {
     *METRICS_CLIENT.write() = Some(Arc::new(client));
+    CLIENT_GENERATION.fetch_add(1, Ordering::Release);
 }
 
-/// Tell the metrics system to report to statsd.
-pub fn configure_statsd<A: ToSocketAddrs>(prefix: &str, host: A, tags: BTreeMap<String, String>) {
-    let addrs: Vec<_> = host.to_socket_addrs().unwrap().collect();
-    if !addrs.is_empty() {
-        tracing::info!("Reporting metrics to statsd at {}", addrs[0]);
+/// Replaces the tags sent with every metric, keeping the configured statsd client.
+///
+/// This has no effect if metrics are not configured.
+pub fn set_tags(tags: BTreeMap<String, String>) {
+    let mut client = METRICS_CLIENT.write();
+    if let Some(ref mut client) = *client {
+        *client = Arc::new(MetricsClient {
+            statsd_client: client.statsd_client.clone(),
+            tags,
+        });
+        CLIENT_GENERATION.fetch_add(1, Ordering::Release);
     }
-    let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
-    socket.set_nonblocking(true).unwrap();
-    let sink = UdpMetricSink::from(&addrs[..], socket).unwrap();
-    let statsd_client = StatsdClient::from_sink(prefix, sink);
+}
+
+/// Returns the tags to send with every metric according to the configuration.
+///
+/// Besides the custom tags, this resolves the hostname and environment tags.
+pub fn tags(config: &Metrics) -> BTreeMap<String, String> {
+    let mut tags = config.custom_tags.clone();
+
+    if let Some(hostname_tag) = config.hostname_tag.clone() {
+        if tags.contains_key(&hostname_tag) {
+            tracing::warn!(
+                "tag {} defined both as hostname tag and as a custom tag",
+                hostname_tag
+            );
+        }
+        if let Some(hostname) = hostname::get().ok().and_then(|s| s.into_string().ok()) {
+            tags.insert(hostname_tag, hostname);
+        } else {
+            tracing::error!("could not read host name");
+        }
+    };
+    if let Some(environment_tag) = config.environment_tag.clone() {
+        if tags.contains_key(&environment_tag) {
+            tracing::warn!(
+                "tag {} defined both as environment tag and as a custom tag",
+                environment_tag
+            );
+        }
+        let environment = sentry::Hub::main()
+            .client()
+            .and_then(|client| client.options().environment.as_ref().map(|s| s.to_string()));
+        if let Some(environment) = environment {
+            tags.insert(environment_tag, environment);
+        } else {
+            tracing::error!("environment name not available");
+        }
+    };
+
+    tags
+}
+
+/// Tell the metrics system to report to statsd and/or the Prometheus registry.
+///
+/// If `statsd` is set, metrics are sent to the statsd instance at that address.  If
+/// `prometheus` is set, metrics are additionally recorded in the registry that is served on
+/// the `/metrics` endpoint.
+pub fn configure_metrics<A: ToSocketAddrs>(
+    prefix: &str,
+    statsd: Option<A>,
+    prometheus: bool,
+    tags: BTreeMap<String, String>,
+) {
+    let udp_sink = statsd.map(|host| {
+        let addrs: Vec<_> = host.to_socket_addrs().unwrap().collect();
+        if !addrs.is_empty() {
+            tracing::info!("Reporting metrics to statsd at {}", addrs[0]);
+        }
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        UdpMetricSink::from(&addrs[..], socket).unwrap()
+    });
+    if prometheus {
+        tracing::info!("Recording metrics for the Prometheus endpoint");
+    }
+    let sink = self::prometheus::MetricsSink::new(udp_sink, prometheus);
+    let statsd_client = Arc::new(StatsdClient::from_sink(prefix, sink));
     set_client(MetricsClient {
         statsd_client,
         tags,
@@ -86,7 +159,7 @@ pub fn configure_statsd<A: ToSocketAddrs>(prefix: &str, host: A, tags: BTreeMap<
 
 /// Invoke a callback with the current statsd client.
 ///
-/// If statsd is not configured the callback is not invoked. For the most part
+/// If metrics are not configured the callback is not invoked. For the most part
 /// the [`metric!`](crate::metric) macro should be used instead.
 #[inline(always)]
 pub fn with_client<F, R>(f: F) -> R
@@ -94,13 +167,19 @@ where
     F: FnOnce(&MetricsClient) -> R,
     R: Default,
 {
-    CURRENT_CLIENT.with(|client| {
-        if let Some(client) = client {
-            f(client)
-        } else {
-            Default::default()
+    let client = CURRENT_CLIENT.with(|current| {
+        let mut current = current.borrow_mut();
+        let generation = CLIENT_GENERATION.load(Ordering::Acquire);
+        if current.0 != generation {
+            *current = (generation, METRICS_CLIENT.read().clone());
         }
-    })
+        current.1.clone()
+    });
+
+    match client {
+        Some(client) => f(&client),
+        None => Default::default(),
+    }
 }
 
 /// Emits a metric.
@@ -0,0 +1,339 @@
+//! An in-process metrics registry which is exposed in the Prometheus text format.
+//!
+//! The registry is fed by a cadence [`MetricSink`] which parses the statsd lines emitted by the
+//! [`metric!`](crate::metric) macro.  This way every metric is recorded with all of its tags,
+//! no matter whether it is also sent to statsd.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+
+use cadence::{MetricSink, UdpMetricSink};
+use parking_lot::Mutex;
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Registry = Registry::default();
+}
+
+/// Upper bounds of the histogram buckets.
+///
+/// Timers and histograms are used for durations in milliseconds as well as for sizes in bytes,
+/// so the buckets grow exponentially from 1 to 4G to cover both.
+const BUCKETS: [f64; 17] = [
+    1.0,
+    4.0,
+    16.0,
+    64.0,
+    256.0,
+    1024.0,
+    4096.0,
+    16384.0,
+    65536.0,
+    262144.0,
+    1048576.0,
+    4194304.0,
+    16777216.0,
+    67108864.0,
+    268435456.0,
+    1073741824.0,
+    4294967296.0,
+];
+
+/// Sorted label names and values of a single series.
+type Labels = Vec<(String, String)>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricType {
+    /// Returns the metric type for a statsd type.
+    ///
+    /// Timers, histograms and distributions are all recorded as histograms.
+    fn from_statsd(ty: &str) -> Option<Self> {
+        match ty {
+            "c" => Some(MetricType::Counter),
+            "g" => Some(MetricType::Gauge),
+            "ms" | "h" | "d" => Some(MetricType::Histogram),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Number of observations in each bucket, not cumulative.
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Debug)]
+enum Series {
+    Value(f64),
+    Histogram(Box<Histogram>),
+}
+
+impl Series {
+    fn new(ty: MetricType) -> Self {
+        match ty {
+            MetricType::Counter | MetricType::Gauge => Series::Value(0.0),
+            MetricType::Histogram => Series::Histogram(Default::default()),
+        }
+    }
+
+    fn record(&mut self, ty: MetricType, value: f64) {
+        match self {
+            Series::Value(current) if ty == MetricType::Counter => *current += value,
+            Series::Value(current) => *current = value,
+            Series::Histogram(histogram) => {
+                if let Some(index) = BUCKETS.iter().position(|bound| value <= *bound) {
+                    histogram.buckets[index] += 1;
+                }
+                histogram.sum += value;
+                histogram.count += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Family {
+    ty: MetricType,
+    series: BTreeMap<Labels, Series>,
+}
+
+/// A collection of metrics keyed by their name and labels.
+#[derive(Debug, Default)]
+struct Registry {
+    families: Mutex<BTreeMap<String, Family>>,
+}
+
+impl Registry {
+    /// Records a metric in the statsd line format with DogStatsD tags.
+    ///
+    /// Lines which cannot be parsed and metrics whose type differs from an earlier metric
+    /// with the same name are ignored.
+    fn record_statsd(&self, line: &str) {
+        let (name, ty, value, labels) = match parse_statsd(line) {
+            Some(parsed) => parsed,
+            None => return,
+        };
+
+        let mut families = self.families.lock();
+        let family = families.entry(name).or_insert_with(|| Family {
+            ty,
+            series: BTreeMap::new(),
+        });
+
+        if family.ty == ty {
+            family
+                .series
+                .entry(labels)
+                .or_insert_with(|| Series::new(ty))
+                .record(ty, value);
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut output = String::new();
+
+        for (name, family) in self.families.lock().iter() {
+            writeln!(output, "# TYPE {} {}", name, family.ty.name()).ok();
+            for (labels, series) in &family.series {
+                match series {
+                    Series::Value(value) => {
+                        writeln!(output, "{}{} {}", name, format_labels(labels, None), value).ok();
+                    }
+                    Series::Histogram(histogram) => {
+                        let mut cumulative = 0;
+                        for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                            cumulative += count;
+                            let le = bound.to_string();
+                            let labels = format_labels(labels, Some(&le));
+                            writeln!(output, "{}_bucket{} {}", name, labels, cumulative).ok();
+                        }
+                        let labels_inf = format_labels(labels, Some("+Inf"));
+                        let labels = format_labels(labels, None);
+                        writeln!(output, "{}_bucket{} {}", name, labels_inf, histogram.count).ok();
+                        writeln!(output, "{}_sum{} {}", name, labels, histogram.sum).ok();
+                        writeln!(output, "{}_count{} {}", name, labels, histogram.count).ok();
+                    }
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Parses a statsd line such as `prefix.name:1|c|#tag:value` into its name, type, value and
+/// labels.
+fn parse_statsd(line: &str) -> Option<(String, MetricType, f64, Labels)> {
+    let (name_value, rest) = line.split_once('|')?;
+    let (name, value) = name_value.rsplit_once(':')?;
+    let value = value.parse().ok()?;
+
+    let mut parts = rest.split('|');
+    let ty = MetricType::from_statsd(parts.next()?)?;
+
+    let mut labels = Labels::new();
+    // Other parts, such as sample rates, are ignored.
+    for tags in parts.filter_map(|part| part.strip_prefix('#')) {
+        for tag in tags.split(',') {
+            let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+            labels.push((sanitize_name(key), value.to_owned()));
+        }
+    }
+    labels.sort();
+    labels.dedup_by(|a, b| a.0 == b.0);
+
+    let mut name = sanitize_name(name);
+    if ty == MetricType::Counter {
+        name.push_str("_total");
+    }
+
+    Some((name, ty, value, labels))
+}
+
+/// Replaces all characters that are not valid in Prometheus metric and label names.
+fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Formats labels as `{name="value",...}`, optionally with an additional `le` label.
+fn format_labels(labels: &[(String, String)], le: Option<&str>) -> String {
+    let mut pairs: Vec<_> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders all metrics recorded so far in the Prometheus text exposition format.
+pub fn render() -> String {
+    REGISTRY.render()
+}
+
+/// A cadence sink which records metrics for Prometheus and optionally sends them to statsd.
+pub struct MetricsSink {
+    statsd: Option<UdpMetricSink>,
+    prometheus: bool,
+}
+
+impl MetricsSink {
+    pub fn new(statsd: Option<UdpMetricSink>, prometheus: bool) -> Self {
+        Self { statsd, prometheus }
+    }
+}
+
+impl MetricSink for MetricsSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        if self.prometheus {
+            REGISTRY.record_statsd(metric);
+        }
+        match self.statsd {
+            Some(ref statsd) => statsd.emit(metric),
+            None => Ok(metric.len()),
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        match self.statsd {
+            Some(ref statsd) => statsd.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_statsd() {
+        let (name, ty, value, labels) =
+            parse_statsd("symbolicator.caches.objects.file.hit:1|c|#hit:true,cache:objects")
+                .unwrap();
+        assert_eq!(name, "symbolicator_caches_objects_file_hit_total");
+        assert_eq!(ty, MetricType::Counter);
+        assert_eq!(value, 1.0);
+        assert_eq!(
+            labels,
+            [
+                ("cache".to_owned(), "objects".to_owned()),
+                ("hit".to_owned(), "true".to_owned())
+            ]
+        );
+
+        let (name, ty, value, labels) =
+            parse_statsd("symbolicator.requests.duration:12|ms").unwrap();
+        assert_eq!(name, "symbolicator_requests_duration");
+        assert_eq!(ty, MetricType::Histogram);
+        assert_eq!(value, 12.0);
+        assert!(labels.is_empty());
+
+        assert!(parse_statsd("symbolicator.unique:1|s").is_none());
+        assert!(parse_statsd("garbage").is_none());
+    }
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::default();
+        registry.record_statsd("symbolicator.requests:1|c|#status:ok");
+        registry.record_statsd("symbolicator.requests:2|c|#status:ok");
+        registry.record_statsd("symbolicator.queue:5|g");
+        registry.record_statsd("symbolicator.queue:3|g");
+        registry.record_statsd("symbolicator.size:100|h|#source:\"a\"");
+        registry.record_statsd("symbolicator.size:2|h|#source:\"a\"");
+        // Mismatching types are ignored.
+        registry.record_statsd("symbolicator.queue:1|ms");
+
+        let output = registry.render();
+        assert!(output.contains("# TYPE symbolicator_requests_total counter\n"));
+        assert!(output.contains("symbolicator_requests_total{status=\"ok\"} 3\n"));
+        assert!(output.contains("# TYPE symbolicator_queue gauge\n"));
+        assert!(output.contains("symbolicator_queue 3\n"));
+        assert!(output.contains("# TYPE symbolicator_size histogram\n"));
+        assert!(output.contains("symbolicator_size_bucket{source=\"\\\"a\\\"\",le=\"1\"} 0\n"));
+        assert!(output.contains("symbolicator_size_bucket{source=\"\\\"a\\\"\",le=\"4\"} 1\n"));
+        assert!(output.contains("symbolicator_size_bucket{source=\"\\\"a\\\"\",le=\"256\"} 2\n"));
+        assert!(output.contains("symbolicator_size_bucket{source=\"\\\"a\\\"\",le=\"+Inf\"} 2\n"));
+        assert!(output.contains("symbolicator_size_sum{source=\"\\\"a\\\"\"} 102\n"));
+        assert!(output.contains("symbolicator_size_count{source=\"\\\"a\\\"\"} 2\n"));
+    }
+}
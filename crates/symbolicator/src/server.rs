@@ -21,6 +21,27 @@ fn read_pem_file(path: &PathBuf) -> Result<Vec<u8>> {
     read(path).context(format!("unable to read file: {}", path.display()))
 }
 
+/// Reloads the configuration whenever the process receives `SIGHUP`.
+#[cfg(unix)]
+async fn reload_on_hangup(service: Service) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(error) => {
+            tracing::error!("Failed to listen for SIGHUP: {}", error);
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        tracing::info!("Received SIGHUP, reloading configuration");
+        if let Err(error) = service.reload_config().await {
+            crate::services::log_reload_error(error);
+        }
+    }
+}
+
 /// Starts all actors and HTTP (and optionally HTTPS) server based on loaded config.
 pub fn run(config: Config) -> Result<()> {
     // Log this metric before actually starting the server. This allows to see restarts even if
@@ -54,6 +75,9 @@ pub fn run(config: Config) -> Result<()> {
         ))
         .context("failed to create service state")?;
 
+    #[cfg(unix)]
+    web_pool.spawn(reload_on_hangup(service.clone()));
+
     let svc = endpoints::create_app(service).into_make_service();
 
     let handle_http = Handle::new();
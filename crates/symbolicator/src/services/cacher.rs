@@ -4,6 +4,7 @@ use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::Context;
 use futures::channel::oneshot;
@@ -16,8 +17,9 @@ use tokio::fs;
 
 use crate::cache::{Cache, CacheStatus};
 use crate::services::shared_cache::{CacheStoreReason, SharedCacheKey, SharedCacheService};
-use crate::types::Scope;
+use crate::types::{ComputationOrigin, Scope};
 use crate::utils::futures::CallOnDrop;
+use crate::utils::request_diagnostics;
 
 type ComputationResult<T, E> = Result<Arc<T>, Arc<E>>;
 // Inner result necessary because `futures::Shared` won't give us `Arc`s but its own custom
@@ -323,9 +325,11 @@ impl<T: CacheItemRequest> Cacher<T> {
             .lookup_local_cache(&request, &key, T::VERSIONS.current)
             .await?
         {
+            request_diagnostics::record_local_hit(self.config.name().as_ref());
             return Ok(item);
         }
 
+        let start = Instant::now();
         let mut temp_file = self.tempfile()?;
         let shared_cache_key = SharedCacheKey {
             name: self.config.name(),
@@ -356,12 +360,12 @@ impl<T: CacheItemRequest> Cacher<T> {
             metric!(counter("shared_cache.file.discarded") += 1);
         }
 
-        let status = match status {
-            Some(status) => status,
+        let (status, origin) = match status {
+            Some(status) => (status, ComputationOrigin::Shared),
             None => {
                 let status = request.compute(temp_file.path()).await?;
                 status.write(&mut temp_fd).await?;
-                status
+                (status, ComputationOrigin::Fresh)
             }
         };
 
@@ -454,6 +458,13 @@ impl<T: CacheItemRequest> Cacher<T> {
                 .await;
         }
 
+        request_diagnostics::record_computation(
+            self.config.name().as_ref(),
+            key.to_string(),
+            origin,
+            start.elapsed(),
+        );
+
         Ok(request.load(key.scope.clone(), status, byte_view, path))
     }
 
@@ -472,6 +483,12 @@ impl<T: CacheItemRequest> Cacher<T> {
     {
         let (sender, receiver) = oneshot::channel();
 
+        // Lazy refreshes run in the background and are not part of the request's diagnostics.
+        let diagnostics = match is_refresh {
+            true => None,
+            false => request_diagnostics::current(),
+        };
+
         let max_lazy_refreshes = self.config.max_lazy_refreshes();
         let current_computations = self.current_computations.clone();
         let remove_computation_token = CallOnDrop::new(move || {
@@ -511,6 +528,7 @@ impl<T: CacheItemRequest> Cacher<T> {
             sender.send(result).ok();
         }
         .bind_hub(Hub::new_from_top(Hub::current()));
+        let channel = request_diagnostics::scope(diagnostics, channel);
 
         // These computations are spawned on the current runtime, which in all cases is the CPU-pool.
         tokio::spawn(channel);
@@ -537,6 +555,9 @@ impl<T: CacheItemRequest> Cacher<T> {
             if let Some(channel) = current_computations.get(&key) {
                 // A concurrent cache lookup was deduplicated.
                 metric!(counter(&format!("caches.{}.channel.hit", name)) += 1);
+                if !is_refresh {
+                    request_diagnostics::record_joined(name.as_ref());
+                }
                 channel.clone()
             } else {
                 // A concurrent cache lookup is considered new. This does not imply a cache miss.
@@ -580,6 +601,22 @@ impl<T: CacheItemRequest> Cacher<T> {
         Box::pin(future)
     }
 
+    /// Looks up an item in the cache without computing it.
+    ///
+    /// This tries the current and all fallback versions and returns `Ok(None)` if none of
+    /// them are cached, or caching is disabled.  Unlike [`Cacher::compute_memoized`], an
+    /// outdated item does not trigger a recomputation.
+    pub async fn lookup(&self, request: &T) -> Result<Option<T::Item>, T::Error> {
+        let key = request.get_cache_key();
+        let versions = std::iter::once(&T::VERSIONS.current).chain(T::VERSIONS.fallbacks);
+        for version in versions {
+            if let Some(item) = self.lookup_local_cache(request, &key, *version).await? {
+                return Ok(Some(item));
+            }
+        }
+        Ok(None)
+    }
+
     /// Computes an item by loading from or populating the cache.
     ///
     /// The actual computation is deduplicated between concurrent requests. Finally, the result is
@@ -605,6 +642,7 @@ impl<T: CacheItemRequest> Cacher<T> {
                 .lookup_local_cache(&request, &key, T::VERSIONS.current)
                 .await?
             {
+                request_diagnostics::record_local_hit(name.as_ref());
                 return Ok(Arc::new(item));
             }
 
@@ -625,6 +663,7 @@ impl<T: CacheItemRequest> Cacher<T> {
                     );
                     let _not_awaiting_future = self.spawn_computation(request, true);
 
+                    request_diagnostics::record_local_hit(name.as_ref());
                     return Ok(Arc::new(item));
                 }
             }
@@ -0,0 +1,1353 @@
+//! Conversion of Linux ELF core files into minidumps.
+//!
+//! Core files are converted into a minidump which is then processed exactly like an uploaded
+//! minidump, so that stackwalking, CFI lookups and the response format are shared.  The
+//! conversion reads the following parts of the core:
+//!
+//! - `NT_PRSTATUS` notes describe the threads of the process and their registers.  The kernel
+//!   writes the thread that received the signal first.
+//! - The `NT_SIGINFO` note describes the signal and, for memory errors, the faulting address.
+//! - The `NT_FILE` note lists all file mappings, from which the module list is built.
+//! - `PT_LOAD` segments contain the process memory.  All of it is referenced by the 64-bit
+//!   memory list of the minidump, and it provides the stack memory of all threads and the ELF
+//!   headers of mapped files, from which GNU build ids are read.
+//! - The `NT_AUXV` note is used to locate the command line arguments on the initial stack of
+//!   the process.  If they cannot be found, the truncated command line from the `NT_PRPSINFO`
+//!   note is used instead.
+//!
+//! The core file is rewritten into the minidump in place, so that its memory is not copied.
+//! Only the notes at the start of the core are overwritten with the minidump metadata and the
+//! thread stacks, and as much memory as they displace is moved to the end of the file.
+//!
+//! Only 64-bit little-endian cores of `x86_64` and `aarch64` processes are supported.  Some
+//! information is not available in core files:
+//!
+//! - Core files do not record the time of the crash, so the caller provides the timestamp.
+//! - Memory which the kernel did not dump, such as unmodified file mappings excluded by
+//!   `coredump_filter`, is missing from the memory list.
+//! - The OS version and CPU details are not recorded in core files.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use symbolic::common::ByteView;
+use thiserror::Error;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_SIGINFO: u32 = 0x5349_4749;
+const NT_FILE: u32 = 0x4649_4c45;
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// The auxiliary vector entry pointing to the file name of the executable.
+const AT_EXECFN: u64 = 31;
+
+/// Offset of `pr_reg` in `struct elf_prstatus` on 64-bit Linux.
+const PRSTATUS_REGS_OFFSET: usize = 112;
+/// Offset of `pr_psargs` in `struct elf_prpsinfo` on 64-bit Linux.
+const PRPSINFO_PSARGS_OFFSET: usize = 56;
+/// Size of `pr_psargs` in `struct elf_prpsinfo`.
+const PRPSINFO_PSARGS_SIZE: usize = 80;
+
+/// The number of bytes below the stack pointer that are included in the stack memory.
+///
+/// This covers the red zone of the System V ABI.
+const STACK_RED_ZONE: u64 = 128;
+
+const MINIDUMP_SIGNATURE: u32 = 0x504d_444d;
+const MINIDUMP_VERSION: u32 = 0xa793;
+const MINIDUMP_HEADER_SIZE: usize = 32;
+
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const EXCEPTION_STREAM: u32 = 6;
+const SYSTEM_INFO_STREAM: u32 = 7;
+const MEMORY64_LIST_STREAM: u32 = 9;
+const LINUX_CMD_LINE_STREAM: u32 = 0x4767_0006;
+
+const PROCESSOR_ARCHITECTURE_AMD64: u16 = 9;
+const PROCESSOR_ARCHITECTURE_ARM64: u16 = 12;
+const PLATFORM_ID_LINUX: u32 = 0x8201;
+
+/// The CodeView signature for ELF build ids, `BpEL`.
+const CV_SIGNATURE_ELF: u32 = 0x4270_454c;
+
+const CONTEXT_AMD64: u32 = 0x0010_0000;
+const CONTEXT_AMD64_SIZE: usize = 1232;
+const CONTEXT_ARM64: u32 = 0x0040_0000;
+const CONTEXT_ARM64_SIZE: usize = 912;
+/// `CONTEXT_CONTROL | CONTEXT_INTEGER`, valid for all architectures.
+const CONTEXT_CONTROL_INTEGER: u32 = 0x3;
+/// `CONTEXT_SEGMENTS`, only valid for x86 architectures.
+const CONTEXT_SEGMENTS: u32 = 0x4;
+
+/// Errors converting an ELF core file into a minidump.
+#[derive(Debug, Error)]
+pub enum CoredumpError {
+    /// The file is not an ELF file.
+    #[error("not an ELF file")]
+    NotElf,
+    /// The file is an ELF file, but not a core file.
+    #[error("not an ELF core file")]
+    NotCore,
+    /// The core file is of an unsupported class, byte order or architecture.
+    #[error("unsupported ELF core: {0}")]
+    Unsupported(&'static str),
+    /// The headers or notes of the core file are out of bounds or otherwise invalid.
+    #[error("malformed ELF core")]
+    Malformed,
+    /// The core file does not contain any threads.
+    #[error("ELF core does not contain any threads")]
+    NoThreads,
+    /// The thread stacks of the core file exceed the maximum size of a minidump.
+    #[error("ELF core is too large")]
+    TooLarge,
+    /// Reading the core file or writing the minidump failed.
+    #[error("failed to convert ELF core")]
+    Io(#[from] io::Error),
+}
+
+/// The CPU architectures supported in core files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CpuArch {
+    Amd64,
+    Arm64,
+}
+
+impl CpuArch {
+    /// The number of registers in `pr_reg` of `struct elf_prstatus`.
+    fn num_registers(self) -> usize {
+        match self {
+            // struct user_regs_struct
+            CpuArch::Amd64 => 27,
+            // struct user_pt_regs
+            CpuArch::Arm64 => 34,
+        }
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    let bytes = buf.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> Option<u64> {
+    let bytes = buf.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn align_up(value: usize, align: usize) -> Option<usize> {
+    Some(value.checked_add(align - 1)? & !(align - 1))
+}
+
+/// A program header of a 64-bit ELF file.
+#[derive(Debug)]
+struct ProgramHeader {
+    ty: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+}
+
+/// A parsed 64-bit little-endian ELF header with its program headers.
+struct ElfHeader {
+    ty: u16,
+    machine: u16,
+    program_headers: Vec<ProgramHeader>,
+}
+
+impl ElfHeader {
+    /// Parses the ELF header and program headers from the start of `data`.
+    ///
+    /// The program headers are expected at their file offset within `data`, which holds both
+    /// for files and for ELF images mapped into memory.
+    fn parse(data: &[u8]) -> Result<Self, CoredumpError> {
+        if !data.starts_with(ELF_MAGIC) {
+            return Err(CoredumpError::NotElf);
+        }
+        if data.get(4) != Some(&ELFCLASS64) {
+            return Err(CoredumpError::Unsupported(
+                "only 64-bit files are supported",
+            ));
+        }
+        if data.get(5) != Some(&ELFDATA2LSB) {
+            return Err(CoredumpError::Unsupported(
+                "only little-endian files are supported",
+            ));
+        }
+
+        let ty = read_u16(data, 16).ok_or(CoredumpError::Malformed)?;
+        let machine = read_u16(data, 18).ok_or(CoredumpError::Malformed)?;
+        let phoff = read_u64(data, 32).ok_or(CoredumpError::Malformed)? as usize;
+        let phentsize = read_u16(data, 54).ok_or(CoredumpError::Malformed)? as usize;
+        let phnum = read_u16(data, 56).ok_or(CoredumpError::Malformed)? as usize;
+
+        if phnum > 0 && phentsize < PROGRAM_HEADER_SIZE {
+            return Err(CoredumpError::Malformed);
+        }
+
+        let program_headers = (0..phnum)
+            .map(|index| {
+                let header = phoff
+                    .checked_add(index * phentsize)
+                    .and_then(|start| data.get(start..start.checked_add(PROGRAM_HEADER_SIZE)?))
+                    .ok_or(CoredumpError::Malformed)?;
+
+                Ok(ProgramHeader {
+                    ty: read_u32(header, 0).ok_or(CoredumpError::Malformed)?,
+                    offset: read_u64(header, 8).ok_or(CoredumpError::Malformed)?,
+                    vaddr: read_u64(header, 16).ok_or(CoredumpError::Malformed)?,
+                    filesz: read_u64(header, 32).ok_or(CoredumpError::Malformed)?,
+                })
+            })
+            .collect::<Result<_, CoredumpError>>()?;
+
+        Ok(Self {
+            ty,
+            machine,
+            program_headers,
+        })
+    }
+}
+
+/// An ELF note.
+struct Note<'a> {
+    name: &'a [u8],
+    ty: u32,
+    desc: &'a [u8],
+}
+
+/// Iterates the notes in the contents of a `PT_NOTE` segment.
+///
+/// Iteration stops at the first note that is out of bounds.
+fn iter_notes(data: &[u8]) -> impl Iterator<Item = Note<'_>> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let namesz = read_u32(data, offset)? as usize;
+        let descsz = read_u32(data, offset + 4)? as usize;
+        let ty = read_u32(data, offset + 8)?;
+
+        let name_start = offset + 12;
+        let desc_start = align_up(name_start.checked_add(namesz)?, 4)?;
+        let desc_end = desc_start.checked_add(descsz)?;
+
+        // The name includes a trailing NUL byte, which we strip.
+        let name = data.get(name_start..name_start + namesz)?;
+        let name = name.strip_suffix(b"\0").unwrap_or(name);
+        let desc = data.get(desc_start..desc_end)?;
+
+        offset = align_up(desc_end, 4)?;
+        Some(Note { name, ty, desc })
+    })
+}
+
+/// A `PT_LOAD` segment of the core file.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    /// The address of the segment in the crashed process.
+    addr: u64,
+    /// The offset of the segment in the core file.
+    offset: u64,
+    /// The size of the segment in the core file.
+    size: u64,
+}
+
+/// The memory of the crashed process, as contained in `PT_LOAD` segments.
+struct Memory<'a> {
+    /// Segments as `(start address, contents)`.
+    segments: Vec<(u64, &'a [u8])>,
+}
+
+impl<'a> Memory<'a> {
+    /// Returns the segment containing the given address.
+    fn segment_at(&self, addr: u64) -> Option<(u64, &'a [u8])> {
+        self.segments
+            .iter()
+            .copied()
+            .find(|(start, data)| addr >= *start && addr - *start < data.len() as u64)
+    }
+
+    /// Returns the remaining contents of the segment starting at the given address.
+    fn slice_from(&self, addr: u64) -> Option<&'a [u8]> {
+        let (start, data) = self.segment_at(addr)?;
+        data.get((addr - start) as usize..)
+    }
+
+    /// Returns `len` bytes of memory starting at the given address.
+    fn slice(&self, addr: u64, len: u64) -> Option<&'a [u8]> {
+        self.slice_from(addr)?.get(..len.try_into().ok()?)
+    }
+}
+
+/// A thread described by an `NT_PRSTATUS` note.
+struct Thread {
+    tid: u32,
+    signal: u32,
+    registers: Vec<u64>,
+}
+
+impl Thread {
+    fn parse(desc: &[u8], arch: CpuArch) -> Option<Self> {
+        let registers = (0..arch.num_registers())
+            .map(|index| read_u64(desc, PRSTATUS_REGS_OFFSET + index * 8))
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            // pr_cursig is a 16-bit value
+            signal: read_u16(desc, 12)?.into(),
+            tid: read_u32(desc, 32)?,
+            registers,
+        })
+    }
+
+    fn stack_pointer(&self, arch: CpuArch) -> u64 {
+        match arch {
+            CpuArch::Amd64 => self.registers[19],
+            CpuArch::Arm64 => self.registers[31],
+        }
+    }
+
+    fn instruction_pointer(&self, arch: CpuArch) -> u64 {
+        match arch {
+            CpuArch::Amd64 => self.registers[16],
+            CpuArch::Arm64 => self.registers[32],
+        }
+    }
+
+    /// Writes the registers of this thread in the minidump `CONTEXT` format.
+    fn to_context(&self, arch: CpuArch) -> Vec<u8> {
+        let regs = &self.registers;
+        match arch {
+            CpuArch::Amd64 => {
+                let mut context = vec![0; CONTEXT_AMD64_SIZE];
+                let flags = CONTEXT_AMD64 | CONTEXT_CONTROL_INTEGER | CONTEXT_SEGMENTS;
+                context[48..52].copy_from_slice(&flags.to_le_bytes());
+
+                // Segment registers in the order cs, ds, es, fs, gs, ss.
+                for (offset, index) in [(56, 17), (58, 23), (60, 24), (62, 25), (64, 26), (66, 20)]
+                {
+                    context[offset..offset + 2]
+                        .copy_from_slice(&(regs[index] as u16).to_le_bytes());
+                }
+                context[68..72].copy_from_slice(&(regs[18] as u32).to_le_bytes());
+
+                // General purpose registers in the order of the CONTEXT structure, mapped from
+                // their index in `struct user_regs_struct`.
+                let gprs = [
+                    10, // rax
+                    11, // rcx
+                    12, // rdx
+                    5,  // rbx
+                    19, // rsp
+                    4,  // rbp
+                    13, // rsi
+                    14, // rdi
+                    9,  // r8
+                    8,  // r9
+                    7,  // r10
+                    6,  // r11
+                    3,  // r12
+                    2,  // r13
+                    1,  // r14
+                    0,  // r15
+                    16, // rip
+                ];
+                for (position, index) in gprs.into_iter().enumerate() {
+                    let offset = 120 + position * 8;
+                    context[offset..offset + 8].copy_from_slice(&regs[index].to_le_bytes());
+                }
+
+                context
+            }
+            CpuArch::Arm64 => {
+                let mut context = vec![0; CONTEXT_ARM64_SIZE];
+                let flags = CONTEXT_ARM64 | CONTEXT_CONTROL_INTEGER;
+                context[0..4].copy_from_slice(&flags.to_le_bytes());
+                // pstate
+                context[4..8].copy_from_slice(&(regs[33] as u32).to_le_bytes());
+
+                // x0 to x30, followed by sp and pc, have the same order in both structures.
+                for (index, value) in regs[..33].iter().enumerate() {
+                    let offset = 8 + index * 8;
+                    context[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+                }
+
+                context
+            }
+        }
+    }
+}
+
+/// A file mapped into the crashed process, as described by the `NT_FILE` note.
+#[derive(Debug, PartialEq, Eq)]
+struct Mapping<'a> {
+    path: &'a str,
+    start: u64,
+    end: u64,
+    /// The offset of the mapping in the file, in pages.
+    page_offset: u64,
+}
+
+fn parse_file_note(desc: &[u8]) -> Option<Vec<Mapping<'_>>> {
+    let count: usize = read_u64(desc, 0)?.try_into().ok()?;
+    let names_offset = count.checked_mul(24)?.checked_add(16)?;
+    let mut names = desc.get(names_offset..)?.split(|b| *b == 0);
+
+    (0..count)
+        .map(|index| {
+            let offset = 16 + index * 24;
+            Some(Mapping {
+                path: std::str::from_utf8(names.next()?).ok()?,
+                start: read_u64(desc, offset)?,
+                end: read_u64(desc, offset + 8)?,
+                page_offset: read_u64(desc, offset + 16)?,
+            })
+        })
+        .collect()
+}
+
+/// A loaded module, built from all mappings of the same file.
+#[derive(Debug, PartialEq, Eq)]
+struct LoadedModule<'a> {
+    path: &'a str,
+    base: u64,
+    size: u64,
+    build_id: Option<&'a [u8]>,
+}
+
+/// Reads the GNU build id from the ELF image mapped at `base`.
+///
+/// Returns `Ok(None)` if the image is an ELF file without a build id or if its headers were not
+/// included in the core, and an error if the mapped file is not an ELF file.
+fn read_build_id<'a>(memory: &Memory<'a>, base: u64) -> Result<Option<&'a [u8]>, CoredumpError> {
+    let image = match memory.slice_from(base) {
+        Some(image) => image,
+        None => return Ok(None),
+    };
+
+    let header = match ElfHeader::parse(image) {
+        Ok(header) => header,
+        Err(CoredumpError::Malformed) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    // Shared libraries and position independent executables are linked at address zero and
+    // mapped at a bias, regular executables are mapped at their linked address.
+    let min_vaddr = header
+        .program_headers
+        .iter()
+        .filter(|ph| ph.ty == PT_LOAD)
+        .map(|ph| ph.vaddr & !0xfff)
+        .min()
+        .unwrap_or(0);
+    let bias = base.wrapping_sub(min_vaddr);
+
+    let build_id = header
+        .program_headers
+        .iter()
+        .filter(|ph| ph.ty == PT_NOTE)
+        .filter_map(|ph| memory.slice(bias.wrapping_add(ph.vaddr), ph.filesz))
+        .flat_map(iter_notes)
+        .find(|note| note.name == b"GNU" && note.ty == NT_GNU_BUILD_ID && !note.desc.is_empty())
+        .map(|note| note.desc);
+
+    Ok(build_id)
+}
+
+/// Builds the list of loaded modules from the file mappings.
+///
+/// Mappings of files which are not ELF images, such as fonts or locale archives, are skipped.
+fn build_modules<'a>(mappings: &[Mapping<'a>], memory: &Memory<'a>) -> Vec<LoadedModule<'a>> {
+    let mut by_path = BTreeMap::<&str, Vec<&Mapping>>::new();
+    for mapping in mappings {
+        by_path.entry(mapping.path).or_default().push(mapping);
+    }
+
+    let mut modules: Vec<_> = by_path
+        .into_iter()
+        .filter_map(|(path, mappings)| {
+            // The image is loaded at the mapping of the start of the file.
+            let base = mappings
+                .iter()
+                .filter(|m| m.page_offset == 0)
+                .map(|m| m.start)
+                .min()?;
+            let end = mappings.iter().map(|m| m.end).max()?;
+
+            let build_id = read_build_id(memory, base).ok()?;
+            Some(LoadedModule {
+                path,
+                base,
+                size: end.saturating_sub(base),
+                build_id,
+            })
+        })
+        .collect();
+
+    modules.sort_by_key(|module| module.base);
+    modules
+}
+
+/// Reads the command line arguments from the initial stack of the process.
+///
+/// The kernel places `argc`, the `argv` and `envp` pointer arrays and the auxiliary vector
+/// directly after each other on the stack of the main thread, followed by the strings they
+/// point to.  The auxiliary vector is also stored in the `NT_AUXV` note, so it is located in
+/// memory first, and the pointer arrays are then walked backwards from there.
+///
+/// Returns the arguments separated by `\0`, like in `/proc/<pid>/cmdline`.
+fn read_cmdline(memory: &Memory<'_>, auxv: &[u8]) -> Option<Vec<u8>> {
+    // The file name of the executable is stored next to the arguments, on the same stack.
+    let execfn = auxv
+        .chunks_exact(16)
+        .find(|entry| read_u64(entry, 0) == Some(AT_EXECFN))
+        .and_then(|entry| read_u64(entry, 8))?;
+    let (_, stack) = memory.segment_at(execfn)?;
+
+    let auxv_offset = (0..stack.len().checked_sub(auxv.len())?)
+        .step_by(8)
+        .find(|offset| stack[*offset..].starts_with(auxv))?;
+
+    // Skip the terminator of `envp` and the environment pointers, up to the terminator of `argv`.
+    let mut offset = auxv_offset.checked_sub(8)?;
+    if read_u64(stack, offset)? != 0 {
+        return None;
+    }
+    loop {
+        offset = offset.checked_sub(8)?;
+        if read_u64(stack, offset)? == 0 {
+            break;
+        }
+    }
+
+    // The argument pointers are preceded by their count.
+    let mut argv = Vec::new();
+    loop {
+        offset = offset.checked_sub(8)?;
+        let value = read_u64(stack, offset)?;
+        if value == argv.len() as u64 {
+            break;
+        }
+        argv.push(value);
+    }
+
+    let mut cmdline = Vec::new();
+    for arg in argv.into_iter().rev() {
+        let arg = memory.slice_from(arg)?;
+        cmdline.extend_from_slice(arg.split(|b| *b == 0).next()?);
+        cmdline.push(0);
+    }
+    Some(cmdline)
+}
+
+/// Converts the command line from `pr_psargs` to the format of `/proc/<pid>/cmdline`.
+///
+/// The arguments are joined by spaces and truncated in `pr_psargs`.  Splitting them again is
+/// the best we can do.
+fn psargs_to_cmdline(psargs: &[u8]) -> Vec<u8> {
+    let psargs = psargs.split(|b| *b == 0).next().unwrap_or_default();
+    psargs
+        .iter()
+        .map(|b| if *b == b' ' { 0 } else { *b })
+        .collect()
+}
+
+/// A location within the minidump, as `(data size, rva)`.
+type Location = (u32, u32);
+
+/// Zeros used to align data in the minidump.
+const PADDING: [u8; 8] = [0; 8];
+
+/// A minimal writer for the metadata at the start of a minidump.
+///
+/// The contents are collected as chunks, so that thread stacks are only copied once when the
+/// minidump is finished.
+struct MinidumpWriter<'a> {
+    chunks: Vec<Cow<'a, [u8]>>,
+    len: u64,
+    streams: Vec<(u32, Location)>,
+}
+
+impl<'a> MinidumpWriter<'a> {
+    fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: MINIDUMP_HEADER_SIZE as u64,
+            streams: Vec::new(),
+        }
+    }
+
+    /// Returns the RVA at which data would be appended, including alignment.
+    fn next_rva(&self) -> u64 {
+        // Align all data to 8 bytes.
+        align_up(self.len as usize, 8).unwrap_or(usize::MAX) as u64
+    }
+
+    /// Returns the end of the minidump after appending data of the given sizes.
+    fn end_after(&self, sizes: &[usize]) -> u64 {
+        sizes.iter().fold(self.len, |len, size| {
+            let rva = align_up(len as usize, 8).unwrap_or(usize::MAX) as u64;
+            rva.saturating_add(*size as u64)
+        })
+    }
+
+    /// Appends data to the minidump and returns its location.
+    fn append(&mut self, data: impl Into<Cow<'a, [u8]>>) -> Result<Location, CoredumpError> {
+        let data = data.into();
+        if self.end_after(&[data.len()]) > u32::MAX.into() {
+            return Err(CoredumpError::TooLarge);
+        }
+
+        let rva = self.next_rva();
+        let padding = (rva - self.len) as usize;
+        if padding > 0 {
+            self.chunks.push(Cow::Borrowed(&PADDING[..padding]));
+        }
+
+        let location = (data.len() as u32, rva as u32);
+        self.len = rva + data.len() as u64;
+        self.chunks.push(data);
+        Ok(location)
+    }
+
+    /// Appends a `MINIDUMP_STRING` and returns its RVA.
+    fn append_string(&mut self, s: &str) -> Result<u32, CoredumpError> {
+        let units: Vec<u8> = s.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut data = (units.len() as u32).to_le_bytes().to_vec();
+        data.extend(units);
+        // Strings are NUL-terminated, which is not included in the length.
+        data.extend([0, 0]);
+        Ok(self.append(data)?.1)
+    }
+
+    fn add_stream(&mut self, stream_type: u32, data: Vec<u8>) -> Result<(), CoredumpError> {
+        let location = self.append(data)?;
+        self.streams.push((stream_type, location));
+        Ok(())
+    }
+
+    /// Returns the header, all data and the stream directory.
+    fn finish(mut self, timestamp: u32) -> Result<Vec<u8>, CoredumpError> {
+        let mut directory = Vec::with_capacity(self.streams.len() * 12);
+        for (stream_type, (size, rva)) in &self.streams {
+            directory.extend(stream_type.to_le_bytes());
+            directory.extend(size.to_le_bytes());
+            directory.extend(rva.to_le_bytes());
+        }
+        let stream_count = self.streams.len() as u32;
+        let (_, directory_rva) = self.append(directory)?;
+
+        let mut buf = Vec::with_capacity(self.len as usize);
+        buf.extend(MINIDUMP_SIGNATURE.to_le_bytes());
+        buf.extend(MINIDUMP_VERSION.to_le_bytes());
+        buf.extend(stream_count.to_le_bytes());
+        buf.extend(directory_rva.to_le_bytes());
+        buf.extend(0u32.to_le_bytes()); // checksum
+        buf.extend(timestamp.to_le_bytes());
+        buf.extend(0u64.to_le_bytes()); // flags
+
+        for chunk in &self.chunks {
+            buf.extend_from_slice(chunk);
+        }
+        Ok(buf)
+    }
+}
+
+fn push_location(buf: &mut Vec<u8>, (size, rva): Location) {
+    buf.extend(size.to_le_bytes());
+    buf.extend(rva.to_le_bytes());
+}
+
+/// Describes how a core file is rewritten into a minidump.
+///
+/// The memory of the core is referenced by the minidump where it is, and only its start is
+/// moved behind its end to make room for the minidump metadata.
+struct MinidumpLayout {
+    /// The header, streams and thread stacks, written at the start of the file.
+    metadata: Vec<u8>,
+    /// The file offset of the memory that is moved.
+    moved_offset: u64,
+    /// The number of bytes of memory that are moved.
+    moved_len: u64,
+    /// The file offset to which the memory is moved.
+    moved_to: u64,
+}
+
+impl MinidumpLayout {
+    /// Rewrites the core file at `path` into a minidump.
+    fn apply(&self, path: &Path) -> Result<(), CoredumpError> {
+        let mut reader = File::open(path)?;
+        let mut writer = OpenOptions::new().write(true).open(path)?;
+
+        reader.seek(SeekFrom::Start(self.moved_offset))?;
+        writer.seek(SeekFrom::Start(self.moved_to))?;
+        let copied = io::copy(&mut (&mut reader).take(self.moved_len), &mut writer)?;
+        if copied != self.moved_len {
+            return Err(CoredumpError::Malformed);
+        }
+
+        // This also removes trailing data of the core that is not part of any segment.
+        writer.set_len(self.moved_to + self.moved_len)?;
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&self.metadata)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Converts the ELF core file at `path` into a minidump, in place.
+///
+/// The `timestamp` is written as time of the crash, in seconds since the UNIX epoch.
+pub fn coredump_to_minidump(path: &Path, timestamp: u32) -> Result<(), CoredumpError> {
+    let data = ByteView::open(path)?;
+    let layout = minidump_layout(&data, timestamp)?;
+    // The layout no longer borrows from the core, which is about to be overwritten.
+    drop(data);
+    layout.apply(path)
+}
+
+fn minidump_layout(data: &[u8], timestamp: u32) -> Result<MinidumpLayout, CoredumpError> {
+    let header = ElfHeader::parse(data)?;
+    if header.ty != ET_CORE {
+        return Err(CoredumpError::NotCore);
+    }
+
+    let arch = match header.machine {
+        EM_X86_64 => CpuArch::Amd64,
+        EM_AARCH64 => CpuArch::Arm64,
+        _ => return Err(CoredumpError::Unsupported("unsupported CPU architecture")),
+    };
+
+    let segment_data = |ph: &ProgramHeader| {
+        let start: usize = ph.offset.try_into().ok()?;
+        data.get(start..start.checked_add(ph.filesz.try_into().ok()?)?)
+    };
+
+    // Truncated cores, for instance due to `RLIMIT_CORE`, end within a segment.  The part of
+    // the segment that was written is kept.
+    let mut segments: Vec<_> = header
+        .program_headers
+        .iter()
+        .filter(|ph| ph.ty == PT_LOAD)
+        .filter_map(|ph| {
+            let size = ph.filesz.min((data.len() as u64).checked_sub(ph.offset)?);
+            (size > 0).then_some(Segment {
+                addr: ph.vaddr,
+                offset: ph.offset,
+                size,
+            })
+        })
+        .collect();
+    segments.sort_by_key(|segment| segment.offset);
+
+    // The kernel and gdb write segments back to back, which allows the minidump to reference
+    // them as a single block.
+    let contiguous = segments
+        .windows(2)
+        .all(|pair| pair[0].offset + pair[0].size == pair[1].offset);
+    if !contiguous {
+        return Err(CoredumpError::Unsupported(
+            "memory segments are not contiguous",
+        ));
+    }
+
+    let memory = Memory {
+        segments: segments
+            .iter()
+            .map(|segment| {
+                let start = segment.offset as usize;
+                (segment.addr, &data[start..start + segment.size as usize])
+            })
+            .collect(),
+    };
+
+    let mut threads = Vec::new();
+    let mut mappings = Vec::new();
+    let mut fault_info = None;
+    let mut psargs = None;
+    let mut auxv = None;
+
+    for ph in header.program_headers.iter().filter(|ph| ph.ty == PT_NOTE) {
+        let notes = segment_data(ph).ok_or(CoredumpError::Malformed)?;
+        for note in iter_notes(notes).filter(|note| note.name == b"CORE") {
+            match note.ty {
+                NT_PRSTATUS => {
+                    threads.push(Thread::parse(note.desc, arch).ok_or(CoredumpError::Malformed)?)
+                }
+                NT_FILE => mappings = parse_file_note(note.desc).ok_or(CoredumpError::Malformed)?,
+                NT_SIGINFO => {
+                    // si_signo, si_code, and for memory faults si_addr after the padding.
+                    let signo = read_u32(note.desc, 0).ok_or(CoredumpError::Malformed)?;
+                    let code = read_u32(note.desc, 8).ok_or(CoredumpError::Malformed)?;
+                    fault_info = Some((signo, code, read_u64(note.desc, 16)));
+                }
+                NT_PRPSINFO => {
+                    psargs = note
+                        .desc
+                        .get(PRPSINFO_PSARGS_OFFSET..PRPSINFO_PSARGS_OFFSET + PRPSINFO_PSARGS_SIZE)
+                }
+                NT_AUXV => auxv = Some(note.desc),
+                _ => (),
+            }
+        }
+    }
+
+    let crashing_thread = threads.first().ok_or(CoredumpError::NoThreads)?;
+    let modules = build_modules(&mappings, &memory);
+
+    let mut writer = MinidumpWriter::new();
+
+    // Threads, including their context and stack memory.  Stack memory is referenced with
+    // 32-bit RVAs and is often located at the end of the core, so it is copied to the start
+    // of the minidump.
+    let mut thread_list = (threads.len() as u32).to_le_bytes().to_vec();
+    let mut stack_list = Vec::new();
+    let mut crashing_context = (0, 0);
+
+    for (index, thread) in threads.iter().enumerate() {
+        let context = writer.append(thread.to_context(arch))?;
+        if index == 0 {
+            crashing_context = context;
+        }
+
+        // The stack reaches from the stack pointer to the end of its segment.
+        let stack_pointer = thread.stack_pointer(arch);
+        let (stack_start, stack_location) = match memory.segment_at(stack_pointer) {
+            Some((segment_start, segment)) => {
+                let start = stack_pointer
+                    .saturating_sub(STACK_RED_ZONE)
+                    .max(segment_start);
+                let location = writer.append(&segment[(start - segment_start) as usize..])?;
+                stack_list.push((start, location));
+                (start, location)
+            }
+            None => (0, (0, 0)),
+        };
+
+        thread_list.extend(thread.tid.to_le_bytes());
+        thread_list.extend(0u32.to_le_bytes()); // suspend_count
+        thread_list.extend(0u32.to_le_bytes()); // priority_class
+        thread_list.extend(0u32.to_le_bytes()); // priority
+        thread_list.extend(0u64.to_le_bytes()); // teb
+        thread_list.extend(stack_start.to_le_bytes());
+        push_location(&mut thread_list, stack_location);
+        push_location(&mut thread_list, context);
+    }
+
+    // Modules, with their names and build ids.
+    let mut module_list = (modules.len() as u32).to_le_bytes().to_vec();
+    for module in &modules {
+        let name_rva = writer.append_string(module.path)?;
+        let cv_record = match module.build_id {
+            Some(build_id) => {
+                let mut cv_record = CV_SIGNATURE_ELF.to_le_bytes().to_vec();
+                cv_record.extend_from_slice(build_id);
+                writer.append(cv_record)?
+            }
+            None => (0, 0),
+        };
+
+        module_list.extend(module.base.to_le_bytes());
+        module_list.extend((module.size.min(u32::MAX.into()) as u32).to_le_bytes());
+        module_list.extend(0u32.to_le_bytes()); // checksum
+        module_list.extend(0u32.to_le_bytes()); // time_date_stamp
+        module_list.extend(name_rva.to_le_bytes());
+        module_list.extend([0; 52]); // version_info
+        push_location(&mut module_list, cv_record);
+        push_location(&mut module_list, (0, 0)); // misc_record
+        module_list.extend(0u64.to_le_bytes()); // reserved0
+        module_list.extend(0u64.to_le_bytes()); // reserved1
+    }
+
+    // The exception stream, describing the signal of the crashing thread.
+    let (signo, code, fault_addr) = fault_info.unwrap_or((crashing_thread.signal, 0, None));
+    let fault_addr = match signo {
+        // SIGILL, SIGBUS, SIGFPE, SIGSEGV
+        4 | 7 | 8 | 11 => fault_addr,
+        _ => None,
+    };
+    let mut exception = crashing_thread.tid.to_le_bytes().to_vec();
+    exception.extend(0u32.to_le_bytes()); // alignment
+    exception.extend(signo.to_le_bytes());
+    exception.extend(code.to_le_bytes());
+    exception.extend(0u64.to_le_bytes()); // exception_record
+    exception.extend(
+        fault_addr
+            .unwrap_or_else(|| crashing_thread.instruction_pointer(arch))
+            .to_le_bytes(),
+    );
+    exception.extend(0u32.to_le_bytes()); // number_parameters
+    exception.extend(0u32.to_le_bytes()); // alignment
+    exception.extend([0; 15 * 8]); // exception_information
+    push_location(&mut exception, crashing_context);
+
+    // System information.  The OS version is not recorded in core files.
+    let csd_version_rva = writer.append_string("")?;
+    let processor_architecture = match arch {
+        CpuArch::Amd64 => PROCESSOR_ARCHITECTURE_AMD64,
+        CpuArch::Arm64 => PROCESSOR_ARCHITECTURE_ARM64,
+    };
+    let mut system_info = processor_architecture.to_le_bytes().to_vec();
+    system_info.extend(0u16.to_le_bytes()); // processor_level
+    system_info.extend(0u16.to_le_bytes()); // processor_revision
+    system_info.extend([0u8, 0u8]); // number_of_processors, product_type
+    system_info.extend(0u32.to_le_bytes()); // major_version
+    system_info.extend(0u32.to_le_bytes()); // minor_version
+    system_info.extend(0u32.to_le_bytes()); // build_number
+    system_info.extend(PLATFORM_ID_LINUX.to_le_bytes());
+    system_info.extend(csd_version_rva.to_le_bytes());
+    system_info.extend(0u16.to_le_bytes()); // suite_mask
+    system_info.extend(0u16.to_le_bytes()); // reserved2
+    system_info.extend([0; 24]); // cpu
+
+    let cmdline = auxv
+        .and_then(|auxv| read_cmdline(&memory, auxv))
+        .or_else(|| psargs.map(psargs_to_cmdline));
+
+    // The 32-bit memory list contains the copied thread stacks.
+    let mut memory_list = (stack_list.len() as u32).to_le_bytes().to_vec();
+    for (start, location) in stack_list {
+        memory_list.extend(start.to_le_bytes());
+        push_location(&mut memory_list, location);
+    }
+
+    writer.add_stream(SYSTEM_INFO_STREAM, system_info)?;
+    writer.add_stream(THREAD_LIST_STREAM, thread_list)?;
+    writer.add_stream(MODULE_LIST_STREAM, module_list)?;
+    writer.add_stream(EXCEPTION_STREAM, exception)?;
+    writer.add_stream(MEMORY_LIST_STREAM, memory_list)?;
+    if let Some(cmdline) = cmdline {
+        writer.add_stream(LINUX_CMD_LINE_STREAM, cmdline)?;
+    }
+
+    // All memory is referenced by the 64-bit memory list, which requires it to be contiguous.
+    // It starts right after the metadata, so the start of the segments that overlaps with the
+    // metadata is moved behind the end of the segments.  One segment may be split in two, and
+    // the memory list stream and the directory are reserved before the split is known.
+    let data_start = segments.first().map_or(0, |s| s.offset);
+    let data_end = segments.last().map_or(0, |s| s.offset + s.size);
+    let memory64_size = 16 + (segments.len() + 1) * 16;
+    let directory_size = (writer.streams.len() + 1) * 12;
+    let memory_rva = writer
+        .end_after(&[memory64_size, directory_size])
+        .max(data_start);
+
+    let mut memory64_list = Vec::new();
+    for segment in &segments {
+        let start = segment.offset.max(memory_rva);
+        let end = segment.offset + segment.size;
+        if start < end {
+            memory64_list.extend((segment.addr + (start - segment.offset)).to_le_bytes());
+            memory64_list.extend((end - start).to_le_bytes());
+        }
+    }
+    for segment in &segments {
+        let end = (segment.offset + segment.size).min(memory_rva);
+        if segment.offset < end {
+            memory64_list.extend(segment.addr.to_le_bytes());
+            memory64_list.extend((end - segment.offset).to_le_bytes());
+        }
+    }
+    let mut memory64_stream = (memory64_list.len() as u64 / 16).to_le_bytes().to_vec();
+    memory64_stream.extend(memory_rva.to_le_bytes());
+    memory64_stream.extend(memory64_list);
+    writer.add_stream(MEMORY64_LIST_STREAM, memory64_stream)?;
+
+    Ok(MinidumpLayout {
+        metadata: writer.finish(timestamp)?,
+        moved_offset: data_start,
+        moved_len: memory_rva.min(data_end).saturating_sub(data_start),
+        moved_to: data_end.max(memory_rva),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use minidump::{
+        Minidump, MinidumpException, MinidumpMemory64List, MinidumpMemoryList, MinidumpModuleList,
+        MinidumpSystemInfo, MinidumpThreadList, Module,
+    };
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn note(name: &[u8], ty: u32, desc: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend((name.len() as u32 + 1).to_le_bytes());
+        buf.extend((desc.len() as u32).to_le_bytes());
+        buf.extend(ty.to_le_bytes());
+        buf.extend(name);
+        buf.push(0);
+        buf.resize(align_up(buf.len(), 4).unwrap(), 0);
+        buf.extend(desc);
+        buf.resize(align_up(buf.len(), 4).unwrap(), 0);
+        buf
+    }
+
+    fn elf_header(ty: u16, phoff: u64, phnum: u16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(ELF_MAGIC);
+        buf.extend([ELFCLASS64, ELFDATA2LSB, 1, 0]);
+        buf.extend([0; 8]);
+        buf.extend(ty.to_le_bytes());
+        buf.extend(EM_X86_64.to_le_bytes());
+        buf.extend(1u32.to_le_bytes()); // version
+        buf.extend(0u64.to_le_bytes()); // entry
+        buf.extend(phoff.to_le_bytes());
+        buf.extend(0u64.to_le_bytes()); // shoff
+        buf.extend(0u32.to_le_bytes()); // flags
+        buf.extend(64u16.to_le_bytes()); // ehsize
+        buf.extend((PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+        buf.extend(phnum.to_le_bytes());
+        buf.extend([0; 6]); // shentsize, shnum, shstrndx
+        buf
+    }
+
+    fn program_header(ty: u32, offset: u64, vaddr: u64, size: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(ty.to_le_bytes());
+        buf.extend(0u32.to_le_bytes()); // flags
+        buf.extend(offset.to_le_bytes());
+        buf.extend(vaddr.to_le_bytes());
+        buf.extend(0u64.to_le_bytes()); // paddr
+        buf.extend(size.to_le_bytes()); // filesz
+        buf.extend(size.to_le_bytes()); // memsz
+        buf.extend(0x1000u64.to_le_bytes()); // align
+        buf
+    }
+
+    /// Builds the first page of a shared library with a build id note.
+    fn library_image(build_id: &[u8]) -> Vec<u8> {
+        let note = note(b"GNU", NT_GNU_BUILD_ID, build_id);
+        let note_offset = 64 + 2 * PROGRAM_HEADER_SIZE as u64;
+
+        let mut image = elf_header(3, 64, 2);
+        image.extend(program_header(PT_LOAD, 0, 0, 0x1000));
+        image.extend(program_header(
+            PT_NOTE,
+            note_offset,
+            note_offset,
+            note.len() as u64,
+        ));
+        image.extend(note);
+        image.resize(0x1000, 0);
+        image
+    }
+
+    fn prstatus(tid: u32, rip: u64, rsp: u64) -> Vec<u8> {
+        let mut desc = vec![0; PRSTATUS_REGS_OFFSET + 27 * 8 + 8];
+        desc[12..14].copy_from_slice(&11u16.to_le_bytes());
+        desc[32..36].copy_from_slice(&tid.to_le_bytes());
+        let regs = PRSTATUS_REGS_OFFSET;
+        desc[regs + 16 * 8..regs + 17 * 8].copy_from_slice(&rip.to_le_bytes());
+        desc[regs + 19 * 8..regs + 20 * 8].copy_from_slice(&rsp.to_le_bytes());
+        desc
+    }
+
+    fn file_note(mappings: &[(&str, u64, u64, u64)]) -> Vec<u8> {
+        let mut desc = Vec::new();
+        desc.extend((mappings.len() as u64).to_le_bytes());
+        desc.extend(0x1000u64.to_le_bytes());
+        for (_, start, end, page_offset) in mappings {
+            desc.extend(start.to_le_bytes());
+            desc.extend(end.to_le_bytes());
+            desc.extend(page_offset.to_le_bytes());
+        }
+        for (path, ..) in mappings {
+            desc.extend(path.as_bytes());
+            desc.push(0);
+        }
+        desc
+    }
+
+    /// Builds the initial stack of a process, placed at the end of a stack at `stack_end`.
+    ///
+    /// Returns the stack contents and the auxiliary vector.
+    fn initial_stack(stack_end: u64, args: &[&str]) -> (Vec<u8>, Vec<u8>) {
+        let mut strings = Vec::new();
+        for arg in args {
+            strings.extend(arg.as_bytes());
+            strings.push(0);
+        }
+        let env_offset = strings.len();
+        strings.extend(b"HOME=/root\0");
+        strings.resize(align_up(strings.len(), 8).unwrap(), 0);
+
+        // argc, argv and NULL, envp and NULL, and two auxv entries.
+        let pointers_size = 8 + (args.len() + 1) * 8 + 2 * 8 + 2 * 16;
+        let strings_addr = stack_end - strings.len() as u64;
+        let start = strings_addr - pointers_size as u64;
+
+        let mut auxv = Vec::new();
+        auxv.extend(AT_EXECFN.to_le_bytes());
+        auxv.extend(strings_addr.to_le_bytes());
+        auxv.extend([0; 16]); // AT_NULL
+
+        let mut stack = (args.len() as u64).to_le_bytes().to_vec();
+        let mut offset = 0;
+        for arg in args {
+            stack.extend((strings_addr + offset).to_le_bytes());
+            offset += arg.len() as u64 + 1;
+        }
+        stack.extend(0u64.to_le_bytes());
+        stack.extend((strings_addr + env_offset as u64).to_le_bytes());
+        stack.extend(0u64.to_le_bytes());
+        stack.extend(&auxv);
+        stack.extend(strings);
+
+        assert_eq!(stack_end - start, stack.len() as u64);
+        (stack, auxv)
+    }
+
+    const BUILD_ID: &[u8] = &[
+        0x5c, 0x94, 0x3e, 0x8a, 0x11, 0xb4, 0x5f, 0x2c, 0x83, 0x07, 0xa1, 0x6b, 0x2d, 0x19, 0x7c,
+        0x0e, 0x42, 0x42, 0x42, 0x42,
+    ];
+
+    /// Builds a core file with two threads, a library, a font and a stack segment.
+    ///
+    /// The segments start `padding` bytes after the notes.
+    fn build_core(padding: usize) -> Vec<u8> {
+        let mut stack = vec![0xab; 0x2000];
+        let (initial_stack, auxv) = initial_stack(
+            0x7ffd_0000_2000,
+            &["/usr/bin/app", "--name", "a very long name"],
+        );
+        let initial_stack_offset = stack.len() - initial_stack.len();
+        stack[initial_stack_offset..].copy_from_slice(&initial_stack);
+
+        let mut notes = Vec::new();
+        notes.extend(note(
+            b"CORE",
+            NT_PRSTATUS,
+            &prstatus(101, 0x7f00_0000_0420, 0x7ffd_0000_1000),
+        ));
+        notes.extend(note(
+            b"CORE",
+            NT_PRSTATUS,
+            &prstatus(102, 0x7f00_0000_0840, 0x7ffd_0000_1800),
+        ));
+        notes.extend(note(
+            b"CORE",
+            NT_FILE,
+            &file_note(&[
+                ("/usr/lib/libfoo.so", 0x7f00_0000_0000, 0x7f00_0000_1000, 0),
+                ("/usr/lib/libfoo.so", 0x7f00_0000_1000, 0x7f00_0000_3000, 1),
+                (
+                    "/usr/share/fonts/font.ttf",
+                    0x7f00_0001_0000,
+                    0x7f00_0001_1000,
+                    0,
+                ),
+            ]),
+        ));
+
+        notes.extend(note(b"CORE", NT_AUXV, &auxv));
+        notes.resize(notes.len() + padding, 0);
+
+        let library = library_image(BUILD_ID);
+
+        let mut font = b"\0\x01\0\0".to_vec();
+        font.resize(0x1000, 0);
+
+        let phnum = 4;
+        let notes_offset = 64 + phnum * PROGRAM_HEADER_SIZE as u64;
+        let library_offset = notes_offset + notes.len() as u64;
+        let font_offset = library_offset + library.len() as u64;
+        let stack_offset = font_offset + font.len() as u64;
+
+        let mut core = elf_header(ET_CORE, 64, phnum as u16);
+        core.extend(program_header(PT_NOTE, notes_offset, 0, notes.len() as u64));
+        core.extend(program_header(
+            PT_LOAD,
+            library_offset,
+            0x7f00_0000_0000,
+            0x1000,
+        ));
+        core.extend(program_header(
+            PT_LOAD,
+            font_offset,
+            0x7f00_0001_0000,
+            0x1000,
+        ));
+        core.extend(program_header(
+            PT_LOAD,
+            stack_offset,
+            0x7ffd_0000_0000,
+            0x2000,
+        ));
+        core.extend(notes);
+        core.extend(library);
+        core.extend(font);
+        core.extend(stack);
+        core
+    }
+
+    fn convert(core: &[u8]) -> Result<Vec<u8>, CoredumpError> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all(core)?;
+        coredump_to_minidump(file.path(), 1_600_000_000)?;
+        Ok(std::fs::read(file.path())?)
+    }
+
+    #[test]
+    fn test_not_a_core() {
+        assert!(matches!(convert(b"MDMP"), Err(CoredumpError::NotElf)));
+        assert!(matches!(
+            convert(&library_image(BUILD_ID)),
+            Err(CoredumpError::NotCore)
+        ));
+    }
+
+    #[test]
+    fn test_parse_file_note() {
+        let desc = file_note(&[
+            ("/lib/a.so", 0x1000, 0x2000, 0),
+            ("/lib/b.so", 0x3000, 0x5000, 2),
+        ]);
+        let mappings = parse_file_note(&desc).unwrap();
+        assert_eq!(
+            mappings,
+            [
+                Mapping {
+                    path: "/lib/a.so",
+                    start: 0x1000,
+                    end: 0x2000,
+                    page_offset: 0,
+                },
+                Mapping {
+                    path: "/lib/b.so",
+                    start: 0x3000,
+                    end: 0x5000,
+                    page_offset: 2,
+                },
+            ]
+        );
+
+        assert!(parse_file_note(&desc[..desc.len() - 12]).is_none());
+    }
+
+    fn check_minidump(core: &[u8]) {
+        let minidump = convert(core).unwrap();
+        let minidump = Minidump::read(&minidump[..]).unwrap();
+        assert_eq!(minidump.header.time_date_stamp, 1_600_000_000);
+
+        let system_info = minidump.get_stream::<MinidumpSystemInfo>().unwrap();
+        assert_eq!(system_info.os, minidump::system_info::Os::Linux);
+        assert_eq!(system_info.cpu, minidump::system_info::Cpu::X86_64);
+
+        let threads = minidump.get_stream::<MinidumpThreadList>().unwrap();
+        let thread_ids: Vec<_> = threads.threads.iter().map(|t| t.raw.thread_id).collect();
+        assert_eq!(thread_ids, [101, 102]);
+        // The stack starts in the red zone below the stack pointer.
+        assert_eq!(
+            threads.threads[0].raw.stack.start_of_memory_range,
+            0x7ffd_0000_0f80
+        );
+        // The stack is not truncated and reaches to the end of the segment.
+        assert_eq!(threads.threads[0].raw.stack.memory.data_size, 0x1080);
+        assert_eq!(threads.threads[1].raw.stack.memory.data_size, 0x880);
+
+        // The thread stacks are also in the 32-bit memory list.
+        let memory = minidump.get_stream::<MinidumpMemoryList>().unwrap();
+        let stack = memory.memory_at_address(0x7ffd_0000_1000).unwrap();
+        assert_eq!(stack.base_address, 0x7ffd_0000_0f80);
+        assert_eq!(stack.bytes[..4], [0xab; 4]);
+
+        // All dumped memory is included in the 64-bit memory list.  Segments may be split.
+        let memory = minidump.get_stream::<MinidumpMemory64List>().unwrap();
+        let mut regions: Vec<_> = memory
+            .iter()
+            .map(|region| (region.base_address, region.size))
+            .collect();
+        regions.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::new();
+        for (start, size) in regions {
+            match merged.last_mut() {
+                Some((last_start, last_size)) if *last_start + *last_size == start => {
+                    *last_size += size
+                }
+                _ => merged.push((start, size)),
+            }
+        }
+        assert_eq!(
+            merged,
+            [
+                (0x7f00_0000_0000, 0x1000),
+                (0x7f00_0001_0000, 0x1000),
+                (0x7ffd_0000_0000, 0x2000),
+            ]
+        );
+
+        let library = memory.memory_at_address(0x7f00_0000_0000).unwrap();
+        assert!(library.bytes.starts_with(ELF_MAGIC));
+        let font = memory.memory_at_address(0x7f00_0001_0000).unwrap();
+        assert_eq!(&font.bytes[..4], b"\0\x01\0\0");
+        let stack = memory.memory_at_address(0x7ffd_0000_1ff8).unwrap();
+        assert_eq!(stack.bytes.last(), Some(&0));
+
+        let modules = minidump.get_stream::<MinidumpModuleList>().unwrap();
+        let modules: Vec<_> = modules.iter().collect();
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].code_file(), "/usr/lib/libfoo.so");
+        assert_eq!(modules[0].base_address(), 0x7f00_0000_0000);
+        assert_eq!(modules[0].size(), 0x3000);
+        assert_eq!(
+            modules[0].code_identifier().unwrap().to_string(),
+            "5c943e8a11b45f2c8307a16b2d197c0e42424242"
+        );
+
+        let exception = minidump.get_stream::<MinidumpException>().unwrap();
+        assert_eq!(exception.raw.thread_id, 101);
+        assert_eq!(exception.raw.exception_record.exception_code, 11);
+
+        // The full command line is read from the initial stack.
+        let cmdline = minidump.get_raw_stream(LINUX_CMD_LINE_STREAM).unwrap();
+        assert_eq!(cmdline, b"/usr/bin/app\0--name\0a very long name\0");
+    }
+
+    #[test]
+    fn test_coredump_to_minidump() {
+        // The metadata does not fit before the segments, which are partially moved.
+        check_minidump(&build_core(0));
+    }
+
+    #[test]
+    fn test_coredump_to_minidump_in_place() {
+        // The metadata fits before the segments, which are not moved.
+        check_minidump(&build_core(0x10000));
+    }
+
+    #[test]
+    fn test_psargs_to_cmdline() {
+        let mut psargs = b"/usr/bin/app --name a very long".to_vec();
+        psargs.resize(PRPSINFO_PSARGS_SIZE, 0);
+        assert_eq!(
+            psargs_to_cmdline(&psargs),
+            b"/usr/bin/app\0--name\0a\0very\0long"
+        );
+    }
+}
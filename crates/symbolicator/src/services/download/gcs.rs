@@ -9,10 +9,9 @@ use futures::prelude::*;
 use parking_lot::Mutex;
 use reqwest::{header, Client, StatusCode};
 
+use symbolicator_sources::gcs::{self, request_new_token, GcsAccess, GcsError, GcsToken};
 use symbolicator_sources::{FileType, GcsSourceConfig, GcsSourceKey, ObjectId};
 
-use crate::utils::gcs::{self, request_new_token, GcsError, GcsToken};
-
 use super::locations::SourceLocation;
 use super::{content_length_timeout, DownloadError, DownloadStatus, RemoteDif, RemoteDifUri};
 
@@ -96,7 +95,7 @@ impl GcsDownloader {
         }
 
         let source_key = source_key.clone();
-        let token = request_new_token(&self.client, &source_key).await?;
+        let token = request_new_token(&self.client, &source_key, GcsAccess::ReadOnly).await?;
         metric!(counter("source.gcs.token.requests") += 1);
         let token = Arc::new(token);
         self.token_cache.lock().put(source_key, token.clone());
@@ -228,6 +227,7 @@ mod tests {
             bucket: "sentryio-system-symbols-0".to_owned(),
             prefix: "/ios".to_owned(),
             source_key: Arc::new(source_key),
+            allow_prefix_override: false,
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         })
     }
@@ -358,6 +358,7 @@ mod tests {
             bucket: String::from("bucket"),
             prefix: String::from("prefix"),
             source_key,
+            allow_prefix_override: false,
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         });
         let location = SourceLocation::new("a/key/with spaces");
@@ -21,7 +21,9 @@ pub use symbolicator_sources::{
 };
 
 use crate::cache::CacheStatus;
+use crate::types::DownloadOutcome;
 use crate::utils::futures::{self as future_utils, m, measure, CancelOnDrop};
+use crate::utils::request_diagnostics;
 
 mod filesystem;
 mod gcs;
@@ -50,7 +52,7 @@ pub enum DownloadError {
     #[error("download was cancelled")]
     Canceled,
     #[error("failed to fetch data from GCS")]
-    Gcs(#[from] crate::utils::gcs::GcsError),
+    Gcs(#[from] symbolicator_sources::gcs::GcsError),
     #[error("failed to fetch data from Sentry")]
     Sentry(sentry::SentryError),
     #[error("failed to fetch data from S3")]
@@ -93,6 +95,19 @@ impl DownloadError {
         }
     }
 
+    /// Returns the HTTP status code of the response which caused this error, if known.
+    ///
+    /// Downloaders map `401` and `403` responses to [`DownloadError::Permissions`], so these
+    /// are reported as `403`.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            DownloadError::Rejected(status) | DownloadError::S3WithCode(status, _) => Some(*status),
+            DownloadError::Sentry(sentry::SentryError::BadStatusCode(status)) => Some(*status),
+            DownloadError::Permissions => Some(StatusCode::FORBIDDEN),
+            _ => None,
+        }
+    }
+
     /// If a given cache entry is [`CacheStatus::CacheSpecificError`], this parses and extracts its
     /// contents into a [`DownloadError`]. This will return none if a
     /// non-[`CacheStatus::CacheSpecificError`] is provided.
@@ -217,17 +232,38 @@ impl DownloadService {
         source: RemoteDif,
         destination: &Path,
     ) -> Result<DownloadStatus, DownloadError> {
+        let start = Instant::now();
+        let source_id = source.source_id().clone();
+        let location = source.uri().to_string();
+
         let slf = self.clone();
         let destination = destination.to_path_buf();
-        let job = async move { slf.dispatch_download(&source, &destination).await };
+        let job = {
+            let destination = destination.clone();
+            async move { slf.dispatch_download(&source, &destination).await }
+        };
         let job = CancelOnDrop::new(self.runtime.spawn(job.bind_hub(::sentry::Hub::current())));
         let job = tokio::time::timeout(self.max_download_timeout, job);
         let job = measure("service.download", m::timed_result, None, job);
 
-        match job.await {
+        let result = match job.await {
             Ok(Ok(result)) => result,
             _ => Err(DownloadError::Canceled),
-        }
+        };
+
+        let (outcome, bytes) = match result {
+            Ok(DownloadStatus::Completed) => {
+                let bytes = tokio::fs::metadata(&destination)
+                    .await
+                    .map_or(0, |metadata| metadata.len());
+                (DownloadOutcome::Completed, bytes)
+            }
+            Ok(DownloadStatus::NotFound) => (DownloadOutcome::NotFound, 0),
+            Err(_) => (DownloadOutcome::Error, 0),
+        };
+        request_diagnostics::record_download(&source_id, location, outcome, bytes, start.elapsed());
+
+        result
     }
 
     /// Returns all objects matching the [`ObjectId`] at the source.
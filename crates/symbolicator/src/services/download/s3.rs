@@ -324,6 +324,7 @@ mod tests {
             bucket: S3_BUCKET.to_owned(),
             prefix: String::new(),
             source_key: Arc::new(source_key),
+            allow_prefix_override: false,
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         })
     }
@@ -535,6 +536,7 @@ mod tests {
             bucket: String::from("bucket"),
             prefix: String::from("prefix"),
             source_key,
+            allow_prefix_override: false,
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         });
         let location = SourceLocation::new("a/key/with spaces");
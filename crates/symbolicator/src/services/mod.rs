@@ -16,18 +16,24 @@
 //! is primarily synchronous work in the best case (everything is cached).
 //! When file fetching is needed, that fetching will happen on the `io_pool`.
 
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{Context, Result};
+use parking_lot::RwLock;
+use thiserror::Error;
 
+use crate::auth::Authenticator;
 use crate::cache::Caches;
 use crate::config::Config;
-use crate::metrics::record_task_metrics;
+use crate::logging;
+use crate::metrics::{self, record_task_metrics};
 
 pub mod bitcode;
 pub mod cacher;
 pub mod cficaches;
+mod coredump;
 pub mod download;
 pub mod il2cpp;
 mod minidump;
@@ -47,6 +53,31 @@ use self::shared_cache::SharedCacheService;
 use self::symbolication::SymbolicationActor;
 use self::symcaches::SymCacheActor;
 
+/// How often the files referenced by the configuration are checked for changes.
+const SECRET_FILES_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The parts of the state which are replaced when the configuration is reloaded.
+#[derive(Debug)]
+struct Current {
+    /// The config object.
+    config: Arc<Config>,
+    /// Authentication of API requests, if enabled.
+    auth: Option<Authenticator>,
+}
+
+/// Errors reloading the configuration.
+#[derive(Debug, Error)]
+pub enum ReloadError {
+    #[error("the configuration was not read from a file")]
+    NoConfigFile,
+
+    #[error("failed to load the configuration")]
+    Invalid(#[source] anyhow::Error),
+
+    #[error("changing {} requires a restart", .0.join(", "))]
+    RestartRequired(Vec<&'static str>),
+}
+
 /// The shared state for the service.
 #[derive(Clone, Debug)]
 pub struct Service {
@@ -54,8 +85,14 @@ pub struct Service {
     symbolication: SymbolicationActor,
     /// Actor for downloading and caching objects (no symcaches or cficaches)
     objects: ObjectsActor,
-    /// The config object.
-    config: Arc<Config>,
+    /// The local caches, whose expiry is reconfigured along with the configuration.
+    caches: Caches,
+    /// The shared cache, which is reconfigured along with the configuration.
+    shared_cache: Arc<SharedCacheService>,
+    /// The current configuration and the state derived from it.
+    current: Arc<RwLock<Current>>,
+    /// Held while reloading, so that only one reload runs at a time.
+    reloading: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl Service {
@@ -65,6 +102,8 @@ impl Service {
         cpu_pool: tokio::runtime::Handle,
     ) -> Result<Self> {
         let config = Arc::new(config);
+        let auth =
+            Authenticator::new(&config.auth).context("failed to configure authentication")?;
 
         let downloader = DownloadService::new(&config, io_pool.clone());
         let shared_cache =
@@ -74,6 +113,7 @@ impl Service {
         caches
             .clear_tmp(&config)
             .context("failed to clear tmp caches")?;
+        let service_caches = caches.clone();
         let objects = ObjectsActor::new(
             caches.object_meta,
             caches.objects,
@@ -91,7 +131,7 @@ impl Service {
         );
         let cficaches = CfiCacheActor::new(caches.cficaches, shared_cache.clone(), objects.clone());
         let ppdb_caches =
-            PortablePdbCacheActor::new(caches.ppdb_caches, shared_cache, objects.clone());
+            PortablePdbCacheActor::new(caches.ppdb_caches, shared_cache.clone(), objects.clone());
 
         let symbolication = SymbolicationActor::new(
             objects.clone(),
@@ -101,6 +141,7 @@ impl Service {
             caches.diagnostics,
             cpu_pool,
             config.max_concurrent_requests,
+            config.scheduling.clone(),
         );
         let symbolication_taskmon = symbolication.symbolication_task_monitor();
         io_pool.spawn(async move {
@@ -110,11 +151,21 @@ impl Service {
             }
         });
 
-        Ok(Self {
+        let watch_secret_files = config.path.is_some();
+        let service = Self {
             symbolication,
             objects,
-            config,
-        })
+            caches: service_caches,
+            shared_cache,
+            current: Arc::new(RwLock::new(Current { config, auth })),
+            reloading: Arc::new(tokio::sync::Mutex::new(())),
+        };
+
+        if watch_secret_files {
+            io_pool.spawn(service.clone().watch_secret_files());
+        }
+
+        Ok(service)
     }
 
     pub fn symbolication(&self) -> SymbolicationActor {
@@ -126,6 +177,131 @@ impl Service {
     }
 
     pub fn config(&self) -> Arc<Config> {
-        self.config.clone()
+        self.current.read().config.clone()
+    }
+
+    pub fn authenticator(&self) -> Option<Authenticator> {
+        self.current.read().auth.clone()
+    }
+
+    /// Reads the configuration file again and applies it.
+    ///
+    /// The new configuration is checked as a whole before any of it is applied.  If it changes
+    /// settings which only take effect after a restart, it is rejected and the current
+    /// configuration stays in place.  Otherwise, it replaces the current configuration for all
+    /// requests started afterwards.
+    pub async fn reload_config(&self) -> Result<(), ReloadError> {
+        let _reloading = self.reloading.lock().await;
+        let path = self
+            .config()
+            .path
+            .clone()
+            .ok_or(ReloadError::NoConfigFile)?;
+
+        let config_path = path.clone();
+        let config = tokio::task::spawn_blocking(move || Config::get(Some(&config_path)))
+            .await
+            .map_err(|error| ReloadError::Invalid(error.into()))?
+            .map_err(ReloadError::Invalid)?;
+
+        self.apply_config(config)?;
+        tracing::info!("Reloaded configuration from {}", path.display());
+        Ok(())
+    }
+
+    /// Resolves the secrets referenced by the current configuration again and applies them.
+    ///
+    /// Unlike [`reload_config`](Self::reload_config), this does not read the configuration file
+    /// again, so all other settings stay the same.
+    pub async fn reload_secrets(&self) -> Result<(), ReloadError> {
+        let _reloading = self.reloading.lock().await;
+        let current = self.config();
+
+        let config = tokio::task::spawn_blocking(move || current.resolve_secrets())
+            .await
+            .map_err(|error| ReloadError::Invalid(error.into()))?
+            .map_err(ReloadError::Invalid)?;
+
+        self.apply_config(config)?;
+        tracing::info!("Reloaded secrets");
+        Ok(())
+    }
+
+    /// Checks the given configuration against the current one and replaces it.
+    ///
+    /// Everything is validated before any of the configuration is applied, so a rejected
+    /// configuration leaves no trace.  Must only be called while holding the `reloading` lock.
+    fn apply_config(&self, config: Config) -> Result<(), ReloadError> {
+        let current = self.config();
+
+        let changed = current.changes_requiring_restart(&config);
+        if !changed.is_empty() {
+            return Err(ReloadError::RestartRequired(changed));
+        }
+        let auth = Authenticator::new(&config.auth)
+            .context("failed to configure authentication")
+            .map_err(ReloadError::Invalid)?;
+
+        if let Err(error) = logging::set_level(config.logging.level) {
+            tracing::error!("{:?}", error);
+        }
+        metrics::set_tags(metrics::tags(&config.metrics));
+        self.caches.set_cache_configs(&config.caches);
+        if config.shared_cache != current.shared_cache {
+            self.shared_cache.reconfigure(config.shared_cache.clone());
+        }
+
+        *self.current.write() = Current {
+            config: Arc::new(config),
+            auth,
+        };
+        Ok(())
+    }
+
+    /// Reloads the secrets whenever one of the files referenced with `file:` changes.
+    ///
+    /// This picks up rotated secrets, e.g. from Kubernetes secret mounts, without a restart.
+    async fn watch_secret_files(self) {
+        let mut modified = modification_times(&self.config().secret_files).await;
+
+        loop {
+            tokio::time::sleep(SECRET_FILES_POLL_INTERVAL).await;
+
+            let secret_files = self.config().secret_files.clone();
+            let current = modification_times(&secret_files).await;
+            if current == modified {
+                continue;
+            }
+
+            tracing::info!("Secret files changed, reloading secrets");
+            match self.reload_secrets().await {
+                Ok(()) => modified = modification_times(&self.config().secret_files).await,
+                // Try again once the files change again, they might be incomplete.
+                Err(error) => {
+                    log_reload_error(error);
+                    modified = current;
+                }
+            }
+        }
+    }
+}
+
+/// Logs an error reloading the configuration along with its causes.
+pub fn log_reload_error(error: ReloadError) {
+    let error = anyhow::Error::new(error);
+    tracing::error!("Failed to reload configuration: {:?}", error);
+}
+
+/// Returns the modification times of the given files.
+///
+/// Missing files and files without modification time are reported as `None`.
+async fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    let mut times = Vec::with_capacity(files.len());
+    for file in files {
+        let modified = tokio::fs::metadata(file)
+            .await
+            .and_then(|metadata| metadata.modified());
+        times.push(modified.ok());
     }
+    times
 }
@@ -164,11 +164,6 @@ async fn fetch_file(
     });
 
     let download_file = tempfile?;
-    let download_dir = download_file
-        .path()
-        .parent()
-        .ok_or(ObjectError::NoTempDir)?;
-
     let status = downloader.download(file_id, download_file.path()).await;
 
     match status {
@@ -199,7 +194,30 @@ async fn fetch_file(
     }
 
     tracing::trace!("Finished download of {}", cache_key);
-    let decompress_result = decompress_object_file(&download_file, tempfile_in(download_dir)?);
+    let download_dir = download_file
+        .path()
+        .parent()
+        .ok_or(ObjectError::NoTempDir)?;
+    Ok(extract_object(
+        &download_file,
+        download_dir,
+        &path,
+        &object_id,
+    )?)
+}
+
+/// Decompresses a downloaded object file and extracts the object matching `object_id`.
+///
+/// The extracted object is written to `path`, using `download_dir` for temporary files.
+/// Returns [`CacheStatus::Malformed`] if the file cannot be decompressed or parsed, and
+/// [`CacheStatus::Negative`] if it is an archive which does not contain the requested object.
+pub(super) fn extract_object(
+    download_file: &NamedTempFile,
+    download_dir: &Path,
+    path: &Path,
+    object_id: &ObjectId,
+) -> io::Result<CacheStatus> {
+    let decompress_result = decompress_object_file(download_file, tempfile_in(download_dir)?);
 
     // Treat decompression errors as malformed files. It is more likely that
     // the error comes from a corrupt file than a local file system error.
@@ -218,12 +236,12 @@ async fn fetch_file(
         Ok(archive) => archive,
         Err(e) => return Ok(CacheStatus::Malformed(e.to_string())),
     };
-    let mut persist_file = fs::File::create(&path)?;
+    let mut persist_file = fs::File::create(path)?;
     if archive.is_multi() {
         let object_opt = archive
             .objects()
             .filter_map(Result::ok)
-            .find(|object| object_matches_id(object, &object_id));
+            .find(|object| object_matches_id(object, object_id));
 
         let object = match object_opt {
             Some(object) => object,
@@ -85,6 +85,16 @@ impl ObjectMetaHandle {
     }
 }
 
+/// Returns the features an object file provides.
+pub(super) fn object_features(object: &Object<'_>) -> ObjectFeatures {
+    ObjectFeatures {
+        has_debug_info: object.has_debug_info(),
+        has_unwind_info: object.has_unwind_info(),
+        has_symbols: object.has_symbols(),
+        has_sources: object.has_sources(),
+    }
+}
+
 impl FetchFileMetaRequest {
     /// Fetches object file and derives metadata from it, storing this in the cache.
     ///
@@ -114,12 +124,7 @@ impl FetchFileMetaRequest {
             if let Ok(object) = Object::parse(&object_handle.data) {
                 let mut new_cache = fs::File::create(path)?;
 
-                let meta = ObjectFeatures {
-                    has_debug_info: object.has_debug_info(),
-                    has_unwind_info: object.has_unwind_info(),
-                    has_symbols: object.has_symbols(),
-                    has_sources: object.has_sources(),
-                };
+                let meta = object_features(&object);
 
                 tracing::trace!("Persisting object meta for {}: {:?}", cache_key, meta);
                 serde_json::to_writer(&mut new_cache, &meta)?;
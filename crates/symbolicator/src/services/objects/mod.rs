@@ -14,7 +14,9 @@ use symbolicator_sources::{FileType, ObjectId, SourceConfig, SourceId};
 use crate::cache::{Cache, CacheStatus};
 use crate::services::cacher::Cacher;
 use crate::services::download::{DownloadError, DownloadService, RemoteDif, RemoteDifUri};
-use crate::types::{AllObjectCandidates, ObjectCandidate, ObjectDownloadInfo, Scope};
+use crate::types::{
+    AllObjectCandidates, ObjectCandidate, ObjectDownloadInfo, ObjectFeatures, Scope,
+};
 
 use data_cache::FetchFileDataRequest;
 use meta_cache::FetchFileMetaRequest;
@@ -26,6 +28,7 @@ use super::shared_cache::SharedCacheService;
 
 mod data_cache;
 mod meta_cache;
+mod probe;
 
 /// Errors happening while fetching objects.
 pub enum ObjectError {
@@ -434,32 +437,13 @@ fn create_candidate_info(
     meta_lookup: &Result<Arc<ObjectMetaHandle>, CacheLookupError>,
 ) -> ObjectCandidate {
     match meta_lookup {
-        Ok(meta_handle) => {
-            let download = match &meta_handle.status {
-                CacheStatus::Positive => ObjectDownloadInfo::Ok {
-                    features: meta_handle.features(),
-                },
-                CacheStatus::Negative => ObjectDownloadInfo::NotFound,
-                CacheStatus::Malformed(_) => ObjectDownloadInfo::Malformed,
-                CacheStatus::CacheSpecificError(message) => {
-                    match DownloadError::from_cache(&meta_handle.status) {
-                        Some(DownloadError::Permissions) => ObjectDownloadInfo::NoPerm {
-                            details: String::default(),
-                        },
-                        Some(_) | None => ObjectDownloadInfo::Error {
-                            details: message.clone(),
-                        },
-                    }
-                }
-            };
-            ObjectCandidate {
-                source: meta_handle.file_source.source_id().clone(),
-                location: meta_handle.file_source.uri(),
-                download,
-                unwind: Default::default(),
-                debug: Default::default(),
-            }
-        }
+        Ok(meta_handle) => ObjectCandidate {
+            source: meta_handle.file_source.source_id().clone(),
+            location: meta_handle.file_source.uri(),
+            download: create_download_info(&meta_handle.status, meta_handle.features()),
+            unwind: Default::default(),
+            debug: Default::default(),
+        },
         Err(wrapped_error) => {
             let details = wrapped_error.error.to_string();
             ObjectCandidate {
@@ -472,3 +456,20 @@ fn create_candidate_info(
         }
     }
 }
+
+/// Build the [`ObjectDownloadInfo`] for an object with the given cache status.
+fn create_download_info(status: &CacheStatus, features: ObjectFeatures) -> ObjectDownloadInfo {
+    match status {
+        CacheStatus::Positive => ObjectDownloadInfo::Ok { features },
+        CacheStatus::Negative => ObjectDownloadInfo::NotFound,
+        CacheStatus::Malformed(_) => ObjectDownloadInfo::Malformed,
+        CacheStatus::CacheSpecificError(message) => match DownloadError::from_cache(status) {
+            Some(DownloadError::Permissions) => ObjectDownloadInfo::NoPerm {
+                details: String::default(),
+            },
+            Some(_) | None => ObjectDownloadInfo::Error {
+                details: message.clone(),
+            },
+        },
+    }
+}
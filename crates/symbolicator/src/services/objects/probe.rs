@@ -0,0 +1,187 @@
+//! Diagnostics of object file lookups.
+//!
+//! Unlike [`ObjectsActor::find`], probing reports the outcome of every location of an object
+//! on every source.  Locations which are not cached are downloaded into temporary files which
+//! are never written to the caches, so probing does not change what symbolication sees.
+
+use std::time::Instant;
+
+use futures::future;
+use reqwest::StatusCode;
+use sentry::{Hub, SentryFutureExt};
+
+use symbolic::common::ByteView;
+use symbolic::debuginfo::Object;
+use symbolicator_sources::ObjectId;
+
+use crate::cache::CacheStatus;
+use crate::services::download::{DownloadStatus, RemoteDif};
+use crate::types::{
+    LocationProbe, ObjectDownloadInfo, ObjectFeatures, ProbeCacheStatus, Scope, SourceProbe,
+};
+
+use super::data_cache::extract_object;
+use super::meta_cache::{object_features, FetchFileMetaRequest};
+use super::{create_download_info, FindObject, ObjectError, ObjectsActor};
+
+impl ObjectsActor {
+    /// Looks up an object on all sources and reports the outcome of every location.
+    ///
+    /// Locations with a cached result are reported as cache hits without contacting the
+    /// source.  If `bypass_negative_cache` is set, cached negative and error results are
+    /// ignored and these locations are downloaded again.
+    pub async fn probe(
+        &self,
+        request: FindObject,
+        bypass_negative_cache: bool,
+    ) -> Vec<SourceProbe> {
+        let FindObject {
+            filetypes,
+            scope,
+            identifier,
+            sources,
+            ..
+        } = request;
+
+        let queries = sources.iter().map(|source| {
+            let scope = &scope;
+            let identifier = &identifier;
+            async move {
+                let start = Instant::now();
+                let listing = self
+                    .download_svc
+                    .list_files(source.clone(), filetypes, identifier)
+                    .await;
+                let list_duration_ms = elapsed_ms(start);
+
+                let (file_sources, error) = match listing {
+                    Ok(file_sources) => (file_sources, None),
+                    Err(err) => (Vec::new(), Some(err.for_cache())),
+                };
+
+                let locations = file_sources.into_iter().map(|file_source| {
+                    self.probe_location(file_source, identifier, scope, bypass_negative_cache)
+                });
+
+                SourceProbe {
+                    source: source.id().clone(),
+                    error,
+                    list_duration_ms,
+                    locations: future::join_all(locations).await,
+                }
+            }
+            .bind_hub(Hub::new_from_top(Hub::current()))
+        });
+
+        future::join_all(queries).await
+    }
+
+    /// Looks up a single location in the metadata cache, downloading it if needed.
+    async fn probe_location(
+        &self,
+        file_source: RemoteDif,
+        object_id: &ObjectId,
+        scope: &Scope,
+        bypass_negative_cache: bool,
+    ) -> LocationProbe {
+        let start = Instant::now();
+        let location = file_source.uri();
+
+        let scope = if file_source.is_public() {
+            Scope::Global
+        } else {
+            scope.clone()
+        };
+        let request = FetchFileMetaRequest {
+            scope,
+            file_source: file_source.clone(),
+            object_id: object_id.clone(),
+            data_cache: self.data_cache.clone(),
+            download_svc: self.download_svc.clone(),
+        };
+
+        let cache = match self.meta_cache.lookup(&request).await {
+            Ok(Some(meta)) if meta.status == CacheStatus::Positive || !bypass_negative_cache => {
+                return LocationProbe {
+                    location,
+                    cache: ProbeCacheStatus::Hit,
+                    status_code: None,
+                    download: create_download_info(&meta.status, meta.features),
+                    duration_ms: elapsed_ms(start),
+                };
+            }
+            Ok(Some(_)) => ProbeCacheStatus::Bypassed,
+            Ok(None) | Err(_) => ProbeCacheStatus::Miss,
+        };
+
+        let (status_code, download) = self
+            .download_location(file_source, object_id)
+            .await
+            .unwrap_or_else(|err| {
+                let details = err.to_string();
+                (None, ObjectDownloadInfo::Error { details })
+            });
+
+        LocationProbe {
+            location,
+            cache,
+            status_code: status_code.map(|status| status.as_u16()),
+            download,
+            duration_ms: elapsed_ms(start),
+        }
+    }
+
+    /// Downloads an object into a temporary file without storing it in the caches.
+    ///
+    /// Returns the HTTP status code of the download, if known, along with the download info
+    /// the object would have in the candidates list.
+    async fn download_location(
+        &self,
+        file_source: RemoteDif,
+        object_id: &ObjectId,
+    ) -> Result<(Option<StatusCode>, ObjectDownloadInfo), ObjectError> {
+        let is_http = !matches!(file_source, RemoteDif::Filesystem(_));
+        let download_file = self.data_cache.tempfile()?;
+        let result = self
+            .download_svc
+            .clone()
+            .download(file_source, download_file.path())
+            .await;
+
+        let status_code = match &result {
+            Ok(DownloadStatus::Completed) if is_http => Some(StatusCode::OK),
+            Ok(DownloadStatus::NotFound) if is_http => Some(StatusCode::NOT_FOUND),
+            Ok(_) => None,
+            Err(err) => err.status_code(),
+        };
+
+        let mut features = ObjectFeatures::default();
+        let status = match result {
+            Ok(DownloadStatus::Completed) => {
+                let object_file = self.data_cache.tempfile()?;
+                let download_dir = download_file
+                    .path()
+                    .parent()
+                    .ok_or(ObjectError::NoTempDir)?;
+                let status =
+                    extract_object(&download_file, download_dir, object_file.path(), object_id)?;
+                if status == CacheStatus::Positive {
+                    let data = ByteView::open(object_file.path())?;
+                    features = Object::parse(&data)
+                        .map(|object| object_features(&object))
+                        .unwrap_or_default();
+                }
+                status
+            }
+            Ok(DownloadStatus::NotFound) => CacheStatus::Negative,
+            Err(err) => CacheStatus::CacheSpecificError(err.for_cache()),
+        };
+
+        Ok((status_code, create_download_info(&status, features)))
+    }
+}
+
+/// Returns the milliseconds elapsed since `start`.
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
@@ -26,13 +26,14 @@ use tokio::sync::{mpsc, oneshot, RwLock};
 use tokio_util::io::{ReaderStream, StreamReader};
 use url::Url;
 
+use symbolicator_sources::gcs::{self, GcsError};
+
 use crate::cache::{
     CacheName, FilesystemSharedCacheConfig, GcsSharedCacheConfig, SharedCacheBackendConfig,
     SharedCacheConfig,
 };
 use crate::services::download::MeasureSourceDownloadGuard;
 use crate::utils::futures::CancelOnDrop;
-use crate::utils::gcs::{self, GcsError};
 
 use super::cacher::CacheKey;
 
@@ -592,6 +593,20 @@ impl SharedCacheService {
         slf
     }
 
+    /// Replaces the backend of the shared cache, e.g. when its configuration was reloaded.
+    ///
+    /// The current backend stays in use until the new one is ready.  If the new backend cannot
+    /// be created, the current one is kept.
+    pub fn reconfigure(&self, config: Option<SharedCacheConfig>) {
+        let inner = self.inner.clone();
+        match config {
+            Some(cfg) => self.runtime.spawn(Self::init(inner, cfg)),
+            None => self
+                .runtime
+                .spawn(async move { *inner.write().await = None }),
+        };
+    }
+
     async fn init(inner: Arc<RwLock<Option<InnerSharedCacheService>>>, config: SharedCacheConfig) {
         let (tx, rx) = mpsc::channel(config.max_upload_queue_size);
         if let Some(backend) = SharedCacheBackend::maybe_new(config.backend).await {
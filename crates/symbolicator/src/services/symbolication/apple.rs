@@ -1,10 +1,12 @@
 use std::fs::File;
+use std::io::Read;
 use std::sync::Arc;
 use std::time::Duration;
 
-use apple_crash_report_parser::{AppleCrashReport, ParseError};
+use apple_crash_report_parser::AppleCrashReport;
 use chrono::{DateTime, Utc};
 use regex::Regex;
+use uuid::Uuid;
 
 use symbolic::common::{Arch, CodeId, DebugId};
 use symbolicator_sources::{ObjectType, SourceConfig};
@@ -16,61 +18,28 @@ use crate::types::{
 use crate::utils::futures::{m, measure};
 use crate::utils::hex::HexValue;
 
+use super::ips::{
+    is_ips_crash_report, parse_ips_crash_report, IpsCrashReport, IpsImage, IpsOsVersion,
+};
 use super::{StacktraceOrigin, SymbolicateStacktraces, SymbolicationActor, SymbolicationError};
 
 impl SymbolicationActor {
     async fn parse_apple_crash_report(
         &self,
         scope: Scope,
-        report: File,
+        mut report: File,
         sources: Arc<[SourceConfig]>,
         options: RequestOptions,
     ) -> Result<(SymbolicateStacktraces, AppleCrashReportState), SymbolicationError> {
         let parse_future = async {
-            let report = AppleCrashReport::from_reader(report)?;
-            let mut metadata = report.metadata;
-
-            let arch = report
-                .code_type
-                .as_ref()
-                .and_then(|code_type| code_type.split(' ').next())
-                .and_then(|word| word.parse().ok())
-                .unwrap_or_default();
-
-            let modules = report
-                .binary_images
-                .into_iter()
-                .map(map_apple_binary_image)
-                .collect();
+            let mut data = Vec::new();
+            report.read_to_end(&mut data).map_err(anyhow::Error::from)?;
 
-            let mut stacktraces = Vec::with_capacity(report.threads.len());
-
-            for thread in report.threads {
-                let registers = thread
-                    .registers
-                    .unwrap_or_default()
-                    .into_iter()
-                    .map(|(name, addr)| (name, HexValue(addr.0)))
-                    .collect();
-
-                let frames = thread
-                    .frames
-                    .into_iter()
-                    .map(|frame| RawFrame {
-                        instruction_addr: HexValue(frame.instruction_addr.0),
-                        package: frame.module,
-                        ..RawFrame::default()
-                    })
-                    .collect();
-
-                stacktraces.push(RawStacktrace {
-                    thread_id: Some(thread.id),
-                    thread_name: thread.name,
-                    is_requesting: Some(thread.crashed),
-                    registers,
-                    frames,
-                });
-            }
+            let (modules, stacktraces, state) = if is_ips_crash_report(&data) {
+                map_ips_crash_report(parse_ips_crash_report(&data)?)
+            } else {
+                map_legacy_crash_report(AppleCrashReport::from_reader(data.as_slice())?)
+            };
 
             let request = SymbolicateStacktraces {
                 modules,
@@ -82,49 +51,12 @@ impl SymbolicationActor {
                 options,
             };
 
-            let mut system_info = SystemInfo {
-                os_name: metadata.remove("OS Version").unwrap_or_default(),
-                device_model: metadata.remove("Hardware Model").unwrap_or_default(),
-                cpu_arch: arch,
-                ..SystemInfo::default()
-            };
-
-            if let Some(captures) = OS_MACOS_REGEX.captures(&system_info.os_name) {
-                system_info.os_version = captures
-                    .name("version")
-                    .map(|m| m.as_str().to_string())
-                    .unwrap_or_default();
-                system_info.os_build = captures
-                    .name("build")
-                    .map(|m| m.as_str().to_string())
-                    .unwrap_or_default();
-                system_info.os_name = "macOS".to_string();
-            }
-
-            // https://developer.apple.com/library/archive/technotes/tn2151/_index.html
-            let crash_reason = metadata.remove("Exception Type");
-            let crash_details = report
-                .application_specific_information
-                .or_else(|| metadata.remove("Exception Message"))
-                .or_else(|| metadata.remove("Exception Subtype"))
-                .or_else(|| metadata.remove("Exception Codes"));
-
-            let state = AppleCrashReportState {
-                timestamp: report.timestamp,
-                system_info,
-                crash_reason,
-                crash_details,
-            };
-
-            Ok::<_, ParseError>((request, state))
+            Ok::<_, SymbolicationError>((request, state))
         };
 
         let future = tokio::time::timeout(Duration::from_secs(1200), parse_future);
         let future = measure("parse_apple_crash_report", m::timed_result, None, future);
-        future
-            .await
-            .map(|res| res.map_err(SymbolicationError::from))
-            .unwrap_or(Err(SymbolicationError::Timeout))
+        future.await.unwrap_or(Err(SymbolicationError::Timeout))
     }
 
     pub(super) async fn do_process_apple_crash_report(
@@ -176,18 +108,211 @@ impl AppleCrashReportState {
     }
 }
 
+/// The modules, stacktraces and metadata of a parsed crash report.
+type ParsedCrashReport = (
+    Vec<CompleteObjectInfo>,
+    Vec<RawStacktrace>,
+    AppleCrashReportState,
+);
+
+/// Converts a crash report in the legacy text format.
+fn map_legacy_crash_report(report: AppleCrashReport) -> ParsedCrashReport {
+    let mut metadata = report.metadata;
+
+    let arch = report
+        .code_type
+        .as_ref()
+        .and_then(|code_type| code_type.split(' ').next())
+        .and_then(|word| word.parse().ok())
+        .unwrap_or_default();
+
+    let modules = report
+        .binary_images
+        .into_iter()
+        .map(map_apple_binary_image)
+        .collect();
+
+    let mut stacktraces = Vec::with_capacity(report.threads.len());
+
+    for thread in report.threads {
+        let registers = thread
+            .registers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, addr)| (name, HexValue(addr.0)))
+            .collect();
+
+        let frames = thread
+            .frames
+            .into_iter()
+            .map(|frame| RawFrame {
+                instruction_addr: HexValue(frame.instruction_addr.0),
+                package: frame.module,
+                ..RawFrame::default()
+            })
+            .collect();
+
+        stacktraces.push(RawStacktrace {
+            thread_id: Some(thread.id),
+            thread_name: thread.name,
+            is_requesting: Some(thread.crashed),
+            registers,
+            frames,
+        });
+    }
+
+    let mut system_info = SystemInfo {
+        os_name: metadata.remove("OS Version").unwrap_or_default(),
+        device_model: metadata.remove("Hardware Model").unwrap_or_default(),
+        cpu_arch: arch,
+        ..SystemInfo::default()
+    };
+
+    if let Some(captures) = OS_MACOS_REGEX.captures(&system_info.os_name) {
+        system_info.os_version = captures
+            .name("version")
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        system_info.os_build = captures
+            .name("build")
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        system_info.os_name = "macOS".to_string();
+    }
+
+    // https://developer.apple.com/library/archive/technotes/tn2151/_index.html
+    let crash_reason = metadata.remove("Exception Type");
+    let crash_details = report
+        .application_specific_information
+        .or_else(|| metadata.remove("Exception Message"))
+        .or_else(|| metadata.remove("Exception Subtype"))
+        .or_else(|| metadata.remove("Exception Codes"));
+
+    let state = AppleCrashReportState {
+        timestamp: report.timestamp,
+        system_info,
+        crash_reason,
+        crash_details,
+    };
+
+    (modules, stacktraces, state)
+}
+
+/// Converts a crash report in the JSON `.ips` format.
+fn map_ips_crash_report(report: IpsCrashReport) -> ParsedCrashReport {
+    let timestamp = report.timestamp();
+    let arch = report.arch();
+    let application_specific_information = report.application_specific_information();
+
+    // Frames reference images by their index in `usedImages`, so resolve addresses before
+    // images without an identifier are dropped.
+    let images = &report.used_images;
+    let stacktraces = report
+        .threads
+        .into_iter()
+        .map(|thread| {
+            let frames = thread
+                .frames
+                .iter()
+                .map(|frame| {
+                    let base = images.get(frame.image_index).map_or(0, |image| image.base);
+                    RawFrame {
+                        instruction_addr: HexValue(base.wrapping_add(frame.image_offset)),
+                        ..RawFrame::default()
+                    }
+                })
+                .collect();
+
+            RawStacktrace {
+                thread_id: thread.id,
+                thread_name: thread.name.or(thread.queue),
+                is_requesting: Some(thread.triggered),
+                registers: thread
+                    .thread_state
+                    .map(|state| state.registers())
+                    .unwrap_or_default(),
+                frames,
+            }
+        })
+        .collect();
+
+    let modules = report
+        .used_images
+        .into_iter()
+        .filter_map(map_ips_binary_image)
+        .collect();
+
+    let mut system_info = SystemInfo {
+        device_model: report.model_code.unwrap_or_default(),
+        cpu_arch: arch,
+        ..SystemInfo::default()
+    };
+
+    match report.os_version {
+        Some(IpsOsVersion { train, build }) => {
+            // The train consists of the OS name and version, such as `macOS 12.3`.
+            let train = train.unwrap_or_default();
+            match train.rsplit_once(' ') {
+                Some((name, version)) => {
+                    system_info.os_name = name.to_string();
+                    system_info.os_version = version.to_string();
+                }
+                None => system_info.os_name = train,
+            }
+            system_info.os_build = build.unwrap_or_default();
+        }
+        None => system_info.os_name = report.header.os_version.unwrap_or_default(),
+    }
+
+    let (crash_reason, crash_details) = match report.exception {
+        Some(exception) => {
+            // Mirror the `Exception Type` of the legacy format, such as `EXC_CRASH (SIGABRT)`.
+            let crash_reason = match (exception.ty, exception.signal) {
+                (Some(ty), Some(signal)) => Some(format!("{} ({})", ty, signal)),
+                (ty, signal) => ty.or(signal),
+            };
+            let crash_details = application_specific_information
+                .or(exception.message)
+                .or(exception.subtype)
+                .or(exception.codes);
+            (crash_reason, crash_details)
+        }
+        None => (None, application_specific_information),
+    };
+
+    let state = AppleCrashReportState {
+        timestamp,
+        system_info,
+        crash_reason,
+        crash_details,
+    };
+
+    (modules, stacktraces, state)
+}
+
 fn map_apple_binary_image(image: apple_crash_report_parser::BinaryImage) -> CompleteObjectInfo {
-    let code_id = CodeId::from_binary(&image.uuid.as_bytes()[..]);
-    let debug_id = DebugId::from_uuid(image.uuid);
+    apple_object_info(image.uuid, image.path, image.addr.0, image.size)
+}
+
+/// Converts an image of an `.ips` report, skipping images without an identifier.
+fn map_ips_binary_image(image: IpsImage) -> Option<CompleteObjectInfo> {
+    let uuid = image.uuid.filter(|uuid| !uuid.is_nil())?;
+    let path = image.path.or(image.name).unwrap_or_default();
+    Some(apple_object_info(uuid, path, image.base, image.size))
+}
+
+fn apple_object_info(uuid: Uuid, path: String, addr: u64, size: u64) -> CompleteObjectInfo {
+    let code_id = CodeId::from_binary(&uuid.as_bytes()[..]);
+    let debug_id = DebugId::from_uuid(uuid);
 
     let raw_info = RawObjectInfo {
         ty: ObjectType::Macho,
         code_id: Some(code_id.to_string()),
-        code_file: Some(image.path.clone()),
+        code_file: Some(path.clone()),
         debug_id: Some(debug_id.to_string()),
-        debug_file: Some(image.path),
-        image_addr: HexValue(image.addr.0),
-        image_size: match image.size {
+        debug_file: Some(path),
+        image_addr: HexValue(addr),
+        image_size: match size {
             0 => None,
             size => Some(size),
         },
@@ -196,3 +321,62 @@ fn map_apple_binary_image(image: apple_crash_report_parser::BinaryImage) -> Comp
 
     raw_info.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_ips_crash_report() {
+        let report = br#"{"bug_type":"309","os_version":"macOS 12.3 (21E230)"}
+{
+  "cpuType" : "X86-64",
+  "osVersion" : {"train" : "macOS 12.3", "build" : "21E230"},
+  "exception" : {"type" : "EXC_CRASH", "signal" : "SIGABRT", "codes" : "0x0000000000000000, 0x0000000000000000"},
+  "threads" : [
+    {"triggered" : true, "id" : 42, "queue" : "com.apple.main-thread", "frames" : [
+      {"imageOffset" : 4096, "imageIndex" : 1},
+      {"imageOffset" : 16, "imageIndex" : 0}
+    ]}
+  ],
+  "usedImages" : [
+    {"base" : 4294967296, "size" : 16384, "uuid" : "4a9bd7f6-9fa6-3bb4-a2b0-2ac5b1b1f8d7", "path" : "/usr/local/bin/crash"},
+    {"base" : 140703128616960, "size" : 0, "uuid" : "00000000-0000-0000-0000-000000000000"}
+  ]
+}"#;
+
+        let report = parse_ips_crash_report(report).unwrap();
+        let (modules, stacktraces, state) = map_ips_crash_report(report);
+
+        assert_eq!(modules.len(), 1);
+        assert_eq!(
+            modules[0].raw.debug_id.as_deref(),
+            Some("4a9bd7f6-9fa6-3bb4-a2b0-2ac5b1b1f8d7")
+        );
+        assert_eq!(modules[0].raw.image_addr, HexValue(0x1_0000_0000));
+
+        let stacktrace = &stacktraces[0];
+        assert_eq!(stacktrace.thread_id, Some(42));
+        assert_eq!(
+            stacktrace.thread_name.as_deref(),
+            Some("com.apple.main-thread")
+        );
+        assert_eq!(stacktrace.is_requesting, Some(true));
+        let addrs: Vec<_> = stacktrace
+            .frames
+            .iter()
+            .map(|f| f.instruction_addr)
+            .collect();
+        assert_eq!(addrs, [HexValue(0x7ff8_0000_1000), HexValue(0x1_0000_0010)]);
+
+        assert_eq!(state.system_info.os_name, "macOS");
+        assert_eq!(state.system_info.os_version, "12.3");
+        assert_eq!(state.system_info.os_build, "21E230");
+        assert_eq!(state.system_info.cpu_arch, Arch::Amd64);
+        assert_eq!(state.crash_reason.as_deref(), Some("EXC_CRASH (SIGABRT)"));
+        assert_eq!(
+            state.crash_details.as_deref(),
+            Some("0x0000000000000000, 0x0000000000000000")
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use symbolicator_sources::{FileType, ObjectType, SourceConfig};
+
+use crate::cache::CacheStatus;
+use crate::services::cficaches::FetchCfiCache;
+use crate::services::objects::{FindObject, ObjectPurpose};
+use crate::services::ppdb_caches::FetchPortablePdbCache;
+use crate::services::symcaches::FetchSymCache;
+use crate::types::{
+    AllObjectCandidates, ModuleDiagnostics, ObjectFeatures, ObjectFileStatus, RawObjectInfo, Scope,
+};
+
+use super::{object_id_from_object_info, SymbolicationActor};
+
+/// A request to diagnose the lookup of a single module.
+#[derive(Debug, Clone)]
+pub struct DiagnoseModule {
+    /// The scope of the request.
+    pub scope: Scope,
+
+    /// The sources to look up the module on.
+    pub sources: Arc<[SourceConfig]>,
+
+    /// The module to look up.
+    pub module: RawObjectInfo,
+
+    /// Download locations again even if a negative or error result is cached.
+    pub bypass_negative_cache: bool,
+}
+
+impl SymbolicationActor {
+    /// Looks up a single module and reports every location probed along the way.
+    ///
+    /// The module is first probed on all sources, after which its symcache and CFI cache are
+    /// fetched the same way symbolication does.  Probing never writes to the caches, so the
+    /// candidates reflect what symbolication would see regardless of `bypass_negative_cache`.
+    pub async fn diagnose_module(&self, request: DiagnoseModule) -> ModuleDiagnostics {
+        let DiagnoseModule {
+            scope,
+            sources,
+            module,
+            bypass_negative_cache,
+        } = request;
+        let object_type = module.ty;
+        let identifier = object_id_from_object_info(&module);
+
+        let start = Instant::now();
+        let probes = self
+            .objects
+            .probe(
+                FindObject {
+                    filetypes: FileType::from_object_type(object_type),
+                    purpose: ObjectPurpose::Debug,
+                    scope: scope.clone(),
+                    identifier: identifier.clone(),
+                    sources: sources.clone(),
+                },
+                bypass_negative_cache,
+            )
+            .await;
+        let probe_duration_ms = elapsed_ms(start);
+
+        let mut features = ObjectFeatures::default();
+        let mut candidates = AllObjectCandidates::default();
+
+        let start = Instant::now();
+        let debug_status = if matches!(object_type, ObjectType::PeDotnet) {
+            let request = FetchPortablePdbCache {
+                identifier: identifier.clone(),
+                sources: sources.clone(),
+                scope: scope.clone(),
+            };
+            match self.ppdb_caches.fetch(request).await {
+                Ok(ppdb_cache) => {
+                    features.merge(ppdb_cache.features());
+                    candidates.merge(ppdb_cache.candidates());
+                    match ppdb_cache.parse() {
+                        Ok(Some(_)) => ObjectFileStatus::Found,
+                        Ok(None) => ObjectFileStatus::Missing,
+                        Err(e) => (&e).into(),
+                    }
+                }
+                Err(e) => e.as_ref().into(),
+            }
+        } else {
+            let request = FetchSymCache {
+                object_type,
+                identifier: identifier.clone(),
+                sources: sources.clone(),
+                scope: scope.clone(),
+            };
+            match self.symcaches.fetch(request).await {
+                Ok(symcache) => {
+                    features.merge(symcache.features());
+                    candidates.merge(symcache.candidates());
+                    match symcache.parse() {
+                        Ok(Some(_)) => ObjectFileStatus::Found,
+                        Ok(None) => ObjectFileStatus::Missing,
+                        Err(e) => (&e).into(),
+                    }
+                }
+                Err(e) => e.as_ref().into(),
+            }
+        };
+        let debug_duration_ms = elapsed_ms(start);
+
+        let start = Instant::now();
+        let request = FetchCfiCache {
+            object_type,
+            identifier,
+            sources,
+            scope,
+        };
+        let unwind_status = match self.cficaches.fetch(request).await {
+            Ok(cficache) => {
+                features.merge(cficache.features());
+                candidates.merge(cficache.candidates());
+                match cficache.status() {
+                    CacheStatus::Positive => ObjectFileStatus::Found,
+                    CacheStatus::Negative => ObjectFileStatus::Missing,
+                    CacheStatus::Malformed(_) | CacheStatus::CacheSpecificError(_) => {
+                        ObjectFileStatus::Malformed
+                    }
+                }
+            }
+            Err(e) => e.as_ref().into(),
+        };
+        let unwind_duration_ms = elapsed_ms(start);
+
+        ModuleDiagnostics {
+            debug_status,
+            unwind_status,
+            features,
+            candidates,
+            probes,
+            probe_duration_ms,
+            debug_duration_ms,
+            unwind_duration_ms,
+        }
+    }
+}
+
+/// Returns the milliseconds elapsed since `start`.
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
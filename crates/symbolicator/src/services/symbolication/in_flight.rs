@@ -0,0 +1,225 @@
+//! Tracking of the stage and pending modules of in-flight requests.
+//!
+//! The tracker also holds the trigger to cancel its request.
+//!
+//! The tracker of a request is stored in a task-local, so the stages of a request can report
+//! progress without passing it through every call.  Only code running in the request's own task
+//! reports progress, computations spawned on its behalf do not.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::channel::oneshot;
+use parking_lot::Mutex;
+use symbolicator_sources::ObjectId;
+
+use crate::types::{InFlightRequest, RequestId, RequestKind, RequestStage, Scope};
+
+tokio::task_local! {
+    static CURRENT: Arc<RequestTracker>;
+}
+
+#[derive(Debug)]
+struct Progress {
+    stage: RequestStage,
+    /// The modules being waited on, along with the number of lookups waiting for them.
+    waiting_on: BTreeMap<String, usize>,
+}
+
+/// Tracks the progress of a single request.
+#[derive(Debug)]
+pub(super) struct RequestTracker {
+    kind: RequestKind,
+    scope: Scope,
+    start: Instant,
+    progress: Mutex<Progress>,
+    cancel: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl RequestTracker {
+    /// Creates a tracker for a new request, which is cancelled by sending on `cancel`.
+    pub fn new(kind: RequestKind, scope: Scope, cancel: oneshot::Sender<()>) -> Arc<Self> {
+        Arc::new(Self {
+            kind,
+            scope,
+            start: Instant::now(),
+            progress: Mutex::new(Progress {
+                stage: RequestStage::Queued,
+                waiting_on: BTreeMap::new(),
+            }),
+            cancel: Mutex::new(Some(cancel)),
+        })
+    }
+
+    /// Runs the future as the given request.
+    pub async fn scope<F: std::future::Future>(self: Arc<Self>, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// Sets the stage of the request.
+    pub fn set_stage(&self, stage: RequestStage) {
+        self.progress.lock().stage = stage;
+    }
+
+    /// Cancels the request.
+    ///
+    /// Returns `false` if the request has already been cancelled or has finished.  Cancelling
+    /// and [finishing](Self::finish) race for the same trigger, so exactly one of them wins.
+    pub fn cancel(&self) -> bool {
+        match self.cancel.lock().take() {
+            Some(sender) => {
+                // The computation may have completed already, in which case the request is
+                // still reported as cancelled once it tries to finish.
+                sender.send(()).ok();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the request as finished, so that it can no longer be cancelled.
+    ///
+    /// Returns `false` if the request has been cancelled before, in which case its result must
+    /// be discarded.
+    pub fn finish(&self) -> bool {
+        self.cancel.lock().take().is_some()
+    }
+
+    /// Returns a snapshot of the request's progress.
+    pub fn snapshot(&self, request_id: RequestId) -> InFlightRequest {
+        let progress = self.progress.lock();
+        InFlightRequest {
+            request_id,
+            kind: self.kind,
+            scope: self.scope.clone(),
+            age_ms: self.start.elapsed().as_millis() as u64,
+            stage: progress.stage,
+            waiting_on: progress.waiting_on.keys().cloned().collect(),
+        }
+    }
+}
+
+/// Sets the stage of the current request.
+pub fn set_stage(stage: RequestStage) {
+    CURRENT.try_with(|tracker| tracker.set_stage(stage)).ok();
+}
+
+/// Marks the current request as waiting on a module until the returned guard is dropped.
+pub fn waiting_on(identifier: &ObjectId) -> WaitingGuard {
+    let tracker = CURRENT.try_with(Arc::clone).ok();
+    let module = module_label(identifier);
+    if let Some(ref tracker) = tracker {
+        *tracker
+            .progress
+            .lock()
+            .waiting_on
+            .entry(module.clone())
+            .or_default() += 1;
+    }
+    WaitingGuard { tracker, module }
+}
+
+/// Removes a module from the modules a request is waiting on when dropped.
+#[derive(Debug)]
+pub struct WaitingGuard {
+    tracker: Option<Arc<RequestTracker>>,
+    module: String,
+}
+
+impl Drop for WaitingGuard {
+    fn drop(&mut self) {
+        if let Some(ref tracker) = self.tracker {
+            let waiting_on = &mut tracker.progress.lock().waiting_on;
+            if let Some(count) = waiting_on.get_mut(&self.module) {
+                *count -= 1;
+                if *count == 0 {
+                    waiting_on.remove(&self.module);
+                }
+            }
+        }
+    }
+}
+
+/// Formats a module as its file name along with its debug id.
+fn module_label(identifier: &ObjectId) -> String {
+    let path = identifier
+        .code_file
+        .as_deref()
+        .or(identifier.debug_file.as_deref());
+    let name = path.map(|path| path.rsplit(&['/', '\\'][..]).next().unwrap_or(path));
+
+    match (name, identifier.debug_id) {
+        (Some(name), Some(debug_id)) => format!("{} ({})", name, debug_id),
+        (Some(name), None) => name.to_owned(),
+        (None, Some(debug_id)) => debug_id.to_string(),
+        (None, None) => "<unknown>".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tracker() {
+        let (cancel, _cancelled) = oneshot::channel();
+        let tracker = RequestTracker::new(RequestKind::Minidump, Scope::Global, cancel);
+        let request_id = RequestId::new(Uuid::nil());
+        let identifier = ObjectId {
+            code_file: Some("C:\\Windows\\System32\\ntdll.dll".into()),
+            debug_id: "971f98e5-ce60-41ff-b2d7-235bbeb34578-1".parse().ok(),
+            ..Default::default()
+        };
+
+        tracker
+            .clone()
+            .scope(async {
+                set_stage(RequestStage::Stackwalk);
+                let first = waiting_on(&identifier);
+                let second = waiting_on(&identifier);
+
+                let snapshot = tracker.snapshot(request_id);
+                assert_eq!(snapshot.stage, RequestStage::Stackwalk);
+                assert_eq!(
+                    snapshot.waiting_on,
+                    ["ntdll.dll (971f98e5-ce60-41ff-b2d7-235bbeb34578-1)"]
+                );
+
+                drop(first);
+                assert_eq!(tracker.snapshot(request_id).waiting_on.len(), 1);
+                drop(second);
+                assert!(tracker.snapshot(request_id).waiting_on.is_empty());
+            })
+            .await;
+
+        // A request can only be cancelled once.
+        assert!(tracker.cancel());
+        assert!(!tracker.cancel());
+        assert!(!tracker.finish());
+    }
+
+    #[test]
+    fn test_cancel_after_finish() {
+        let (cancel, mut cancelled) = oneshot::channel();
+        let tracker = RequestTracker::new(RequestKind::Minidump, Scope::Global, cancel);
+
+        assert!(tracker.finish());
+        assert!(!tracker.cancel());
+        assert!(!tracker.finish());
+        assert!(cancelled.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_cancel_after_completion() {
+        // The computation has completed, but the request has not finished yet.
+        let (cancel, cancelled) = oneshot::channel();
+        let tracker = RequestTracker::new(RequestKind::Minidump, Scope::Global, cancel);
+        drop(cancelled);
+
+        assert!(tracker.cancel());
+        assert!(!tracker.finish());
+    }
+}
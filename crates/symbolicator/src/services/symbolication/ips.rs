@@ -0,0 +1,306 @@
+//! Parser for the JSON crash report format (`.ips`) of macOS 12 and iOS 15 and later.
+//!
+//! An `.ips` file consists of two JSON documents: A single line with a header describing the
+//! report, followed by the crash report itself.  Only the fields needed for symbolication are
+//! parsed here; everything else is ignored.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use symbolic::common::Arch;
+
+use crate::types::Registers;
+use crate::utils::hex::HexValue;
+
+/// The format of timestamps in `.ips` reports, such as `2022-03-15 12:34:56.78 +0100`.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f %z";
+
+/// Returns `true` if the given crash report is in the JSON `.ips` format.
+///
+/// The legacy text format never starts with a JSON object.
+pub(super) fn is_ips_crash_report(data: &[u8]) -> bool {
+    data.iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| *b == b'{')
+}
+
+/// Parses an `.ips` crash report consisting of a header line and the report body.
+pub(super) fn parse_ips_crash_report(data: &[u8]) -> Result<IpsCrashReport, serde_json::Error> {
+    let mut headers = serde_json::Deserializer::from_slice(data).into_iter::<IpsHeader>();
+
+    let header = match headers.next() {
+        Some(header) => header?,
+        None => return Err(serde::de::Error::custom("missing crash report header")),
+    };
+
+    // Older reports and reports of other types consist only of the header.
+    let body = &data[headers.byte_offset()..];
+    let mut bodies = serde_json::Deserializer::from_slice(body).into_iter::<IpsCrashReport>();
+    let mut report = match bodies.next() {
+        Some(report) => report?,
+        None => return Err(serde::de::Error::custom("missing crash report body")),
+    };
+    report.header = header;
+
+    Ok(report)
+}
+
+/// The header line of an `.ips` report.
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct IpsHeader {
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub os_version: Option<String>,
+}
+
+/// The body of an `.ips` crash report.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct IpsCrashReport {
+    #[serde(skip)]
+    pub header: IpsHeader,
+    #[serde(default)]
+    pub capture_time: Option<String>,
+    #[serde(default)]
+    pub cpu_type: Option<String>,
+    #[serde(default)]
+    pub model_code: Option<String>,
+    #[serde(default)]
+    pub os_version: Option<IpsOsVersion>,
+    #[serde(default)]
+    pub exception: Option<IpsException>,
+    /// Application specific information, keyed by the image that provided it.
+    #[serde(default)]
+    pub asi: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub threads: Vec<IpsThread>,
+    #[serde(default)]
+    pub used_images: Vec<IpsImage>,
+}
+
+impl IpsCrashReport {
+    /// Returns the time of the crash.
+    ///
+    /// This falls back to the time the report was written if the capture time is missing.
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        let timestamp = self
+            .capture_time
+            .as_deref()
+            .or(self.header.timestamp.as_deref())?;
+        DateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+
+    /// Returns the CPU architecture of the crashed process.
+    pub fn arch(&self) -> Arch {
+        match self.cpu_type.as_deref() {
+            Some("X86-64") => Arch::Amd64,
+            Some("X86") => Arch::X86,
+            Some("ARM-64") => Arch::Arm64,
+            Some("ARM") => Arch::Arm,
+            _ => Arch::Unknown,
+        }
+    }
+
+    /// Returns the application specific information of all images.
+    pub fn application_specific_information(&self) -> Option<String> {
+        let lines: Vec<_> = self.asi.values().flatten().map(String::as_str).collect();
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+}
+
+/// The version of the operating system, such as `macOS 12.3` with build `21E230`.
+#[derive(Debug, Deserialize)]
+pub(super) struct IpsOsVersion {
+    #[serde(default)]
+    pub train: Option<String>,
+    #[serde(default)]
+    pub build: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct IpsException {
+    #[serde(default, rename = "type")]
+    pub ty: Option<String>,
+    #[serde(default)]
+    pub signal: Option<String>,
+    #[serde(default)]
+    pub subtype: Option<String>,
+    #[serde(default)]
+    pub codes: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct IpsThread {
+    #[serde(default)]
+    pub id: Option<u64>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub queue: Option<String>,
+    #[serde(default)]
+    pub triggered: bool,
+    #[serde(default)]
+    pub thread_state: Option<IpsThreadState>,
+    #[serde(default)]
+    pub frames: Vec<IpsFrame>,
+}
+
+/// The register state of a thread.
+///
+/// On ARM, the general purpose registers are listed in `x`, all other registers are stored by
+/// their name.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct IpsThreadState {
+    #[serde(default)]
+    x: Vec<IpsRegister>,
+    #[serde(flatten)]
+    named: BTreeMap<String, serde_json::Value>,
+}
+
+impl IpsThreadState {
+    /// Returns the values of all registers by their name.
+    pub fn registers(&self) -> Registers {
+        let general_purpose = self
+            .x
+            .iter()
+            .enumerate()
+            .map(|(index, register)| (format!("x{}", index), HexValue(register.value)));
+
+        let named = self.named.iter().filter_map(|(name, value)| {
+            // Skip entries that are not registers, such as the `flavor` of the thread state.
+            let register = IpsRegister::deserialize(value).ok()?;
+            Some((name.clone(), HexValue(register.value)))
+        });
+
+        general_purpose.chain(named).collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IpsRegister {
+    value: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct IpsFrame {
+    pub image_index: usize,
+    pub image_offset: u64,
+}
+
+/// An image loaded into the crashed process.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct IpsImage {
+    #[serde(default)]
+    pub uuid: Option<Uuid>,
+    #[serde(default)]
+    pub base: u64,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"{"app_name":"crash","timestamp":"2022-03-15 12:34:57.00 +0100","bug_type":"309","os_version":"macOS 12.3 (21E230)"}
+{
+  "captureTime" : "2022-03-15 12:34:56.1234 +0100",
+  "cpuType" : "ARM-64",
+  "modelCode" : "MacBookPro18,3",
+  "osVersion" : {"train" : "macOS 12.3", "build" : "21E230", "releaseType" : "User"},
+  "exception" : {"codes" : "0x0000000000000001, 0x0000000000000000", "type" : "EXC_BAD_ACCESS", "signal" : "SIGSEGV", "subtype" : "KERN_INVALID_ADDRESS at 0x0000000000000000"},
+  "asi" : {"libsystem_c.dylib" : ["abort() called"]},
+  "faultingThread" : 0,
+  "threads" : [
+    {
+      "triggered" : true,
+      "id" : 1234,
+      "queue" : "com.apple.main-thread",
+      "threadState" : {
+        "x" : [{"value" : 1}, {"value" : 2}],
+        "fp" : {"value" : 6171915632},
+        "pc" : {"value" : 4295000084, "matchesCrashFrame" : 1},
+        "far" : {"value" : 0},
+        "flavor" : "ARM_THREAD_STATE64"
+      },
+      "frames" : [{"imageOffset" : 16404, "symbol" : "main", "symbolLocation" : 20, "imageIndex" : 0}]
+    },
+    {"id" : 1235, "name" : "worker", "frames" : []}
+  ],
+  "usedImages" : [
+    {"source" : "P", "arch" : "arm64", "base" : 4294983680, "size" : 16384, "uuid" : "4a9bd7f6-9fa6-3bb4-a2b0-2ac5b1b1f8d7", "path" : "/usr/local/bin/crash", "name" : "crash"},
+    {"size" : 0, "source" : "A", "base" : 0, "uuid" : "00000000-0000-0000-0000-000000000000"}
+  ]
+}"#;
+
+    #[test]
+    fn test_is_ips_crash_report() {
+        assert!(is_ips_crash_report(REPORT.as_bytes()));
+        assert!(is_ips_crash_report(b"\n  {}"));
+        assert!(!is_ips_crash_report(b"Process: crash [1234]\n"));
+        assert!(!is_ips_crash_report(b""));
+    }
+
+    #[test]
+    fn test_parse_ips_crash_report() {
+        let report = parse_ips_crash_report(REPORT.as_bytes()).unwrap();
+
+        assert_eq!(
+            report.header.os_version.as_deref(),
+            Some("macOS 12.3 (21E230)")
+        );
+        assert_eq!(report.arch(), Arch::Arm64);
+        assert_eq!(
+            report.timestamp().unwrap().to_rfc3339(),
+            "2022-03-15T11:34:56.123400+00:00"
+        );
+        assert_eq!(
+            report.application_specific_information().as_deref(),
+            Some("abort() called")
+        );
+
+        assert_eq!(report.threads.len(), 2);
+        let thread = &report.threads[0];
+        assert!(thread.triggered);
+        assert_eq!(thread.frames[0].image_index, 0);
+        assert_eq!(thread.frames[0].image_offset, 16404);
+
+        let registers = thread.thread_state.as_ref().unwrap().registers();
+        let names: Vec<_> = registers.keys().map(String::as_str).collect();
+        assert_eq!(names, ["far", "fp", "pc", "x0", "x1"]);
+        assert_eq!(registers["pc"], HexValue(4295000084));
+
+        assert_eq!(report.used_images.len(), 2);
+        assert_eq!(
+            report.used_images[0].path.as_deref(),
+            Some("/usr/local/bin/crash")
+        );
+        assert_eq!(report.used_images[1].uuid, Some(Uuid::nil()));
+    }
+
+    #[test]
+    fn test_parse_header_only() {
+        let header = r#"{"app_name":"crash","bug_type":"309"}"#;
+        assert!(parse_ips_crash_report(header.as_bytes()).is_err());
+    }
+}
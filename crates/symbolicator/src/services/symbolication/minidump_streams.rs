@@ -0,0 +1,549 @@
+//! Extraction of additional minidump streams for the symbolication response.
+//!
+//! This reads streams which are not needed for stackwalking, but which carry valuable
+//! information about the crashed process.  The result is returned as [`MinidumpStreams`] if
+//! the request opted in via [`RequestOptions::minidump_streams`].
+//!
+//! [`RequestOptions::minidump_streams`]: crate::types::RequestOptions::minidump_streams
+
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryInto;
+use std::fmt::Write;
+
+use minidump::format::MINIDUMP_STREAM_TYPE;
+use minidump::{
+    MinidumpAnnotation, MinidumpCrashpadInfo, MinidumpException, MinidumpMemory64List,
+    MinidumpMemoryInfoList, MinidumpMemoryList, MinidumpModuleList, Module,
+};
+use minidump_processor::ProcessState;
+use num_traits::FromPrimitive;
+
+use crate::types::{
+    CrashpadInfo, CrashpadModuleInfo, ExceptionRecord, HandleDescriptor, LinuxProcessInfo,
+    MemoryExcerpt, MemoryRegionInfo, MinidumpStreams, ModuleAnnotations,
+};
+use crate::utils::hex::HexValue;
+
+use super::process_minidump::Minidump;
+
+/// The maximum size of a stream returned in [`MinidumpStreams::extension_streams`].
+const MAX_EXTENSION_STREAM_BYTES: usize = 64 * 1024;
+
+/// The minimum size of a `MINIDUMP_HANDLE_DESCRIPTOR`.
+///
+/// Version 2 of the descriptor appends more fields, which we do not read.
+const HANDLE_DESCRIPTOR_SIZE: usize = 32;
+
+/// The maximum total number of bytes returned in all memory excerpts of a minidump.
+const MAX_MEMORY_EXCERPT_BYTES: usize = 16 * 1024;
+
+/// The number of bytes captured before and after the crash address.
+const CRASH_ADDRESS_WINDOW: (u64, u64) = (64, 64);
+
+/// The number of bytes captured before and after the stack pointer.
+///
+/// Stacks grow downwards, so the interesting part of the stack is above the stack pointer.
+const STACK_POINTER_WINDOW: (u64, u64) = (64, 1024);
+
+/// The number of bytes captured before and after addresses found in registers.
+const REGISTER_WINDOW: (u64, u64) = (32, 32);
+
+/// Extracts all supported additional streams from the given minidump.
+///
+/// `data` is the raw minidump, which is needed to resolve strings referenced by RVA.  Missing
+/// or malformed streams are skipped and result in empty sections.
+pub(super) fn extract_minidump_streams(
+    minidump: &Minidump,
+    data: &[u8],
+    process_state: &ProcessState,
+) -> MinidumpStreams {
+    let thread_names: BTreeMap<u64, String> = process_state
+        .threads
+        .iter()
+        .filter_map(|thread| Some((thread.thread_id.into(), thread.thread_name.clone()?)))
+        .collect();
+
+    let handles = match minidump.get_raw_stream(MINIDUMP_STREAM_TYPE::HandleDataStream as u32) {
+        Ok(stream) => parse_handle_data(stream, data),
+        Err(_) => Vec::new(),
+    };
+
+    MinidumpStreams {
+        linux_process: linux_process_info(minidump),
+        exception: exception_record(minidump),
+        thread_names,
+        handles,
+        memory_info: memory_info(minidump),
+        crashpad: crashpad_info(minidump, &process_state.modules),
+        extension_streams: extension_streams(minidump),
+    }
+}
+
+/// Reads the Linux `/proc` streams written by Breakpad and Crashpad.
+fn linux_process_info(minidump: &Minidump) -> Option<LinuxProcessInfo> {
+    let raw_stream =
+        |ty: MINIDUMP_STREAM_TYPE| minidump.get_raw_stream(ty as u32).unwrap_or_default();
+
+    let info = LinuxProcessInfo {
+        cmdline: split_nul_separated(raw_stream(MINIDUMP_STREAM_TYPE::LinuxCmdLine)).collect(),
+        environ: parse_environ(raw_stream(MINIDUMP_STREAM_TYPE::LinuxEnviron)),
+        status: parse_proc_status(raw_stream(MINIDUMP_STREAM_TYPE::LinuxProcStatus)),
+    };
+
+    if info == LinuxProcessInfo::default() {
+        None
+    } else {
+        Some(info)
+    }
+}
+
+/// Splits a buffer of `\0`-separated strings, as found in `/proc/<pid>/cmdline`.
+fn split_nul_separated(buf: &[u8]) -> impl Iterator<Item = String> + '_ {
+    buf.split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+}
+
+/// Parses the contents of `/proc/<pid>/environ` into a map.
+fn parse_environ(buf: &[u8]) -> BTreeMap<String, String> {
+    split_nul_separated(buf)
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once('=')?;
+            Some((key.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+/// Parses the `Key: Value` lines of `/proc/<pid>/status` into a map.
+fn parse_proc_status(buf: &[u8]) -> BTreeMap<String, String> {
+    String::from_utf8_lossy(buf)
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim().to_owned(), value.trim().to_owned()))
+        })
+        .collect()
+}
+
+fn exception_record(minidump: &Minidump) -> Option<ExceptionRecord> {
+    let exception = minidump.get_stream::<MinidumpException>().ok()?;
+    let record = &exception.raw.exception_record;
+
+    let num_parameters =
+        (record.number_parameters as usize).min(record.exception_information.len());
+
+    Some(ExceptionRecord {
+        thread_id: exception.raw.thread_id.into(),
+        code: HexValue(record.exception_code.into()),
+        flags: HexValue(record.exception_flags.into()),
+        address: HexValue(record.exception_address),
+        parameters: record.exception_information[..num_parameters]
+            .iter()
+            .map(|param| HexValue(*param))
+            .collect(),
+    })
+}
+
+fn memory_info(minidump: &Minidump) -> Vec<MemoryRegionInfo> {
+    let list = match minidump.get_stream::<MinidumpMemoryInfoList>() {
+        Ok(list) => list,
+        Err(_) => return Vec::new(),
+    };
+
+    list.iter()
+        .filter_map(|info| {
+            // Empty regions do not have a memory range and carry no information.
+            let range = info.memory_range()?;
+            Some(MemoryRegionInfo {
+                base_address: HexValue(range.start),
+                allocation_protection: info.allocation_protection.bits(),
+                region_size: range.end - range.start + 1,
+                state: info.state.bits(),
+                protection: info.protection.bits(),
+                ty: info.ty.bits(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the process and module annotations written by the Crashpad client.
+pub(super) fn crashpad_info(
+    minidump: &Minidump,
+    modules: &MinidumpModuleList,
+) -> Option<CrashpadInfo> {
+    let info = minidump.get_stream::<MinidumpCrashpadInfo>().ok()?;
+
+    let modules = info
+        .module_list
+        .into_iter()
+        .map(|module| {
+            // The module index refers to the order of the original module list stream.
+            let minidump_module = modules.iter().nth(module.module_index);
+            CrashpadModuleInfo {
+                code_file: minidump_module.map(|m| m.code_file().into_owned()),
+                image_addr: minidump_module.map(|m| HexValue(m.base_address())),
+                annotations: ModuleAnnotations {
+                    list_annotations: module.list_annotations,
+                    simple_annotations: module.simple_annotations,
+                    annotation_objects: module
+                        .annotation_objects
+                        .into_iter()
+                        .filter_map(|(key, value)| match value {
+                            MinidumpAnnotation::String(value) => Some((key, value)),
+                            _ => None,
+                        })
+                        .collect(),
+                },
+            }
+        })
+        .collect();
+
+    Some(CrashpadInfo {
+        simple_annotations: info.simple_annotations,
+        modules,
+    })
+}
+
+/// Returns the contents of all streams of unknown type, encoded in base64.
+///
+/// Streams larger than [`MAX_EXTENSION_STREAM_BYTES`] are skipped.
+fn extension_streams(minidump: &Minidump) -> BTreeMap<String, String> {
+    minidump
+        .all_streams()
+        .map(|directory| directory.stream_type)
+        .filter(|stream_type| MINIDUMP_STREAM_TYPE::from_u32(*stream_type).is_none())
+        .filter_map(|stream_type| {
+            let data = minidump.get_raw_stream(stream_type).ok()?;
+            if data.len() > MAX_EXTENSION_STREAM_BYTES {
+                return None;
+            }
+            Some((format!("{:#x}", stream_type), base64::encode(data)))
+        })
+        .collect()
+}
+
+/// Extracts excerpts of the captured memory around the crash.
+///
+/// Excerpts are captured around the crash address, the stack pointer of the crashing thread
+/// and all other registers of the crashing thread, in this order, until the total size
+/// reaches [`MAX_MEMORY_EXCERPT_BYTES`].  Memory is looked up in the memory list first and in
+/// the 64-bit memory list second.  Addresses outside of the captured memory are skipped.
+pub(super) fn extract_memory_excerpts(
+    minidump: &Minidump,
+    process_state: &ProcessState,
+) -> Vec<MemoryExcerpt> {
+    let memory_list = minidump.get_stream::<MinidumpMemoryList>().ok();
+    let memory64_list = minidump.get_stream::<MinidumpMemory64List>().ok();
+    if memory_list.is_none() && memory64_list.is_none() {
+        return Vec::new();
+    }
+
+    let mut targets = Vec::new();
+    if let Some(crash_address) = process_state.crash_address {
+        targets.push(("crash_address", crash_address, CRASH_ADDRESS_WINDOW));
+    }
+
+    let crashing_context = process_state
+        .requesting_thread
+        .and_then(|index| process_state.threads.get(index))
+        .and_then(|thread| thread.frames.first())
+        .map(|frame| &frame.context);
+
+    if let Some(context) = crashing_context {
+        let stack_pointer = context.get_stack_pointer();
+        let instruction_pointer = context.get_instruction_pointer();
+        targets.push(("stack_pointer", stack_pointer, STACK_POINTER_WINDOW));
+
+        for (register, value) in context.valid_registers() {
+            if value != instruction_pointer {
+                targets.push((register, value, REGISTER_WINDOW));
+            }
+        }
+    }
+
+    let mut budget = MAX_MEMORY_EXCERPT_BYTES;
+    let mut seen = HashSet::new();
+    let mut excerpts = Vec::new();
+
+    for (source, target, (before, after)) in targets {
+        if budget == 0 {
+            break;
+        }
+
+        // Multiple registers frequently point to the same address.
+        if !seen.insert(target) {
+            continue;
+        }
+
+        let memory = memory_list
+            .as_ref()
+            .and_then(|list| list.memory_at_address(target))
+            .map(|memory| (memory.base_address, memory.bytes))
+            .or_else(|| {
+                let memory = memory64_list.as_ref()?.memory_at_address(target)?;
+                Some((memory.base_address, memory.bytes))
+            });
+        let (base_address, memory) = match memory {
+            Some(memory) => memory,
+            None => continue,
+        };
+
+        let excerpt = memory_excerpt(base_address, memory, target, before, after, budget);
+        if let Some((start_addr, bytes)) = excerpt {
+            budget -= bytes.len();
+            excerpts.push(MemoryExcerpt {
+                source: source.to_owned(),
+                target_addr: HexValue(target),
+                start_addr: HexValue(start_addr),
+                bytes: to_hex(bytes),
+            });
+        }
+    }
+
+    excerpts
+}
+
+/// Returns the excerpt of a memory region around the `target` address.
+///
+/// The excerpt spans from `before` bytes before the target to `after` bytes after it, clamped
+/// to the bounds of the region, and is truncated to at most `budget` bytes.
+fn memory_excerpt(
+    base_addr: u64,
+    bytes: &[u8],
+    target: u64,
+    before: u64,
+    after: u64,
+    budget: usize,
+) -> Option<(u64, &[u8])> {
+    let offset = target.checked_sub(base_addr)?;
+    if offset >= bytes.len() as u64 {
+        return None;
+    }
+
+    let start = offset.saturating_sub(before) as usize;
+    let end = offset.saturating_add(after).min(bytes.len() as u64) as usize;
+    let end = end.min(start.saturating_add(budget));
+
+    if start >= end {
+        return None;
+    }
+
+    Some((base_addr + start as u64, &bytes[start..end]))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> Option<u64> {
+    let bytes = buf.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Reads a `MINIDUMP_STRING` at the given RVA.
+///
+/// The string is prefixed with its length in bytes and encoded as UTF-16.  An RVA of `0`
+/// signals that there is no string.
+fn read_minidump_string(data: &[u8], rva: u32) -> Option<String> {
+    if rva == 0 {
+        return None;
+    }
+
+    let start = (rva as usize).checked_add(4)?;
+    let len = read_u32(data, rva as usize)? as usize;
+    let bytes = data.get(start..start.checked_add(len)?)?;
+
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Parses a `MINIDUMP_HANDLE_DATA_STREAM`.
+///
+/// Parsing stops at the first descriptor that is out of bounds.
+fn parse_handle_data(stream: &[u8], data: &[u8]) -> Vec<HandleDescriptor> {
+    let (header_size, descriptor_size, count) = match (
+        read_u32(stream, 0),
+        read_u32(stream, 4),
+        read_u32(stream, 8),
+    ) {
+        (Some(header), Some(descriptor), Some(count)) => {
+            (header as usize, descriptor as usize, count as usize)
+        }
+        _ => return Vec::new(),
+    };
+
+    if descriptor_size < HANDLE_DESCRIPTOR_SIZE {
+        return Vec::new();
+    }
+
+    (0..count)
+        .map_while(|index| {
+            let offset = header_size.checked_add(index.checked_mul(descriptor_size)?)?;
+            let descriptor = stream.get(offset..offset.checked_add(descriptor_size)?)?;
+
+            Some(HandleDescriptor {
+                handle: HexValue(read_u64(descriptor, 0)?),
+                type_name: read_minidump_string(data, read_u32(descriptor, 8)?),
+                object_name: read_minidump_string(data, read_u32(descriptor, 12)?),
+                attributes: read_u32(descriptor, 16)?,
+                granted_access: read_u32(descriptor, 20)?,
+                handle_count: read_u32(descriptor, 24)?,
+                pointer_count: read_u32(descriptor, 28)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolic::common::ByteView;
+
+    use super::*;
+
+    fn minidump_string(s: &str) -> Vec<u8> {
+        let units: Vec<u8> = s.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut buf = (units.len() as u32).to_le_bytes().to_vec();
+        buf.extend(units);
+        buf
+    }
+
+    #[test]
+    fn test_parse_linux_streams() {
+        let cmdline = b"/usr/bin/app\0--verbose\0\0";
+        assert_eq!(
+            split_nul_separated(cmdline).collect::<Vec<_>>(),
+            ["/usr/bin/app", "--verbose"]
+        );
+
+        let environ = parse_environ(b"HOME=/root\0EMPTY=\0INVALID\0PATH=/bin:/usr/bin\0");
+        assert_eq!(environ.len(), 3);
+        assert_eq!(environ["HOME"], "/root");
+        assert_eq!(environ["EMPTY"], "");
+        assert_eq!(environ["PATH"], "/bin:/usr/bin");
+
+        let status = parse_proc_status(b"Name:\tapp\nState:\tS (sleeping)\nPid:\t1234\n");
+        assert_eq!(status["Name"], "app");
+        assert_eq!(status["State"], "S (sleeping)");
+        assert_eq!(status["Pid"], "1234");
+    }
+
+    #[test]
+    fn test_memory_excerpt() {
+        let bytes: Vec<u8> = (0..=255).collect();
+
+        // A window in the middle of the region.
+        let (start, excerpt) = memory_excerpt(0x1000, &bytes, 0x1010, 4, 4, 100).unwrap();
+        assert_eq!(start, 0x100c);
+        assert_eq!(excerpt, &bytes[0x0c..0x14]);
+
+        // The window is clamped to the region.
+        let (start, excerpt) = memory_excerpt(0x1000, &bytes, 0x1002, 4, 1024, 1024).unwrap();
+        assert_eq!(start, 0x1000);
+        assert_eq!(excerpt.len(), 256);
+
+        // The window is truncated to the budget.
+        let (start, excerpt) = memory_excerpt(0x1000, &bytes, 0x1010, 4, 4, 2).unwrap();
+        assert_eq!(start, 0x100c);
+        assert_eq!(excerpt, &[0x0c, 0x0d]);
+
+        // Targets outside of the region.
+        assert!(memory_excerpt(0x1000, &bytes, 0xfff, 4, 4, 100).is_none());
+        assert!(memory_excerpt(0x1000, &bytes, 0x1100, 4, 4, 100).is_none());
+        assert!(memory_excerpt(0x1000, &bytes, 0x1010, 4, 4, 0).is_none());
+    }
+
+    #[test]
+    fn test_extension_streams() {
+        // A header and directory with an extension stream and a known stream.
+        let mut data = Vec::new();
+        data.extend(0x504d_444du32.to_le_bytes()); // signature
+        data.extend(0xa793u32.to_le_bytes()); // version
+        data.extend(2u32.to_le_bytes()); // stream_count
+        data.extend(32u32.to_le_bytes()); // stream_directory_rva
+        data.extend([0; 16]); // checksum, time_date_stamp, flags
+        for (stream_type, size, rva) in [(0x5379_0001u32, 5u32, 56u32), (0x4767_0006, 4, 61)] {
+            data.extend(stream_type.to_le_bytes());
+            data.extend(size.to_le_bytes());
+            data.extend(rva.to_le_bytes());
+        }
+        data.extend(b"hello");
+        data.extend(b"app\0");
+
+        let minidump = Minidump::read(ByteView::from_vec(data)).unwrap();
+        let streams = extension_streams(&minidump);
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams["0x53790001"], "aGVsbG8=");
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&[0x00, 0x0f, 0xab, 0xff]), "000fabff");
+        assert_eq!(to_hex(&[]), "");
+    }
+
+    #[test]
+    fn test_parse_handle_data() {
+        // The strings are placed at the start of the "minidump", followed by the stream.
+        let mut data = vec![0u8; 8];
+        let type_rva = data.len() as u32;
+        data.extend(minidump_string("File"));
+        let name_rva = data.len() as u32;
+        data.extend(minidump_string("C:\\foo.txt"));
+
+        let mut stream = Vec::new();
+        stream.extend(16u32.to_le_bytes()); // size_of_header
+        stream.extend(40u32.to_le_bytes()); // size_of_descriptor
+        stream.extend(2u32.to_le_bytes()); // number_of_descriptors
+        stream.extend(0u32.to_le_bytes()); // reserved
+
+        for (handle, name_rva) in [(0x44u64, name_rva), (0x48u64, 0)] {
+            stream.extend(handle.to_le_bytes());
+            stream.extend(type_rva.to_le_bytes());
+            stream.extend(name_rva.to_le_bytes());
+            stream.extend(0x1u32.to_le_bytes()); // attributes
+            stream.extend(0x12019fu32.to_le_bytes()); // granted_access
+            stream.extend(2u32.to_le_bytes()); // handle_count
+            stream.extend(3u32.to_le_bytes()); // pointer_count
+            stream.extend([0u8; 8]); // object_info_rva and reserved0
+        }
+
+        let handles = parse_handle_data(&stream, &data);
+        assert_eq!(handles.len(), 2);
+
+        assert_eq!(handles[0].handle, HexValue(0x44));
+        assert_eq!(handles[0].type_name.as_deref(), Some("File"));
+        assert_eq!(handles[0].object_name.as_deref(), Some("C:\\foo.txt"));
+        assert_eq!(handles[0].attributes, 1);
+        assert_eq!(handles[0].granted_access, 0x12019f);
+        assert_eq!(handles[0].handle_count, 2);
+        assert_eq!(handles[0].pointer_count, 3);
+
+        assert_eq!(handles[1].handle, HexValue(0x48));
+        assert_eq!(handles[1].object_name, None);
+    }
+
+    #[test]
+    fn test_parse_handle_data_truncated() {
+        let mut stream = Vec::new();
+        stream.extend(16u32.to_le_bytes());
+        stream.extend(32u32.to_le_bytes());
+        stream.extend(5u32.to_le_bytes());
+        stream.extend(0u32.to_le_bytes());
+
+        assert!(parse_handle_data(&stream, &[]).is_empty());
+        assert!(parse_handle_data(&stream[..8], &[]).is_empty());
+    }
+}
@@ -12,33 +12,50 @@ use sentry::protocol::SessionStatus;
 use sentry::SentryFutureExt;
 use tempfile::TempPath;
 use thiserror::Error;
+use tracing::Instrument;
 
 use symbolicator_sources::{ObjectId, SourceConfig};
 
+use crate::config::Scheduling;
 use crate::services::cficaches::{CfiCacheActor, CfiCacheError};
+use crate::services::coredump::CoredumpError;
 use crate::services::objects::ObjectsActor;
 use crate::services::ppdb_caches::{PortablePdbCacheActor, PortablePdbCacheError};
 use crate::services::symcaches::{SymCacheActor, SymCacheError};
 use crate::types::{
-    CompletedSymbolicationResponse, ObjectFileStatus, RawObjectInfo, RequestId, RequestOptions,
-    Scope, SymbolicationResponse,
+    CompletedSymbolicationResponse, InFlightRequest, ObjectFileStatus, RawObjectInfo, RequestId,
+    RequestKind, RequestOptions, RequestStage, Scope, SymbolicationResponse,
 };
-use crate::utils::futures::CallOnDrop;
+use crate::utils::futures::{CallOnDrop, CancelOnDrop};
+use crate::utils::request_diagnostics::{self, DiagnosticsCollector};
+
+use self::in_flight::RequestTracker;
+use self::scheduler::Scheduler;
 
 mod apple;
+mod diagnose;
+mod in_flight;
+mod ips;
+mod minidump_streams;
 mod module_lookup;
+mod process_coredump;
 mod process_minidump;
+mod scheduler;
 // we should really rename this here to the `SymbolicatorService`, as it does a lot more
 // than just symbolication ;-)
 #[allow(clippy::module_inception)]
 mod symbolication;
 
+pub use diagnose::DiagnoseModule;
 pub use symbolication::{StacktraceOrigin, SymbolicateStacktraces};
 
 // We want a shared future here because otherwise polling for a response would hold the global lock.
 type ComputationChannel = future::Shared<oneshot::Receiver<(Instant, SymbolicationResponse)>>;
 
-type ComputationMap = Arc<Mutex<BTreeMap<RequestId, ComputationChannel>>>;
+/// The response channels of all requests, along with the scope each request was created in.
+type ComputationMap = Arc<Mutex<BTreeMap<RequestId, (Scope, ComputationChannel)>>>;
+
+type InFlightMap = Arc<Mutex<BTreeMap<RequestId, Arc<RequestTracker>>>>;
 
 #[derive(Clone)]
 pub struct SymbolicationActor {
@@ -49,8 +66,9 @@ pub struct SymbolicationActor {
     diagnostics_cache: crate::cache::Cache,
     cpu_pool: tokio::runtime::Handle,
     requests: ComputationMap,
-    max_concurrent_requests: Option<usize>,
+    in_flight: InFlightMap,
     current_requests: Arc<AtomicUsize>,
+    scheduler: Scheduler,
     symbolication_taskmon: tokio_metrics::TaskMonitor,
 }
 
@@ -64,6 +82,7 @@ impl SymbolicationActor {
         diagnostics_cache: crate::cache::Cache,
         cpu_pool: tokio::runtime::Handle,
         max_concurrent_requests: Option<usize>,
+        scheduling: Scheduling,
     ) -> Self {
         SymbolicationActor {
             objects,
@@ -73,16 +92,17 @@ impl SymbolicationActor {
             diagnostics_cache,
             cpu_pool,
             requests: Arc::new(Mutex::new(BTreeMap::new())),
-            max_concurrent_requests,
+            in_flight: Arc::new(Mutex::new(BTreeMap::new())),
             current_requests: Arc::new(AtomicUsize::new(0)),
+            scheduler: Scheduler::new(scheduling, max_concurrent_requests),
             symbolication_taskmon: tokio_metrics::TaskMonitor::new(),
         }
     }
 
     /// Creates a new request to symbolicate stacktraces.
     ///
-    /// Returns `None` if the `SymbolicationActor` is already processing the
-    /// maximum number of requests, as given by `max_concurrent_requests`.
+    /// Returns an error if the scope of the request already has the maximum number of
+    /// requests waiting to run, as given by its [`ScopeQuota`](crate::config::ScopeQuota).
     pub fn symbolicate_stacktraces(
         &self,
         request: SymbolicateStacktraces,
@@ -94,7 +114,10 @@ impl SymbolicationActor {
             "symbolicate_stacktraces",
             span,
         );
-        self.create_symbolication_request(async move {
+        let kind = RequestKind::Symbolicate;
+        let request_scope = request.scope.clone();
+        let diagnostics = request.options.diagnostics;
+        self.create_symbolication_request(kind, request_scope, diagnostics, async move {
             let transaction = sentry::start_transaction(ctx);
             sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone().into())));
             let res = slf.do_symbolicate(request).await;
@@ -105,8 +128,8 @@ impl SymbolicationActor {
 
     /// Creates a new request to process a minidump.
     ///
-    /// Returns `None` if the `SymbolicationActor` is already processing the
-    /// maximum number of requests, as given by `max_concurrent_requests`.
+    /// Returns an error if the scope of the request already has the maximum number of
+    /// requests waiting to run, as given by its [`ScopeQuota`](crate::config::ScopeQuota).
     pub fn process_minidump(
         &self,
         scope: Scope,
@@ -121,7 +144,9 @@ impl SymbolicationActor {
             "process_minidump",
             span,
         );
-        self.create_symbolication_request(async move {
+        let kind = RequestKind::Minidump;
+        let diagnostics = options.diagnostics;
+        self.create_symbolication_request(kind, scope.clone(), diagnostics, async move {
             let transaction = sentry::start_transaction(ctx);
             sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone().into())));
             let res = slf
@@ -132,10 +157,41 @@ impl SymbolicationActor {
         })
     }
 
+    /// Creates a new request to process a Linux ELF core file.
+    ///
+    /// Returns an error if the scope of the request already has the maximum number of
+    /// requests waiting to run, as given by its [`ScopeQuota`](crate::config::ScopeQuota).
+    pub fn process_coredump(
+        &self,
+        scope: Scope,
+        coredump_file: TempPath,
+        sources: Arc<[SourceConfig]>,
+        options: RequestOptions,
+    ) -> Result<RequestId, MaxRequestsError> {
+        let slf = self.clone();
+        let span = sentry::configure_scope(|scope| scope.get_span());
+        let ctx = sentry::TransactionContext::continue_from_span(
+            "process_coredump",
+            "process_coredump",
+            span,
+        );
+        let kind = RequestKind::Coredump;
+        let diagnostics = options.diagnostics;
+        self.create_symbolication_request(kind, scope.clone(), diagnostics, async move {
+            let transaction = sentry::start_transaction(ctx);
+            sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone().into())));
+            let res = slf
+                .do_process_coredump(scope, coredump_file, sources, options)
+                .await;
+            transaction.finish();
+            res
+        })
+    }
+
     /// Creates a new request to process an Apple crash report.
     ///
-    /// Returns `None` if the `SymbolicationActor` is already processing the
-    /// maximum number of requests, as given by `max_concurrent_requests`.
+    /// Returns an error if the scope of the request already has the maximum number of
+    /// requests waiting to run, as given by its [`ScopeQuota`](crate::config::ScopeQuota).
     pub fn process_apple_crash_report(
         &self,
         scope: Scope,
@@ -150,7 +206,9 @@ impl SymbolicationActor {
             "process_apple_crash_report",
             span,
         );
-        self.create_symbolication_request(async move {
+        let kind = RequestKind::AppleCrashReport;
+        let diagnostics = options.diagnostics;
+        self.create_symbolication_request(kind, scope.clone(), diagnostics, async move {
             let transaction = sentry::start_transaction(ctx);
             sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone().into())));
             let res = slf
@@ -170,7 +228,11 @@ impl SymbolicationActor {
         request_id: RequestId,
         timeout: Option<u64>,
     ) -> Option<SymbolicationResponse> {
-        let channel_opt = self.requests.lock().get(&request_id).cloned();
+        let channel_opt = self
+            .requests
+            .lock()
+            .get(&request_id)
+            .map(|(_, channel)| channel.clone());
         match channel_opt {
             Some(channel) => Some(wrap_response_channel(request_id, timeout, channel).await),
             None => {
@@ -183,6 +245,40 @@ impl SymbolicationActor {
         }
     }
 
+    /// Returns the scope of a request, as long as its response can be polled.
+    pub fn request_scope(&self, request_id: RequestId) -> Option<Scope> {
+        let requests = self.requests.lock();
+        requests.get(&request_id).map(|(scope, _)| scope.clone())
+    }
+
+    /// Returns a snapshot of all requests that are currently being processed.
+    ///
+    /// The oldest requests are listed first.
+    pub fn in_flight_requests(&self) -> Vec<InFlightRequest> {
+        let mut requests: Vec<_> = self
+            .in_flight
+            .lock()
+            .iter()
+            .map(|(request_id, tracker)| tracker.snapshot(*request_id))
+            .collect();
+        requests.sort_by_key(|request| std::cmp::Reverse(request.age_ms));
+        requests
+    }
+
+    /// Cancels a request that is currently being processed.
+    ///
+    /// The computation of the request is aborted and its concurrency slot released.  Polling
+    /// the request afterwards returns [`SymbolicationResponse::Cancelled`].
+    ///
+    /// Returns `false` if the request is unknown or has already finished.  If the request
+    /// finishes concurrently, whichever comes first wins: either the cancellation succeeds and
+    /// the request responds with [`SymbolicationResponse::Cancelled`], or this returns `false`
+    /// and the request responds with its result.
+    pub fn cancel_request(&self, request_id: RequestId) -> bool {
+        let tracker = self.in_flight.lock().get(&request_id).cloned();
+        tracker.is_some_and(|tracker| tracker.cancel())
+    }
+
     /// Returns a clone of the task monitor for symbolication requests.
     pub fn symbolication_task_monitor(&self) -> tokio_metrics::TaskMonitor {
         self.symbolication_taskmon.clone()
@@ -190,9 +286,18 @@ impl SymbolicationActor {
 
     /// Creates a new request to compute the given future.
     ///
-    /// Returns `None` if the `SymbolicationActor` is already processing the
-    /// maximum number of requests, as given by `max_concurrent_requests`.
-    fn create_symbolication_request<F>(&self, f: F) -> Result<RequestId, MaxRequestsError>
+    /// If `diagnostics` is set, timings and cache statistics are collected while computing
+    /// the future and attached to the response.
+    ///
+    /// Returns an error if the scope of the request already has the maximum number of
+    /// requests waiting to run, as given by its [`ScopeQuota`](crate::config::ScopeQuota).
+    fn create_symbolication_request<F>(
+        &self,
+        kind: RequestKind,
+        scope: Scope,
+        diagnostics: bool,
+        f: F,
+    ) -> Result<RequestId, MaxRequestsError>
     where
         F: Future<Output = Result<CompletedSymbolicationResponse, SymbolicationError>>
             + Send
@@ -209,16 +314,19 @@ impl SymbolicationActor {
         let num_requests = current_requests.load(Ordering::Relaxed);
         metric!(gauge("requests.in_flight") = num_requests as u64);
 
-        // Reject the request if `requests` already contains `max_concurrent_requests` elements.
-        if let Some(max_concurrent_requests) = self.max_concurrent_requests {
-            if num_requests >= max_concurrent_requests {
+        // Requests which may not run yet wait in the queue of their scope, unless it is full.
+        let permit = match self.scheduler.acquire(scope.clone(), kind) {
+            Ok(permit) => permit,
+            Err(error) => {
                 metric!(counter("requests.rejected") += 1);
-                return Err(MaxRequestsError);
+                return Err(error);
             }
-        }
+        };
 
         let request_id = RequestId::new(uuid::Uuid::new_v4());
-        requests.lock().insert(request_id, receiver.shared());
+        requests
+            .lock()
+            .insert(request_id, (scope.clone(), receiver.shared()));
         current_requests.fetch_add(1, Ordering::Relaxed);
         let drop_hub = hub.clone();
         let token = CallOnDrop::new(move || {
@@ -228,15 +336,57 @@ impl SymbolicationActor {
             drop_hub.end_session_with_status(SessionStatus::Crashed);
         });
 
+        let (cancel, cancelled) = oneshot::channel();
+        let tracker = RequestTracker::new(kind, scope, cancel);
+        self.in_flight.lock().insert(request_id, tracker.clone());
+        let request_tracker = tracker.clone();
+        let in_flight = Arc::clone(&self.in_flight);
+        let in_flight_token = CallOnDrop::new(move || {
+            in_flight.lock().remove(&request_id);
+        });
+
+        // The computation runs detached from the HTTP request, but is still part of its trace.
+        let span = tracing::info_span!("symbolication_request", request_id = %request_id);
+
+        let collector = diagnostics.then(DiagnosticsCollector::new);
+
         let spawn_time = Instant::now();
+        let computation = {
+            let collector = collector.clone();
+            async move {
+                metric!(timer("symbolication.create_request.first_poll") = spawn_time.elapsed());
+                let _permit = permit.await;
+                metric!(timer("requests.queue_time") = spawn_time.elapsed());
+                tracker.set_stage(RequestStage::Started);
+                let f = request_diagnostics::scope(collector, f.instrument(span));
+                tracker.scope(f).await
+            }
+        };
+
+        // The computation runs in its own task, so that cancelling the request can abort it
+        // while the response stays available to pollers.
+        let computation =
+            CancelOnDrop::new(self.cpu_pool.spawn(tokio_metrics::TaskMonitor::instrument(
+                &self.symbolication_taskmon,
+                computation.bind_hub(hub.clone()),
+            )));
+
         let request_future = async move {
-            metric!(timer("symbolication.create_request.first_poll") = spawn_time.elapsed());
-            let response = match f.await {
-                Ok(response) => {
+            let result = tokio::select! {
+                result = computation => Some(result),
+                Ok(()) = cancelled => None,
+            };
+            // A cancellation that raced with the completion of the computation still wins if it
+            // claimed the request first, since the canceller has been told so.
+            let result = result.filter(|_| request_tracker.finish());
+
+            let response = match result {
+                Some(Ok(Ok(mut response))) => {
                     sentry::end_session_with_status(SessionStatus::Exited);
+                    response.diagnostics = collector.map(|collector| collector.finish());
                     SymbolicationResponse::Completed(Box::new(response))
                 }
-                Err(error) => {
+                Some(Ok(Err(error))) => {
                     // a timeout is an abnormal session exit, all other errors are considered "crashed"
                     let status = match &error {
                         SymbolicationError::Timeout => SessionStatus::Abnormal,
@@ -249,14 +399,29 @@ impl SymbolicationActor {
                     tracing::error!("Symbolication error: {:?}", error);
                     response
                 }
+                Some(Err(join_error)) => {
+                    // The panic itself is captured at the source.
+                    sentry::end_session_with_status(SessionStatus::Crashed);
+                    tracing::error!("Symbolication panicked: {}", join_error);
+                    SymbolicationResponse::InternalError
+                }
+                None => {
+                    // The computation was aborted when `select!` dropped it, or its result is
+                    // discarded since the cancellation won.
+                    sentry::end_session_with_status(SessionStatus::Exited);
+                    metric!(counter("requests.cancelled") += 1);
+                    SymbolicationResponse::Cancelled
+                }
             };
 
-            sender.send((Instant::now(), response)).ok();
-
             // We stop counting the request as an in-flight request at this point, even though
-            // it will stay in the `requests` map for another 90s.
+            // it will stay in the `requests` map for another 90s.  This happens before sending
+            // the response, so that pollers never observe a finished request holding a slot.
+            drop(in_flight_token);
             current_requests.fetch_sub(1, Ordering::Relaxed);
 
+            sender.send((Instant::now(), response)).ok();
+
             // Wait before removing the channel from the computation map to allow clients to
             // poll the status.
             tokio::time::sleep(MAX_POLL_DELAY).await;
@@ -265,8 +430,7 @@ impl SymbolicationActor {
         }
         .bind_hub(hub);
 
-        self.cpu_pool
-            .spawn(self.symbolication_taskmon.instrument(request_future));
+        self.cpu_pool.spawn(request_future);
 
         Ok(request_id)
     }
@@ -281,8 +445,9 @@ impl fmt::Debug for SymbolicationActor {
             .field("diagnostics_cache", &self.diagnostics_cache)
             .field("cpu_pool", &self.cpu_pool)
             .field("requests", &self.requests)
-            .field("max_concurrent_requests", &self.max_concurrent_requests)
+            .field("in_flight", &self.in_flight)
             .field("current_requests", &self.current_requests)
+            .field("scheduler", &self.scheduler)
             .field("symbolication_taskmon", &"<TaskMonitor>")
             .finish()
     }
@@ -302,23 +467,30 @@ pub enum SymbolicationError {
 
     #[error("failed to parse apple crash report")]
     InvalidAppleCrashReport(#[from] apple_crash_report_parser::ParseError),
+
+    #[error("failed to parse apple crash report: {0}")]
+    InvalidIpsCrashReport(#[from] serde_json::Error),
+
+    #[error("failed to parse ELF core file: {0}")]
+    InvalidCoredump(#[from] CoredumpError),
 }
 
 impl SymbolicationError {
     fn to_symbolication_response(&self) -> SymbolicationResponse {
         match self {
             SymbolicationError::Timeout => SymbolicationResponse::Timeout,
-            SymbolicationError::Failed(_) | SymbolicationError::InvalidAppleCrashReport(_) => {
-                SymbolicationResponse::Failed {
-                    message: self.to_string(),
-                }
-            }
+            SymbolicationError::Failed(_)
+            | SymbolicationError::InvalidAppleCrashReport(_)
+            | SymbolicationError::InvalidIpsCrashReport(_)
+            | SymbolicationError::InvalidCoredump(_) => SymbolicationResponse::Failed {
+                message: self.to_string(),
+            },
         }
     }
 }
 
-/// An error returned when symbolicator receives a request while already processing
-/// the maximum number of requests.
+/// An error returned when symbolicator receives a request while already processing the maximum
+/// number of requests, and the queue of the request's scope is full.
 #[derive(Debug, Clone, Error)]
 #[error("maximum number of concurrent requests reached")]
 pub struct MaxRequestsError;
@@ -442,11 +614,13 @@ mod tests {
 
     use symbolicator_sources::ObjectType;
 
-    use crate::config::Config;
+    use crate::config::{Config, ScopeQuota};
     use crate::services::symbolication::module_lookup::ModuleLookup;
     use crate::services::Service;
     use crate::test::{self, fixture};
-    use crate::types::{CompleteObjectInfo, RawFrame, RawStacktrace};
+    use crate::types::{
+        CompleteObjectInfo, DownloadOutcome, RawFrame, RawStacktrace, RequestDiagnostics,
+    };
     use crate::utils::addr::AddrMode;
     use crate::utils::hex::HexValue;
 
@@ -501,6 +675,7 @@ mod tests {
             })],
             options: RequestOptions {
                 dif_candidates: true,
+                ..Default::default()
             },
         }
     }
@@ -576,6 +751,46 @@ mod tests {
         Ok(())
     }
 
+    async fn symbolicate_with_diagnostics(
+        symbolication: &SymbolicationActor,
+        source: &SourceConfig,
+    ) -> RequestDiagnostics {
+        let mut request = get_symbolication_request(vec![source.clone()]);
+        request.options.diagnostics = true;
+        let request_id = symbolication.symbolicate_stacktraces(request).unwrap();
+        match symbolication.get_response(request_id, None).await {
+            Some(SymbolicationResponse::Completed(response)) => response.diagnostics.unwrap(),
+            response => panic!("unexpected response: {:?}", response),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_diagnostics() {
+        let (service, _cache_dir) = setup_service().await;
+        let symbolication = service.symbolication();
+        let (_symsrv, source) = test::symbol_server();
+
+        let first = symbolicate_with_diagnostics(&symbolication, &source).await;
+        assert!(first.stages.contains_key("module_lookup"));
+        assert!(first.stages.contains_key("source_context"));
+        assert_eq!(first.caches["symcaches"].computed, 1);
+        assert!(first
+            .computations
+            .iter()
+            .any(|computation| computation.cache == "objects"));
+        assert!(first
+            .downloads
+            .iter()
+            .any(|download| download.outcome == DownloadOutcome::Completed));
+        assert!(first.downloaded_bytes[source.id()] > 0);
+
+        // The second request is served from the local caches without any downloads.
+        let second = symbolicate_with_diagnostics(&symbolication, &source).await;
+        assert_eq!(second.caches["symcaches"].local_hits, 1);
+        assert!(second.computations.is_empty());
+        assert!(second.downloads.is_empty());
+    }
+
     #[tokio::test]
     async fn test_get_response_multi() {
         // Make sure we can repeatedly poll for the response
@@ -633,6 +848,7 @@ mod tests {
                 Arc::new([source]),
                 RequestOptions {
                     dif_candidates: true,
+                    ..Default::default()
                 },
             )
             .unwrap();
@@ -730,6 +946,7 @@ mod tests {
             scope: Default::default(),
             options: RequestOptions {
                 dif_candidates: true,
+                ..Default::default()
             },
         };
 
@@ -798,6 +1015,104 @@ mod tests {
         assert!(symbolication.symbolicate_stacktraces(request).is_err());
     }
 
+    #[tokio::test]
+    async fn test_queued_requests() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+
+        let config = Config {
+            cache_dir: Some(cache_dir.path().to_owned()),
+            connect_to_reserved_ips: true,
+            max_concurrent_requests: Some(1),
+            scheduling: Scheduling {
+                default_scope: ScopeQuota {
+                    max_queued: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let handle = tokio::runtime::Handle::current();
+        let service = Service::create(config, handle.clone(), handle)
+            .await
+            .unwrap();
+
+        let symbolication = service.symbolication();
+        let symbol_server = test::FailingSymbolServer::new();
+
+        // The first request runs, the second one waits in the queue of its scope, and the third
+        // one is rejected since the queue is full.
+        let request = get_symbolication_request(vec![symbol_server.pending_source.clone()]);
+        assert!(symbolication.symbolicate_stacktraces(request).is_ok());
+
+        let request = get_symbolication_request(vec![symbol_server.pending_source.clone()]);
+        assert!(symbolication.symbolicate_stacktraces(request).is_ok());
+
+        let request = get_symbolication_request(vec![symbol_server.pending_source]);
+        assert!(symbolication.symbolicate_stacktraces(request).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+
+        let config = Config {
+            cache_dir: Some(cache_dir.path().to_owned()),
+            connect_to_reserved_ips: true,
+            max_concurrent_requests: Some(1),
+            ..Default::default()
+        };
+
+        let handle = tokio::runtime::Handle::current();
+        let service = Service::create(config, handle.clone(), handle)
+            .await
+            .unwrap();
+
+        let symbolication = service.symbolication();
+        let symbol_server = test::FailingSymbolServer::new();
+
+        let request = get_symbolication_request(vec![symbol_server.pending_source.clone()]);
+        let request_id = symbolication.symbolicate_stacktraces(request).unwrap();
+
+        assert!(symbolication.cancel_request(request_id));
+        assert!(!symbolication.cancel_request(request_id));
+
+        let response = symbolication.get_response(request_id, None).await;
+        assert!(matches!(response, Some(SymbolicationResponse::Cancelled)));
+        assert!(symbolication.in_flight_requests().is_empty());
+
+        // The cancelled request no longer counts towards the concurrency limit.
+        let request = get_symbolication_request(vec![symbol_server.pending_source]);
+        assert!(symbolication.symbolicate_stacktraces(request).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_finished_request() {
+        let (service, _cache_dir) = setup_service().await;
+        let symbolication = service.symbolication();
+
+        let request = get_symbolication_request(vec![]);
+        let request_id = symbolication.symbolicate_stacktraces(request).unwrap();
+        let response = symbolication.get_response(request_id, None).await;
+        assert!(matches!(
+            response,
+            Some(SymbolicationResponse::Completed(_))
+        ));
+
+        // Once the request has finished, cancelling loses and the result stays available.
+        assert!(!symbolication.cancel_request(request_id));
+        let response = symbolication.get_response(request_id, None).await;
+        assert!(matches!(
+            response,
+            Some(SymbolicationResponse::Completed(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_dotnet_integration() -> anyhow::Result<()> {
         let (service, _cache_dir) = setup_service().await;
@@ -857,6 +1172,7 @@ mod tests {
             scope: Default::default(),
             options: RequestOptions {
                 dif_candidates: true,
+                ..Default::default()
             },
         };
 
@@ -19,7 +19,7 @@ use crate::types::{
 };
 use crate::utils::addr::AddrMode;
 
-use super::object_id_from_object_info;
+use super::{in_flight, object_id_from_object_info};
 
 #[derive(Debug, Error)]
 pub enum CacheFileError {
@@ -178,6 +178,7 @@ impl ModuleLookup {
 
                 Some(
                     async move {
+                        let _waiting = in_flight::waiting_on(&identifier);
                         match object_type {
                             ObjectType::PeDotnet => {
                                 let request = FetchPortablePdbCache {
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tempfile::TempPath;
+
+use symbolicator_sources::SourceConfig;
+
+use crate::services::coredump::coredump_to_minidump;
+use crate::types::{CompletedSymbolicationResponse, RequestOptions, Scope};
+
+use super::{SymbolicationActor, SymbolicationError};
+
+impl SymbolicationActor {
+    /// Converts the ELF core file into a minidump and processes it like an uploaded minidump.
+    pub(super) async fn do_process_coredump(
+        &self,
+        scope: Scope,
+        coredump_file: TempPath,
+        sources: Arc<[SourceConfig]>,
+        options: RequestOptions,
+    ) -> Result<CompletedSymbolicationResponse, SymbolicationError> {
+        // Rewriting the file is blocking, so it does not run on the async executor.
+        let minidump_file = tokio::task::spawn_blocking(move || convert_coredump(coredump_file))
+            .await
+            .map_err(anyhow::Error::from)??;
+
+        self.do_process_minidump(scope, minidump_file, sources, options)
+            .await
+    }
+}
+
+/// Converts the core file into a minidump in place and returns its path.
+#[tracing::instrument(skip_all)]
+fn convert_coredump(coredump_file: TempPath) -> Result<TempPath, SymbolicationError> {
+    let metadata = coredump_file.metadata().map_err(anyhow::Error::from)?;
+    tracing::debug!("Processing coredump ({} bytes)", metadata.len());
+    metric!(time_raw("coredump.upload.size") = metadata.len());
+
+    // Core files do not record the time of the crash.  The core file was written when it was
+    // received, which is the closest approximation available.
+    let timestamp = metadata
+        .modified()
+        .unwrap_or_else(|_| SystemTime::now())
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as u32);
+
+    coredump_to_minidump(&coredump_file, timestamp)?;
+    Ok(coredump_file)
+}
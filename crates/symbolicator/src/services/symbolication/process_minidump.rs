@@ -30,16 +30,19 @@ use crate::services::cficaches::{CfiCacheActor, CfiCacheError, FetchCfiCache};
 use crate::services::minidump::parse_stacktraces_from_minidump;
 use crate::services::objects::ObjectError;
 use crate::types::{
-    AllObjectCandidates, CompleteObjectInfo, CompletedSymbolicationResponse, ObjectFeatures,
-    ObjectFileStatus, RawFrame, RawObjectInfo, RawStacktrace, Registers, RequestOptions, Scope,
-    SystemInfo,
+    AllObjectCandidates, CompleteObjectInfo, CompletedSymbolicationResponse, MemoryExcerpt,
+    MinidumpStreams, ObjectFeatures, ObjectFileStatus, RawFrame, RawObjectInfo, RawStacktrace,
+    Registers, RequestOptions, RequestStage, Scope, SystemInfo,
 };
 use crate::utils::futures::{m, measure};
 use crate::utils::hex::HexValue;
+use crate::utils::request_diagnostics;
 
+use super::in_flight;
+use super::minidump_streams::{crashpad_info, extract_memory_excerpts, extract_minidump_streams};
 use super::{StacktraceOrigin, SymbolicateStacktraces, SymbolicationActor, SymbolicationError};
 
-type Minidump = minidump::Minidump<'static, ByteView<'static>>;
+pub(super) type Minidump = minidump::Minidump<'static, ByteView<'static>>;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct StackWalkMinidumpResult {
@@ -62,6 +65,9 @@ pub(super) struct MinidumpState {
     crashed: bool,
     crash_reason: String,
     assertion: String,
+    streams: Option<MinidumpStreams>,
+    memory_excerpts: Vec<MemoryExcerpt>,
+    annotations: BTreeMap<String, String>,
 }
 
 impl MinidumpState {
@@ -105,6 +111,9 @@ impl MinidumpState {
                 })
                 .unwrap_or_default(),
             assertion: process_state.assertion.clone().unwrap_or_default(),
+            streams: None,
+            memory_excerpts: Vec::new(),
+            annotations: BTreeMap::new(),
         }
     }
 
@@ -126,6 +135,9 @@ impl MinidumpState {
         response.crashed = Some(self.crashed);
         response.crash_reason = Some(self.crash_reason);
         response.assertion = Some(self.assertion);
+        response.minidump_streams = self.streams;
+        response.memory_excerpts = self.memory_excerpts;
+        response.annotations = self.annotations;
     }
 }
 
@@ -273,6 +285,7 @@ impl SymbolicatorSymbolProvider {
                         .map(|debug_file| debug_file.into_owned()),
                     object_type: self.object_type,
                 };
+                let _waiting = in_flight::waiting_on(&identifier);
 
                 let cache_result = self
                     .cficache_actor
@@ -411,10 +424,12 @@ async fn stackwalk(
     minidump_path: PathBuf,
     scope: Scope,
     sources: Arc<[SourceConfig]>,
+    options: &RequestOptions,
 ) -> anyhow::Result<StackWalkMinidumpResult> {
     // Stackwalk the minidump.
     let duration = Instant::now();
-    let minidump = Minidump::read(ByteView::open(minidump_path)?)?;
+    let minidump_data = ByteView::open(minidump_path)?;
+    let minidump = Minidump::read(minidump_data.clone())?;
     let system_info = minidump
         .get_stream::<MinidumpSystemInfo>()
         .map_err(|_| minidump_processor::ProcessError::MissingSystemInfo)?;
@@ -428,7 +443,29 @@ async fn stackwalk(
     let process_state = minidump_processor::process_minidump(&minidump, &provider).await?;
     let duration = duration.elapsed();
 
-    let minidump_state = MinidumpState::from_process_state(&process_state);
+    let mut minidump_state = MinidumpState::from_process_state(&process_state);
+
+    let mut module_annotations = HashMap::new();
+    if options.crashpad_annotations {
+        if let Some(info) = crashpad_info(&minidump, &process_state.modules) {
+            // Module annotations are attached to the respective modules below.
+            for module in info.modules {
+                if let Some(HexValue(image_addr)) = module.image_addr {
+                    if !module.annotations.is_empty() {
+                        module_annotations.insert(image_addr, module.annotations);
+                    }
+                }
+            }
+            minidump_state.annotations = info.simple_annotations;
+        }
+    }
+    if options.minidump_streams {
+        let streams = extract_minidump_streams(&minidump, &minidump_data, &process_state);
+        minidump_state.streams = Some(streams);
+    }
+    if options.memory_excerpts {
+        minidump_state.memory_excerpts = extract_memory_excerpts(&minidump, &process_state);
+    }
 
     // Finally iterate through the threads and build the stacktraces to
     // return, marking modules as used when they are referenced by a frame.
@@ -474,13 +511,18 @@ async fn stackwalk(
         .by_addr()
         .filter_map(|module| {
             let key = LookupKey::new(module);
+            let annotations = module_annotations.remove(&module.base_address());
 
-            // Discard modules that weren't used and don't have a debug id.
-            if !cficaches.contains_key(&key) && module.debug_identifier().is_none() {
+            // Discard modules that weren't used and don't have a debug id or annotations.
+            if !cficaches.contains_key(&key)
+                && module.debug_identifier().is_none()
+                && annotations.is_none()
+            {
                 return None;
             }
 
             let mut obj_info = object_info_from_minidump_module(ty, module);
+            obj_info.annotations = annotations.unwrap_or_default();
 
             obj_info.unwind_status = match cficaches.remove(&key) {
                 None => Some(ObjectFileStatus::Unused),
@@ -561,11 +603,13 @@ impl SymbolicationActor {
             tracing::debug!("Processing minidump ({} bytes)", len);
             metric!(time_raw("minidump.upload.size") = len);
 
+            in_flight::set_stage(RequestStage::Stackwalk);
             let future = stackwalk(
                 self.cficaches.clone(),
                 minidump_file.to_path_buf(),
                 scope.clone(),
                 sources.clone(),
+                &options,
             );
 
             let result = match future.await {
@@ -584,6 +628,7 @@ impl SymbolicationActor {
             } = result;
 
             metric!(timer("minidump.stackwalk.duration") = duration);
+            request_diagnostics::record_stage("stackwalk", duration);
 
             match parse_stacktraces_from_minidump(&ByteView::open(&minidump_file)?) {
                 Ok(Some(client_stacktraces)) => merge_clientside_with_processed_stacktraces(
@@ -696,6 +741,7 @@ mod tests {
                 $path,
                 RequestOptions {
                     dif_candidates: true,
+                    ..Default::default()
                 }
             )
         }};
@@ -0,0 +1,528 @@
+//! Scheduling of accepted requests across scopes.
+//!
+//! Requests wait in a queue per scope and priority class until they may run, and are rejected
+//! once the queue of their scope is full.  Free slots go to the highest priority class with a
+//! waiting request.  Within a class, scopes take turns in
+//! proportion to their weights using stride scheduling: every scope has a virtual `pass` which
+//! advances inversely to its weight whenever one of its requests starts, and the scope with the
+//! lowest pass goes next.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::channel::oneshot;
+use parking_lot::Mutex;
+
+use crate::config::{Scheduling, ScopeQuota};
+use crate::types::{RequestKind, Scope};
+
+use super::MaxRequestsError;
+
+/// The amount the pass of a scope with weight `1` advances by when one of its requests starts.
+const STRIDE: u64 = 1 << 20;
+
+#[derive(Debug, Default)]
+struct ScopeState {
+    /// The waiting requests by priority class, highest first.
+    queued: BTreeMap<Reverse<u32>, VecDeque<oneshot::Sender<Permit>>>,
+    running: usize,
+    pass: u64,
+}
+
+impl ScopeState {
+    fn queue_depth(&self) -> usize {
+        self.queued.values().map(VecDeque::len).sum()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.running == 0 && self.queued.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    running: usize,
+    /// The pass of the scope that started a request most recently.
+    ///
+    /// Scopes which start queueing are moved up to this pass, so that they cannot build up
+    /// credit while they are idle.
+    virtual_time: u64,
+    /// The state of all scopes with running or waiting requests.
+    scopes: BTreeMap<Scope, ScopeState>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    config: Scheduling,
+    /// The maximum number of requests running at the same time across all scopes.
+    max_running: Option<usize>,
+    state: Mutex<State>,
+}
+
+/// Decides which of the accepted requests may run.
+#[derive(Clone, Debug)]
+pub(super) struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+impl Scheduler {
+    pub fn new(config: Scheduling, max_running: Option<usize>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                max_running,
+                state: Mutex::new(State::default()),
+            }),
+        }
+    }
+
+    /// Queues a request and returns a future that resolves once the request may run.
+    ///
+    /// The request is queued immediately, not when the future is first polled.  It keeps its
+    /// slot until the returned [`Permit`] is dropped.
+    ///
+    /// Returns an error if the request cannot run right away and the queue of its scope is full.
+    pub fn acquire(
+        &self,
+        scope: Scope,
+        kind: RequestKind,
+    ) -> Result<impl Future<Output = Permit>, MaxRequestsError> {
+        let (sender, receiver) = oneshot::channel();
+        let priority = Reverse(
+            self.inner
+                .config
+                .priorities
+                .get(&kind)
+                .copied()
+                .unwrap_or_default(),
+        );
+
+        {
+            let mut state = self.inner.state.lock();
+            let virtual_time = state.virtual_time;
+            let scope_state = state.scopes.entry(scope.clone()).or_default();
+            if scope_state.queued.is_empty() {
+                scope_state.pass = scope_state.pass.max(virtual_time);
+            }
+            scope_state
+                .queued
+                .entry(priority)
+                .or_default()
+                .push_back(sender);
+
+            self.dispatch(&mut state);
+
+            // Dispatching takes requests from the front, so if the queue of this priority is not
+            // empty, this request is still waiting at its back.
+            if let Some(max_queued) = self.quota(&scope).max_queued {
+                if let Some(scope_state) = state.scopes.get_mut(&scope) {
+                    if scope_state.queue_depth() > max_queued {
+                        if let Some(queue) = scope_state.queued.get_mut(&priority) {
+                            queue.pop_back();
+                            if queue.is_empty() {
+                                scope_state.queued.remove(&priority);
+                            }
+                        }
+                        if scope_state.is_idle() {
+                            state.scopes.remove(&scope);
+                        }
+                        return Err(MaxRequestsError);
+                    }
+                }
+            }
+
+            self.report_queue_depth(&state, &scope);
+        }
+
+        // The sender is only dropped without a permit if the scheduler itself is dropped.
+        Ok(async move { receiver.await.unwrap_or(Permit { release: None }) })
+    }
+
+    fn quota(&self, scope: &Scope) -> &ScopeQuota {
+        let config = &self.inner.config;
+        config
+            .scopes
+            .get(scope.as_ref())
+            .unwrap_or(&config.default_scope)
+    }
+
+    /// Starts waiting requests until all slots are taken.
+    fn dispatch(&self, state: &mut State) {
+        loop {
+            if let Some(max_running) = self.inner.max_running {
+                if state.running >= max_running {
+                    break;
+                }
+            }
+
+            let next = state
+                .scopes
+                .iter()
+                .filter(|(scope, scope_state)| match self.quota(scope).max_running {
+                    Some(max_running) => scope_state.running < max_running,
+                    None => true,
+                })
+                .filter_map(|(scope, scope_state)| {
+                    let priority = *scope_state.queued.keys().next()?;
+                    Some((priority, scope_state.pass, scope))
+                })
+                .min();
+
+            let (priority, scope) = match next {
+                Some((priority, _, scope)) => (priority, scope.clone()),
+                None => break,
+            };
+
+            let weight = u64::from(self.quota(&scope).weight.max(1));
+            let scope_state = match state.scopes.get_mut(&scope) {
+                Some(scope_state) => scope_state,
+                None => break,
+            };
+
+            let queue = scope_state.queued.entry(priority).or_default();
+            let sender = queue.pop_front();
+            if queue.is_empty() {
+                scope_state.queued.remove(&priority);
+            }
+
+            let sender = match sender {
+                Some(sender) if !sender.is_canceled() => sender,
+                // The request was cancelled while it was waiting.
+                _ => {
+                    self.report_queue_depth(state, &scope);
+                    continue;
+                }
+            };
+
+            let pass = scope_state.pass;
+            scope_state.pass += STRIDE / weight;
+            scope_state.running += 1;
+            state.running += 1;
+            state.virtual_time = pass;
+
+            let permit = Permit {
+                release: Some((self.clone(), scope.clone())),
+            };
+            if let Err(mut permit) = sender.send(permit) {
+                // The request was cancelled just now.  Releasing the permit regularly would
+                // deadlock on the state we are holding.
+                permit.release = None;
+                state.running -= 1;
+                if let Some(scope_state) = state.scopes.get_mut(&scope) {
+                    scope_state.running -= 1;
+                }
+            }
+
+            self.report_queue_depth(state, &scope);
+        }
+
+        state.scopes.retain(|_, scope_state| !scope_state.is_idle());
+    }
+
+    /// Reports the number of waiting requests of the given scope.
+    ///
+    /// Only scopes with their own quota are reported individually, so that the number of
+    /// distinct tags stays bounded.  All other scopes are reported together as `other`.
+    fn report_queue_depth(&self, state: &State, scope: &Scope) {
+        let scopes = &self.inner.config.scopes;
+        let (tag, depth) = if scopes.contains_key(scope.as_ref()) {
+            let depth = state.scopes.get(scope).map_or(0, ScopeState::queue_depth);
+            (scope.as_ref(), depth)
+        } else {
+            let depth = state
+                .scopes
+                .iter()
+                .filter(|(scope, _)| !scopes.contains_key(scope.as_ref()))
+                .map(|(_, scope_state)| scope_state.queue_depth())
+                .sum();
+            ("other", depth)
+        };
+
+        metric!(gauge("requests.queued") = depth as u64, "scope" => tag);
+    }
+
+    fn release(&self, scope: &Scope) {
+        let mut state = self.inner.state.lock();
+        state.running -= 1;
+        if let Some(scope_state) = state.scopes.get_mut(scope) {
+            scope_state.running -= 1;
+        }
+        self.dispatch(&mut state);
+    }
+}
+
+/// Allows a request to run until it is dropped.
+#[derive(Debug)]
+pub(super) struct Permit {
+    release: Option<(Scheduler, Scope)>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some((scheduler, scope)) = self.release.take() {
+            scheduler.release(&scope);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+
+    fn scope(name: &str) -> Scope {
+        Scope::Scoped(name.to_owned())
+    }
+
+    /// A quota which lets requests wait without limit.
+    fn queueing() -> ScopeQuota {
+        ScopeQuota {
+            max_queued: None,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the permit if the request may run already.
+    fn try_start(future: impl Future<Output = Permit>) -> Option<Permit> {
+        future.now_or_never()
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let scheduler = Scheduler::new(Scheduling::default(), None);
+
+        let permits: Vec<_> = (0..10)
+            .map(|_| {
+                try_start(
+                    scheduler
+                        .acquire(scope("a"), RequestKind::Minidump)
+                        .unwrap(),
+                )
+            })
+            .collect();
+        assert!(permits.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn test_scope_cap() {
+        let config = Scheduling {
+            scopes: [(
+                "a".to_owned(),
+                ScopeQuota {
+                    max_running: Some(1),
+                    ..queueing()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(config, None);
+
+        let first = try_start(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        assert!(first.is_some());
+        let mut second = Box::pin(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        assert!((&mut second).now_or_never().is_none());
+
+        // Other scopes are not affected by the cap.
+        assert!(try_start(
+            scheduler
+                .acquire(scope("b"), RequestKind::Minidump)
+                .unwrap()
+        )
+        .is_some());
+
+        drop(first);
+        assert!(second.now_or_never().is_some());
+    }
+
+    #[test]
+    fn test_weighted_fair_queuing() {
+        let config = Scheduling {
+            scopes: [(
+                "heavy".to_owned(),
+                ScopeQuota {
+                    weight: 2,
+                    ..queueing()
+                },
+            )]
+            .into(),
+            default_scope: queueing(),
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(config, Some(1));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let blocker = try_start(
+            scheduler
+                .acquire(scope("other"), RequestKind::Minidump)
+                .unwrap(),
+        );
+
+        // Queue plenty of requests for both scopes while the only slot is taken.
+        let mut waiting = Vec::new();
+        for name in ["heavy", "light"] {
+            for _ in 0..6 {
+                let sender = sender.clone();
+                let future = scheduler
+                    .acquire(scope(name), RequestKind::Minidump)
+                    .unwrap();
+                waiting.push(Box::pin(future.map(move |permit| {
+                    sender.send(name).unwrap();
+                    permit
+                })));
+            }
+        }
+
+        drop(blocker);
+        let mut order = Vec::new();
+        for _ in 0..6 {
+            // Drive all futures, then release the slot of the one that started.
+            let mut started = None;
+            waiting.retain_mut(|future| match future.as_mut().now_or_never() {
+                Some(permit) => {
+                    started = Some(permit);
+                    false
+                }
+                None => true,
+            });
+            order.push(receiver.try_recv().unwrap());
+            drop(started);
+        }
+
+        let heavy = order.iter().filter(|name| **name == "heavy").count();
+        assert_eq!(heavy, 4, "unexpected order: {:?}", order);
+    }
+
+    #[test]
+    fn test_priorities() {
+        let config = Scheduling {
+            default_scope: queueing(),
+            priorities: [(RequestKind::Symbolicate, 1)].into(),
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(config, Some(1));
+
+        let blocker = try_start(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        let mut minidump = Box::pin(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        let symbolicate = scheduler
+            .acquire(scope("b"), RequestKind::Symbolicate)
+            .unwrap();
+
+        drop(blocker);
+        assert!((&mut minidump).now_or_never().is_none());
+        let permit = symbolicate.now_or_never();
+        assert!(permit.is_some());
+
+        drop(permit);
+        assert!(minidump.now_or_never().is_some());
+    }
+
+    #[test]
+    fn test_cancelled_while_queued() {
+        let config = Scheduling {
+            default_scope: queueing(),
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(config, Some(1));
+
+        let blocker = try_start(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        drop(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        let waiting = scheduler
+            .acquire(scope("b"), RequestKind::Minidump)
+            .unwrap();
+
+        drop(blocker);
+        assert!(waiting.now_or_never().is_some());
+    }
+
+    #[test]
+    fn test_queue_cap() {
+        let config = Scheduling {
+            default_scope: ScopeQuota {
+                max_queued: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(config, Some(1));
+
+        // Requests which may run right away never count towards the queue.
+        let blocker = try_start(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        assert!(blocker.is_some());
+
+        let waiting = scheduler
+            .acquire(scope("a"), RequestKind::Minidump)
+            .unwrap();
+        assert!(scheduler
+            .acquire(scope("a"), RequestKind::Minidump)
+            .is_err());
+
+        // The queue of every scope is bounded on its own.
+        let other = scheduler
+            .acquire(scope("b"), RequestKind::Minidump)
+            .unwrap();
+        assert!(scheduler
+            .acquire(scope("b"), RequestKind::Minidump)
+            .is_err());
+
+        drop(other);
+        drop(blocker);
+        let permit = try_start(waiting);
+        assert!(permit.is_some());
+
+        // Rejected requests do not hold on to a slot.
+        drop(permit);
+        assert!(try_start(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap()
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn test_no_queue_by_default() {
+        let scheduler = Scheduler::new(Scheduling::default(), Some(1));
+
+        let running = try_start(
+            scheduler
+                .acquire(scope("a"), RequestKind::Minidump)
+                .unwrap(),
+        );
+        assert!(running.is_some());
+        assert!(scheduler
+            .acquire(scope("b"), RequestKind::Minidump)
+            .is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use symbolic::common::{split_path, DebugId, InstructionInfo, Language, Name};
 use symbolic::demangle::{Demangle, DemangleOptions};
@@ -9,12 +9,14 @@ use crate::services::ppdb_caches::PortablePdbCacheFile;
 use crate::services::symcaches::SymCacheFile;
 use crate::types::{
     CompleteObjectInfo, CompleteStacktrace, CompletedSymbolicationResponse, FrameStatus,
-    FrameTrust, ObjectFileStatus, RawFrame, RawStacktrace, Registers, RequestOptions, Scope,
-    Signal, SymbolicatedFrame,
+    FrameTrust, ObjectFileStatus, RawFrame, RawStacktrace, Registers, RequestOptions, RequestStage,
+    Scope, Signal, SymbolicatedFrame,
 };
 use crate::utils::futures::{m, measure};
 use crate::utils::hex::HexValue;
+use crate::utils::request_diagnostics;
 
+use super::in_flight;
 use super::module_lookup::{CacheFile, CacheLookupResult, ModuleLookup};
 use super::{SymbolicationActor, SymbolicationError};
 
@@ -56,6 +58,8 @@ impl SymbolicationActor {
             ..
         } = request;
 
+        in_flight::set_stage(RequestStage::ModuleLookup);
+        let start = Instant::now();
         let mut module_lookup = ModuleLookup::new(scope, sources, modules.into_iter());
         module_lookup
             .fetch_caches(
@@ -64,13 +68,19 @@ impl SymbolicationActor {
                 &stacktraces,
             )
             .await;
+        request_diagnostics::record_stage("module_lookup", start.elapsed());
 
+        in_flight::set_stage(RequestStage::Symbolication);
+        let start = Instant::now();
         let mut metrics = StacktraceMetrics::default();
         let mut stacktraces: Vec<_> = stacktraces
             .into_iter()
             .map(|trace| symbolicate_stacktrace(trace, &module_lookup, &mut metrics, signal))
             .collect();
+        request_diagnostics::record_stage("symbolication", start.elapsed());
 
+        in_flight::set_stage(RequestStage::SourceContext);
+        let start = Instant::now();
         module_lookup
             .fetch_sources(self.objects.clone(), &stacktraces)
             .await;
@@ -102,6 +112,7 @@ impl SymbolicationActor {
         }
         // explicitly drop this, so it does not borrow `module_lookup` anymore.
         drop(debug_sessions);
+        request_diagnostics::record_stage("source_context", start.elapsed());
 
         // bring modules back into the original order
         let modules = module_lookup.into_inner();
@@ -0,0 +1,171 @@
+//! Export of tracing spans to OpenTelemetry.
+//!
+//! Spans are exported via OTLP if an endpoint is configured.  Incoming requests carrying a W3C
+//! `traceparent` header are attached to the caller's trace, so that symbolication shows up
+//! inside the trace of the service that sent the request.
+
+use std::task::{Context, Poll};
+
+use anyhow::{Context as _, Result};
+use axum::http::{HeaderMap, Request};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::{trace as sdktrace, Resource};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use tower_layer::Layer;
+use tower_service::Service as TowerService;
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::OpenTelemetry;
+
+/// Keeps the span exporter running.
+///
+/// Dropping this flushes all pending spans and shuts down the exporter.
+#[derive(Debug)]
+pub struct OtlpGuard {
+    /// The runtime the batch exporter runs on.
+    ///
+    /// Logging is initialized before the server runtimes are created, so the exporter brings
+    /// its own.
+    _runtime: tokio::runtime::Runtime,
+}
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        global::shutdown_tracer_provider();
+    }
+}
+
+/// Creates a tracer that exports spans to the configured OTLP endpoint.
+///
+/// Returns `None` if no endpoint is configured.
+pub fn init_tracer(config: &OpenTelemetry) -> Result<Option<(sdktrace::Tracer, OtlpGuard)>> {
+    let endpoint = match config.otlp_endpoint {
+        Some(ref endpoint) => endpoint,
+        None => return Ok(None),
+    };
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("symbolicator-otlp")
+        .worker_threads(1)
+        .enable_all()
+        .build()?;
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let sampler = sdktrace::Sampler::TraceIdRatioBased(config.sample_rate);
+    let trace_config = sdktrace::config()
+        .with_sampler(sdktrace::Sampler::ParentBased(Box::new(sampler)))
+        .with_resource(Resource::new(vec![KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )]));
+
+    let tracer = {
+        // The batch exporter spawns its worker on the current runtime.
+        let _enter = runtime.enter();
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace_config)
+            .install_batch(opentelemetry::runtime::Tokio)
+            .context("failed to create OTLP exporter")?
+    };
+
+    Ok(Some((tracer, OtlpGuard { _runtime: runtime })))
+}
+
+/// Reads propagation headers from an HTTP request.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Wraps every request in a span whose parent is taken from the request's trace context.
+#[derive(Clone)]
+pub struct TraceContextLayer;
+
+#[derive(Clone)]
+pub struct TraceContextService<S> {
+    service: S,
+}
+
+impl<S> Layer<S> for TraceContextLayer {
+    type Service = TraceContextService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        Self::Service { service }
+    }
+}
+
+impl<S, B> TowerService<Request<B>> for TraceContextService<S>
+where
+    S: TowerService<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method(),
+            path = %request.uri().path(),
+        );
+        span.set_parent(parent);
+
+        let future = span.in_scope(|| self.service.call(request));
+        future.instrument(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::trace::TraceContextExt;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_traceparent() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+
+        let context = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+        let span_context = context.span().span_context().clone();
+
+        assert!(span_context.is_remote());
+        assert!(span_context.is_sampled());
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+        assert_eq!(span_context.span_id().to_string(), "b7ad6b7169203331");
+    }
+}
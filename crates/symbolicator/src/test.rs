@@ -31,12 +31,15 @@ use warp::Filter;
 
 use symbolicator_sources::{
     CommonSourceConfig, DirectoryLayoutType, FileType, FilesystemSourceConfig, GcsSourceKey,
-    HttpSourceConfig, SourceConfig, SourceFilters, SourceId,
+    HttpSourceConfig, ObjectType, SourceConfig, SourceFilters, SourceId,
 };
 
 use crate::config::Config;
 use crate::endpoints;
+use crate::services::symbolication::{StacktraceOrigin, SymbolicateStacktraces};
 use crate::services::Service;
+use crate::types::{RawFrame, RawObjectInfo, RawStacktrace, RequestOptions, Scope};
+use crate::utils::hex::HexValue;
 
 pub use tempfile::TempDir;
 
@@ -341,6 +344,40 @@ impl FailingSymbolServer {
     }
 }
 
+/// Creates a request to symbolicate a single frame of a macOS module from the given sources.
+///
+/// With the `pending_source` of a [`FailingSymbolServer`], the request never finishes.
+pub(crate) fn symbolication_request(
+    scope: Scope,
+    sources: Vec<SourceConfig>,
+) -> SymbolicateStacktraces {
+    SymbolicateStacktraces {
+        scope,
+        signal: None,
+        sources: Arc::from(sources),
+        origin: StacktraceOrigin::Symbolicate,
+        stacktraces: vec![RawStacktrace {
+            frames: vec![RawFrame {
+                instruction_addr: HexValue(0x1_0000_0fa0),
+                ..RawFrame::default()
+            }],
+            ..RawStacktrace::default()
+        }],
+        modules: vec![RawObjectInfo {
+            ty: ObjectType::Macho,
+            code_id: Some("502fc0a51ec13e479998684fa139dca7".into()),
+            debug_id: Some("502fc0a5-1ec1-3e47-9998-684fa139dca7".into()),
+            image_addr: HexValue(0x1_0000_0000),
+            image_size: Some(4096),
+            code_file: None,
+            debug_file: None,
+            checksum: None,
+        }
+        .into()],
+        options: RequestOptions::default(),
+    }
+}
+
 /// Returns the legacy read-only GCS credentials for testing GCS support.
 ///
 /// Use the `gcs_source_key!()` macro instead which will skip correctly.
@@ -0,0 +1,160 @@
+//! Types describing where the time of a symbolication request goes.
+//!
+//! The [`RequestDiagnostics`] section is only collected and returned if requested via
+//! [`RequestOptions::diagnostics`](super::RequestOptions::diagnostics).  The
+//! [`InFlightRequest`] list is returned by the `/admin/requests` endpoint.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use symbolicator_sources::SourceId;
+
+use super::{RequestId, Scope};
+
+/// Timings and cache statistics of a single symbolication request.
+///
+/// Cache items are computed once and shared between concurrent requests.  A computation is
+/// attributed to the request which started it, other requests waiting for the same item count
+/// it as [`joined`](CacheDiagnostics::joined).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestDiagnostics {
+    /// Wall time of the entire request in milliseconds.
+    pub total_ms: u64,
+
+    /// Wall time of each stage of the request in milliseconds.
+    ///
+    /// Stages are `stackwalk`, `module_lookup`, `symbolication` and `source_context`.  Only
+    /// stages that ran for this kind of request are present.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stages: BTreeMap<String, u64>,
+
+    /// Lookups per cache, keyed by the cache name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub caches: BTreeMap<String, CacheDiagnostics>,
+
+    /// All cache items computed for this request, in order of completion.
+    ///
+    /// For the `objects` cache, computing an item means downloading the object file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub computations: Vec<ComputationDiagnostics>,
+
+    /// All downloads made for this request, in order of completion.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub downloads: Vec<DownloadDiagnostics>,
+
+    /// Total bytes downloaded per source.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub downloaded_bytes: BTreeMap<SourceId, u64>,
+}
+
+/// How often a cache was consulted and where its items came from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CacheDiagnostics {
+    /// Items found in the local cache directory.
+    pub local_hits: u32,
+    /// Items fetched from the shared cache.
+    pub shared_hits: u32,
+    /// Items freshly computed.
+    pub computed: u32,
+    /// Items that were already being computed for another request.
+    pub joined: u32,
+}
+
+/// Where a computed cache item came from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComputationOrigin {
+    /// The item was fetched from the shared cache.
+    Shared,
+    /// The item was freshly computed.
+    Fresh,
+}
+
+/// A single cache item computed for a request.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ComputationDiagnostics {
+    /// The name of the cache.
+    pub cache: String,
+    /// The cache key of the item, identifying the module and source.
+    pub key: String,
+    /// Whether the item was fetched from the shared cache or computed.
+    pub origin: ComputationOrigin,
+    /// Wall time of the computation in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// The outcome of a download.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadOutcome {
+    /// The file was downloaded.
+    Completed,
+    /// The file does not exist on the source.
+    NotFound,
+    /// The download failed or timed out.
+    Error,
+}
+
+/// A single download made for a request.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DownloadDiagnostics {
+    /// The source the file was downloaded from.
+    pub source: SourceId,
+    /// The location of the file on the source.
+    pub location: String,
+    /// The outcome of the download.
+    pub outcome: DownloadOutcome,
+    /// The size of the downloaded file in bytes.
+    pub bytes: u64,
+    /// Wall time of the download in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// The kind of a symbolication request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKind {
+    /// Symbolication of stack traces via `/symbolicate`.
+    Symbolicate,
+    /// Processing of a minidump via `/minidump`.
+    Minidump,
+    /// Processing of a Linux core file via `/coredump`.
+    Coredump,
+    /// Processing of an Apple crash report via `/applecrashreport`.
+    AppleCrashReport,
+}
+
+/// The stage a symbolication request is currently in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestStage {
+    /// The request has been accepted but has not started running yet.
+    Queued,
+    /// The request is parsing or converting its input.
+    Started,
+    /// The request is stackwalking a minidump, fetching CFI for its modules.
+    Stackwalk,
+    /// The request is fetching symcaches for the referenced modules.
+    ModuleLookup,
+    /// The request is symbolicating frames.
+    Symbolication,
+    /// The request is fetching sources and applying source context.
+    SourceContext,
+}
+
+/// A symbolication request that is currently being processed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InFlightRequest {
+    /// The identifier of the request, as used to poll for its response.
+    pub request_id: RequestId,
+    /// The kind of the request.
+    pub kind: RequestKind,
+    /// The scope of the request.
+    pub scope: Scope,
+    /// The time since the request was accepted in milliseconds.
+    pub age_ms: u64,
+    /// The stage the request is currently in.
+    pub stage: RequestStage,
+    /// The modules whose debug or unwind information the request is currently waiting for.
+    pub waiting_on: Vec<String>,
+}
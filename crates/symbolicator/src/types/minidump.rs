@@ -0,0 +1,218 @@
+//! Types describing additional minidump streams returned in the symbolication response.
+//!
+//! The [`MinidumpStreams`] section is only extracted and returned if requested via
+//! [`RequestOptions::minidump_streams`](super::RequestOptions::minidump_streams).
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::hex::HexValue;
+
+/// Additional structured information read from the streams of a minidump.
+///
+/// Every section is optional, as minidump writers differ greatly in which streams they
+/// include.  Sections that are missing or empty in the minidump are not serialized.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MinidumpStreams {
+    /// Information about the crashed process read from the Linux `/proc` streams.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linux_process: Option<LinuxProcessInfo>,
+
+    /// The raw exception record of the crash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exception: Option<ExceptionRecord>,
+
+    /// The names of all named threads, keyed by their thread id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub thread_names: BTreeMap<u64, String>,
+
+    /// Descriptors of the OS handles held by the process at the time of the crash.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub handles: Vec<HandleDescriptor>,
+
+    /// The memory regions of the process, as described by the memory info list stream.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory_info: Vec<MemoryRegionInfo>,
+
+    /// Annotations written by the Crashpad client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crashpad: Option<CrashpadInfo>,
+
+    /// The contents of streams that Symbolicator does not interpret, such as custom streams
+    /// written by crash reporting clients.
+    ///
+    /// Keys are the stream types formatted as hex, values are the base64 encoded contents.
+    /// Streams larger than 64KiB are not returned.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extension_streams: BTreeMap<String, String>,
+}
+
+/// Information about a Linux process, read from the streams mirroring `/proc/<pid>`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LinuxProcessInfo {
+    /// The command line arguments, including the executable as first argument.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cmdline: Vec<String>,
+
+    /// The environment variables of the process.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environ: BTreeMap<String, String>,
+
+    /// The entries of `/proc/<pid>/status`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub status: BTreeMap<String, String>,
+}
+
+/// The exception record of a crash, as written into the exception stream.
+///
+/// The meaning of the individual values is OS-specific.  The interpreted crash reason is
+/// available separately in the `crash_reason` field of the response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExceptionRecord {
+    /// The id of the thread that caused the exception.
+    pub thread_id: u64,
+
+    /// The OS-specific exception code, e.g. a signal number on Unix platforms.
+    pub code: HexValue,
+
+    /// The OS-specific exception flags, e.g. the signal code on Unix platforms.
+    pub flags: HexValue,
+
+    /// The address at which the exception occurred.
+    pub address: HexValue,
+
+    /// Additional exception parameters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<HexValue>,
+}
+
+/// A single OS handle held by the crashed process.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HandleDescriptor {
+    /// The value of the handle.
+    pub handle: HexValue,
+
+    /// The name of the object type, such as `File` or `Event`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+
+    /// The name of the object, such as the path of a file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_name: Option<String>,
+
+    /// Object-specific attributes of the handle.
+    pub attributes: u32,
+
+    /// The access rights granted for this handle.
+    pub granted_access: u32,
+
+    /// The number of handles referencing the object.
+    pub handle_count: u32,
+
+    /// The number of pointers referencing the object.
+    pub pointer_count: u32,
+}
+
+/// Information about a region of the crashed process' virtual memory.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MemoryRegionInfo {
+    /// The start address of the region.
+    pub base_address: HexValue,
+
+    /// The memory protection of the allocation when it was initially created.
+    pub allocation_protection: u32,
+
+    /// The size of the region in bytes.
+    pub region_size: u64,
+
+    /// The state of the pages in this region, such as committed or reserved.
+    pub state: u32,
+
+    /// The current memory protection of the pages in this region.
+    pub protection: u32,
+
+    /// The type of the pages in this region, such as image or mapped.
+    #[serde(rename = "type")]
+    pub ty: u32,
+}
+
+/// Annotations written into the minidump by the Crashpad client.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CrashpadInfo {
+    /// Process-wide key/value annotations.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub simple_annotations: BTreeMap<String, String>,
+
+    /// Annotations that were attached to individual modules.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<CrashpadModuleInfo>,
+}
+
+/// Annotations that the Crashpad client attached to one loaded module.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CrashpadModuleInfo {
+    /// The name of the module's code file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_file: Option<String>,
+
+    /// The address at which the module was loaded.
+    ///
+    /// This corresponds to the `image_addr` of the module in the `modules` list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_addr: Option<HexValue>,
+
+    /// The annotations of the module.
+    #[serde(flatten)]
+    pub annotations: ModuleAnnotations,
+}
+
+/// Annotations that a crash reporting client, such as Crashpad, attached to a loaded module.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModuleAnnotations {
+    /// Free-form annotation strings.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub list_annotations: Vec<String>,
+
+    /// Key/value annotations.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub simple_annotations: BTreeMap<String, String>,
+
+    /// Annotation objects with a string value.
+    ///
+    /// Annotation objects of other types are not supported and skipped.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotation_objects: BTreeMap<String, String>,
+}
+
+impl ModuleAnnotations {
+    /// Returns `true` if there are no annotations.
+    pub fn is_empty(&self) -> bool {
+        self.list_annotations.is_empty()
+            && self.simple_annotations.is_empty()
+            && self.annotation_objects.is_empty()
+    }
+}
+
+/// An excerpt of the crashed process' memory around an interesting address.
+///
+/// Excerpts are only returned if requested via
+/// [`RequestOptions::memory_excerpts`](super::RequestOptions::memory_excerpts), and only
+/// contain memory that was captured in the minidump.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MemoryExcerpt {
+    /// What the excerpt was captured for.
+    ///
+    /// This is either `crash_address`, `stack_pointer` or the name of a register of the
+    /// crashing thread that points into captured memory.
+    pub source: String,
+
+    /// The address that the excerpt was captured around.
+    pub target_addr: HexValue,
+
+    /// The address of the first byte in the excerpt.
+    pub start_addr: HexValue,
+
+    /// The memory contents as a lowercase hex string.
+    pub bytes: String,
+}
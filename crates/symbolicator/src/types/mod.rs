@@ -16,9 +16,24 @@ use uuid::Uuid;
 use crate::utils::addr::AddrMode;
 use crate::utils::hex::HexValue;
 
+mod diagnostics;
+mod minidump;
 mod objects;
-
-pub use objects::{AllObjectCandidates, ObjectCandidate, ObjectDownloadInfo, ObjectUseInfo};
+mod sources;
+
+pub use diagnostics::{
+    ComputationDiagnostics, ComputationOrigin, DownloadDiagnostics, DownloadOutcome,
+    InFlightRequest, RequestDiagnostics, RequestKind, RequestStage,
+};
+pub use minidump::{
+    CrashpadInfo, CrashpadModuleInfo, ExceptionRecord, HandleDescriptor, LinuxProcessInfo,
+    MemoryExcerpt, MemoryRegionInfo, MinidumpStreams, ModuleAnnotations,
+};
+pub use objects::{
+    AllObjectCandidates, LocationProbe, ObjectCandidate, ObjectDownloadInfo, ObjectUseInfo,
+    ProbeCacheStatus, SourceProbe,
+};
+pub use sources::{RequestSource, SourceRef, SourceRefError};
 
 /// Symbolication task identifier.
 #[derive(Debug, Clone, Copy, Serialize, Ord, PartialOrd, Eq, PartialEq)]
@@ -119,6 +134,43 @@ pub struct RequestOptions {
     /// [`ObjectCandidate`] struct for which extra information is returned for DIF objects.
     #[serde(default)]
     pub dif_candidates: bool,
+
+    /// Whether to return additional information read from the streams of a minidump.
+    ///
+    /// This includes the process command line and environment, the raw exception record,
+    /// thread names, handle data, the memory info list, Crashpad annotations and the contents
+    /// of streams that are not interpreted by Symbolicator.  See the [`MinidumpStreams`] struct
+    /// for the full set of returned sections.  This has no effect on requests which do not
+    /// process a minidump.
+    #[serde(default)]
+    pub minidump_streams: bool,
+
+    /// Whether to return the annotations that the crash reporting client attached to the process.
+    ///
+    /// Crashpad annotations of the process are returned in the `annotations` field of the
+    /// response, and annotations of modules in the respective entries of the modules list.
+    /// This has no effect on requests which do not process a minidump.
+    #[serde(default)]
+    pub crashpad_annotations: bool,
+
+    /// Whether to return excerpts of memory around the crash.
+    ///
+    /// This returns hex excerpts of the memory captured in a minidump around the crashing
+    /// stack pointer, the crash address and values in registers of the crashing thread which
+    /// point into captured memory.  The total size of all excerpts is capped.  See the
+    /// [`MemoryExcerpt`] struct for the returned information.  This has no effect on
+    /// requests which do not process a minidump.
+    #[serde(default)]
+    pub memory_excerpts: bool,
+
+    /// Whether to return timings and cache statistics of the request.
+    ///
+    /// This returns the wall time of every stage of the request, which caches were hit
+    /// locally or in the shared cache and which items were computed, and all downloads along
+    /// with the bytes downloaded per source.  See the [`RequestDiagnostics`] struct for the
+    /// returned information.
+    #[serde(default)]
+    pub diagnostics: bool,
 }
 
 /// A map of register values.
@@ -493,6 +545,13 @@ pub struct CompleteObjectInfo {
     /// This list is not serialised if it is empty.
     #[serde(skip_serializing_if = "AllObjectCandidates::is_empty", default)]
     pub candidates: AllObjectCandidates,
+
+    /// Annotations that the crash reporting client attached to this module.
+    ///
+    /// These are currently only read from Crashpad minidumps if requested via
+    /// [`RequestOptions::crashpad_annotations`], and not serialised if empty.
+    #[serde(skip_serializing_if = "ModuleAnnotations::is_empty", default)]
+    pub annotations: ModuleAnnotations,
 }
 
 impl CompleteObjectInfo {
@@ -548,6 +607,7 @@ impl From<RawObjectInfo> for CompleteObjectInfo {
             arch: Arch::Unknown,
             raw,
             candidates: AllObjectCandidates::default(),
+            annotations: ModuleAnnotations::default(),
         }
     }
 }
@@ -569,6 +629,8 @@ pub enum SymbolicationResponse {
     },
     Timeout,
     InternalError,
+    /// The request was cancelled via `DELETE /requests/:request_id` before it finished.
+    Cancelled,
 }
 
 /// The symbolicated crash data.
@@ -577,7 +639,7 @@ pub enum SymbolicationResponse {
 /// meta-information about the crash.
 ///
 /// This object is the main type containing the symblicated crash as returned by the
-/// `/minidump`, `/symbolicate` and `/applecrashreport` endpoints.  It is publicly
+/// `/minidump`, `/coredump`, `/symbolicate` and `/applecrashreport` endpoints.  It is publicly
 /// documented at <https://getsentry.github.io/symbolicator/api/response/>.  For the actual
 /// HTTP response this is further wrapped in [`SymbolicationResponse`] which can also return a
 /// pending or failed state etc instead of a result.
@@ -624,6 +686,30 @@ pub struct CompletedSymbolicationResponse {
 
     /// A list of images, extended with status information.
     pub modules: Vec<CompleteObjectInfo>,
+
+    /// Key/value annotations that the crash reporting client attached to the process.
+    ///
+    /// Only present if requested via [`RequestOptions::crashpad_annotations`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+
+    /// Additional information read from the streams of a minidump.
+    ///
+    /// Only present if requested via [`RequestOptions::minidump_streams`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minidump_streams: Option<MinidumpStreams>,
+
+    /// Excerpts of the process memory around the crash.
+    ///
+    /// Only present if requested via [`RequestOptions::memory_excerpts`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory_excerpts: Vec<MemoryExcerpt>,
+
+    /// Timings and cache statistics of the request.
+    ///
+    /// Only present if requested via [`RequestOptions::diagnostics`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<RequestDiagnostics>,
 }
 
 impl CompletedSymbolicationResponse {
@@ -638,6 +724,34 @@ impl CompletedSymbolicationResponse {
     }
 }
 
+/// Diagnostics of the lookup of a single module, as returned by the `/diagnose` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleDiagnostics {
+    /// Status of the debug file lookup, as it would be reported by symbolication.
+    pub debug_status: ObjectFileStatus,
+
+    /// Status of the unwind info lookup, as it would be reported by minidump stackwalking.
+    pub unwind_status: ObjectFileStatus,
+
+    /// Features available during symbolication.
+    pub features: ObjectFeatures,
+
+    /// All DIF object candidates used for symbolication and unwinding.
+    pub candidates: AllObjectCandidates,
+
+    /// Every location at which the module was looked up, grouped by source.
+    pub probes: Vec<SourceProbe>,
+
+    /// The time spent probing all sources, in milliseconds.
+    pub probe_duration_ms: u64,
+
+    /// The time spent fetching the symcache or portable PDB cache, in milliseconds.
+    pub debug_duration_ms: u64,
+
+    /// The time spent fetching the CFI cache, in milliseconds.
+    pub unwind_duration_ms: u64,
+}
+
 /// Information about the operating system.
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct SystemInfo {
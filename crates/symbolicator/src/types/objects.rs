@@ -276,6 +276,52 @@ impl AllObjectCandidates {
     }
 }
 
+/// Whether a probed DIF object location was served from the cache.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeCacheStatus {
+    /// The object metadata was loaded from the cache without contacting the source.
+    Hit,
+    /// Nothing was cached for this location and the object was downloaded.
+    Miss,
+    /// A negative or error entry was cached, but was ignored on request and the object was
+    /// downloaded again.
+    Bypassed,
+}
+
+/// Diagnostics of a single location probed for a DIF object.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LocationProbe {
+    /// The location of the DIF object on the source.
+    pub location: RemoteDifUri,
+    /// Whether the location was served from the cache or downloaded.
+    pub cache: ProbeCacheStatus,
+    /// The HTTP status code of the download, if the source was contacted via HTTP.
+    ///
+    /// Client errors other than `401` and `403` are treated as not found by Symbolicator and
+    /// are reported as `404`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub status_code: Option<u16>,
+    /// The result of fetching the DIF object from this location.
+    pub download: ObjectDownloadInfo,
+    /// The time spent looking up and downloading this location, in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// Diagnostics of looking up a DIF object on a single source.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceProbe {
+    /// The ID of the object source.
+    pub source: SourceId,
+    /// The error which occurred while listing the locations on this source, if any.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error: Option<String>,
+    /// The time spent listing the locations on this source, in milliseconds.
+    pub list_duration_ms: u64,
+    /// All locations at which the DIF object was looked up.
+    pub locations: Vec<LocationProbe>,
+}
+
 #[cfg(test)]
 mod tests {
     use crate::types::ObjectDownloadInfo;
@@ -0,0 +1,190 @@
+//! Sources sent along with symbolication requests.
+//!
+//! Besides full [`SourceConfig`]s, requests may reference sources configured on the server by
+//! their id.  This keeps the credentials of these sources out of the requests.
+
+use std::sync::Arc;
+
+use serde::{de, Deserialize, Deserializer};
+use symbolicator_sources::{SourceConfig, SourceFilters, SourceId};
+use thiserror::Error;
+
+/// A source sent along with a request.
+#[derive(Clone, Debug)]
+pub enum RequestSource {
+    /// A reference to a source configured on the server, with `"type": "ref"`.
+    Ref(SourceRef),
+    /// The full configuration of a source.
+    Config(SourceConfig),
+}
+
+impl<'de> Deserialize<'de> for RequestSource {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Look at the type first, so that errors in full configurations are reported as such.
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let result = if value.get("type").and_then(|ty| ty.as_str()) == Some("ref") {
+            serde_json::from_value(value).map(RequestSource::Ref)
+        } else {
+            serde_json::from_value(value).map(RequestSource::Config)
+        };
+        result.map_err(de::Error::custom)
+    }
+}
+
+/// A reference to a source configured on the server.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SourceRef {
+    /// The id of the referenced source.
+    pub id: SourceId,
+
+    /// Overrides the path from the root of the bucket where files are located.
+    ///
+    /// Only supported for `s3` and `gcs` sources which allow it with `allow_prefix_override`.
+    #[serde(default)]
+    pub prefix: Option<String>,
+
+    /// Overrides the filters of the source.
+    ///
+    /// Not supported for `sentry` sources.
+    #[serde(default)]
+    pub filters: Option<SourceFilters>,
+}
+
+/// Errors resolving a [`SourceRef`].
+#[derive(Debug, Error)]
+pub enum SourceRefError {
+    #[error("unknown source `{0}`")]
+    Unknown(SourceId),
+
+    #[error("the prefix of source `{0}` cannot be overridden")]
+    PrefixNotSupported(SourceId),
+
+    #[error("the prefix of source `{0}` is not allowed to be overridden")]
+    PrefixNotAllowed(SourceId),
+
+    #[error("the filters of source `{0}` cannot be overridden")]
+    FiltersNotSupported(SourceId),
+}
+
+impl SourceRef {
+    /// Applies the overrides of this reference to the referenced source.
+    ///
+    /// Since cache keys do not include the prefix, a source with an overridden prefix gets its
+    /// own id of the form `<id>/<prefix>`.
+    pub fn apply(&self, source: &SourceConfig) -> Result<SourceConfig, SourceRefError> {
+        let mut source = source.clone();
+
+        if let Some(ref prefix) = self.prefix {
+            let id = SourceId::new(format!("{}/{}", self.id, prefix.trim_matches('/')));
+            let allowed = match source {
+                SourceConfig::S3(ref mut config) => {
+                    let config = Arc::make_mut(config);
+                    config.id = id;
+                    config.prefix = prefix.clone();
+                    config.allow_prefix_override
+                }
+                SourceConfig::Gcs(ref mut config) => {
+                    let config = Arc::make_mut(config);
+                    config.id = id;
+                    config.prefix = prefix.clone();
+                    config.allow_prefix_override
+                }
+                _ => return Err(SourceRefError::PrefixNotSupported(self.id.clone())),
+            };
+            if !allowed {
+                return Err(SourceRefError::PrefixNotAllowed(self.id.clone()));
+            }
+        }
+
+        if let Some(ref filters) = self.filters {
+            let files = match source {
+                SourceConfig::Http(ref mut config) => &mut Arc::make_mut(config).files,
+                SourceConfig::S3(ref mut config) => &mut Arc::make_mut(config).files,
+                SourceConfig::Gcs(ref mut config) => &mut Arc::make_mut(config).files,
+                SourceConfig::Filesystem(ref mut config) => &mut Arc::make_mut(config).files,
+                SourceConfig::Sentry(_) => {
+                    return Err(SourceRefError::FiltersNotSupported(self.id.clone()))
+                }
+            };
+            files.filters = filters.clone();
+        }
+
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolicator_sources::{CommonSourceConfig, GcsSourceConfig, GcsSourceKey};
+
+    use super::*;
+
+    fn gcs_source(allow_prefix_override: bool) -> SourceConfig {
+        SourceConfig::Gcs(Arc::new(GcsSourceConfig {
+            id: SourceId::new("ios-system"),
+            bucket: "symbols".into(),
+            prefix: "ios".into(),
+            source_key: Arc::new(GcsSourceKey {
+                private_key: "secret".into(),
+                client_email: "symbolicator@example.com".into(),
+            }),
+            allow_prefix_override,
+            files: CommonSourceConfig::default(),
+        }))
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let sources: Vec<RequestSource> = serde_json::from_str(
+            r#"[
+                {"type": "ref", "id": "ios-system", "prefix": "ios/16"},
+                {"type": "http", "id": "public", "url": "https://example.com/symbols/"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(matches!(sources[0], RequestSource::Ref(ref r) if r.id.as_str() == "ios-system"));
+        assert!(matches!(
+            sources[1],
+            RequestSource::Config(SourceConfig::Http(_))
+        ));
+
+        // Errors in full configurations are not masked by references.
+        let error = serde_json::from_str::<RequestSource>(r#"{"type": "http", "id": "public"}"#)
+            .unwrap_err();
+        assert!(error.to_string().contains("url"), "{}", error);
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let reference: SourceRef = serde_json::from_str(
+            r#"{"id": "ios-system", "prefix": "/ios/16/", "filters": {"filetypes": ["mach_code"]}}"#,
+        )
+        .unwrap();
+
+        let source = reference.apply(&gcs_source(true)).unwrap();
+        match source {
+            SourceConfig::Gcs(ref config) => {
+                assert_eq!(config.id.as_str(), "ios-system/ios/16");
+                assert_eq!(config.prefix, "/ios/16/");
+                assert_eq!(config.source_key.private_key, "secret");
+                assert_eq!(config.files.filters.filetypes.len(), 1);
+            }
+            _ => panic!("unexpected source: {:?}", source),
+        }
+
+        // Without overrides, the source is used as is.
+        let reference: SourceRef = serde_json::from_str(r#"{"id": "ios-system"}"#).unwrap();
+        let source = reference.apply(&gcs_source(false)).unwrap();
+        assert_eq!(source.id().as_str(), "ios-system");
+    }
+
+    #[test]
+    fn test_prefix_override_not_allowed() {
+        let reference: SourceRef =
+            serde_json::from_str(r#"{"id": "ios-system", "prefix": "/android"}"#).unwrap();
+
+        let error = reference.apply(&gcs_source(false)).unwrap_err();
+        assert!(matches!(error, SourceRefError::PrefixNotAllowed(_)));
+    }
+}
@@ -1,7 +1,7 @@
 pub mod addr;
 pub mod compression;
 pub mod futures;
-pub mod gcs;
 pub mod hex;
 pub mod http;
+pub mod request_diagnostics;
 pub mod sentry;
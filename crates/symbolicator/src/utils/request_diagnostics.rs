@@ -0,0 +1,189 @@
+//! Collection of [`RequestDiagnostics`] for a single symbolication request.
+//!
+//! The collector of a request is stored in a task-local, so that the caches and the download
+//! service can record into it without passing it through every call.  Computations which are
+//! spawned on behalf of a request must be wrapped in [`scope`] with the [`current`] collector
+//! to keep recording into it.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use symbolicator_sources::SourceId;
+
+use crate::types::{
+    ComputationDiagnostics, ComputationOrigin, DownloadDiagnostics, DownloadOutcome,
+    RequestDiagnostics,
+};
+
+tokio::task_local! {
+    static COLLECTOR: Option<Arc<DiagnosticsCollector>>;
+}
+
+/// Collects the diagnostics of a single request.
+#[derive(Debug)]
+pub struct DiagnosticsCollector {
+    start: Instant,
+    diagnostics: Mutex<RequestDiagnostics>,
+}
+
+impl DiagnosticsCollector {
+    /// Creates a new collector, starting the clock for the total request time.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            start: Instant::now(),
+            diagnostics: Mutex::new(RequestDiagnostics::default()),
+        })
+    }
+
+    /// Returns the diagnostics collected so far.
+    pub fn finish(&self) -> RequestDiagnostics {
+        let mut diagnostics = self.diagnostics.lock().clone();
+        diagnostics.total_ms = as_millis(self.start.elapsed());
+        diagnostics
+    }
+}
+
+/// Returns the collector of the current task, if the request asked for diagnostics.
+pub fn current() -> Option<Arc<DiagnosticsCollector>> {
+    COLLECTOR.try_with(Clone::clone).ok().flatten()
+}
+
+/// Runs the future with the given collector.
+pub fn scope<F: Future>(
+    collector: Option<Arc<DiagnosticsCollector>>,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    COLLECTOR.scope(collector, future)
+}
+
+fn record(f: impl FnOnce(&mut RequestDiagnostics)) {
+    if let Some(collector) = current() {
+        f(&mut collector.diagnostics.lock());
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+/// Adds the duration to the wall time of a stage.
+pub fn record_stage(stage: &str, duration: Duration) {
+    record(|diagnostics| {
+        *diagnostics.stages.entry(stage.to_owned()).or_default() += as_millis(duration);
+    });
+}
+
+/// Records an item found in the local cache.
+pub fn record_local_hit(cache: &str) {
+    record(|diagnostics| {
+        diagnostics
+            .caches
+            .entry(cache.to_owned())
+            .or_default()
+            .local_hits += 1;
+    });
+}
+
+/// Records waiting for an item that is already being computed.
+pub fn record_joined(cache: &str) {
+    record(|diagnostics| {
+        diagnostics
+            .caches
+            .entry(cache.to_owned())
+            .or_default()
+            .joined += 1;
+    });
+}
+
+/// Records an item fetched from the shared cache or freshly computed.
+pub fn record_computation(cache: &str, key: String, origin: ComputationOrigin, duration: Duration) {
+    record(|diagnostics| {
+        let stats = diagnostics.caches.entry(cache.to_owned()).or_default();
+        match origin {
+            ComputationOrigin::Shared => stats.shared_hits += 1,
+            ComputationOrigin::Fresh => stats.computed += 1,
+        }
+        diagnostics.computations.push(ComputationDiagnostics {
+            cache: cache.to_owned(),
+            key,
+            origin,
+            duration_ms: as_millis(duration),
+        });
+    });
+}
+
+/// Records a download and adds its size to the bytes downloaded from the source.
+pub fn record_download(
+    source: &SourceId,
+    location: String,
+    outcome: DownloadOutcome,
+    bytes: u64,
+    duration: Duration,
+) {
+    record(|diagnostics| {
+        *diagnostics
+            .downloaded_bytes
+            .entry(source.clone())
+            .or_default() += bytes;
+        diagnostics.downloads.push(DownloadDiagnostics {
+            source: source.clone(),
+            location,
+            outcome,
+            bytes,
+            duration_ms: as_millis(duration),
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_in_scope() {
+        // Nothing is recorded outside of a scope.
+        record_local_hit("symcaches");
+
+        let collector = DiagnosticsCollector::new();
+        scope(Some(collector.clone()), async {
+            record_local_hit("symcaches");
+            record_stage("module_lookup", Duration::from_millis(5));
+            record_stage("module_lookup", Duration::from_millis(7));
+
+            // Spawned tasks only record if they are scoped explicitly.
+            tokio::spawn(async { record_joined("symcaches") })
+                .await
+                .unwrap();
+            tokio::spawn(scope(current(), async {
+                record_computation(
+                    "objects",
+                    "key".into(),
+                    ComputationOrigin::Fresh,
+                    Duration::from_millis(3),
+                );
+                record_download(
+                    &SourceId::new("local"),
+                    "file://foo".into(),
+                    DownloadOutcome::Completed,
+                    42,
+                    Duration::from_millis(2),
+                );
+            }))
+            .await
+            .unwrap();
+        })
+        .await;
+
+        let diagnostics = collector.finish();
+        assert_eq!(diagnostics.stages["module_lookup"], 12);
+        let symcaches = &diagnostics.caches["symcaches"];
+        assert_eq!(symcaches.local_hits, 1);
+        // The unscoped spawned task is not recorded.
+        assert_eq!(symcaches.joined, 0);
+        assert_eq!(diagnostics.caches["objects"].computed, 1);
+        assert_eq!(diagnostics.computations.len(), 1);
+        assert_eq!(diagnostics.downloaded_bytes[&SourceId::new("local")], 42);
+    }
+}
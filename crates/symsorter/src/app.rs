@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::io;
 use std::path::PathBuf;
@@ -8,25 +8,39 @@ use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Utc};
 use console::style;
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use structopt::clap::{AppSettings, ArgGroup};
 use structopt::StructOpt;
 use symbolic::common::{Arch, ByteView};
 use symbolic::debuginfo::{Archive, FileFormat, ObjectKind};
+use symbolicator_sources::{parse_directory_path, DirectoryLayoutType, ObjectId};
 use walkdir::WalkDir;
 use zip::ZipArchive;
 use zstd::stream::copy_encode;
 
 use crate::config::{RunConfig, SortConfig};
+use crate::manifest::{
+    content_hash, BundleDiff, Conflict, FileStatus, Manifest, Mismatch, RunReport,
+};
+use crate::prune::{self, PruneCli};
+use crate::upload::{load_destination, upload_files, UploadConfig};
 use crate::utils::{
-    create_source_bundle, get_target_filename, get_unified_id, is_bundle_id, make_bundle_id,
+    create_source_bundle, decompress_cab, get_target_filename, get_unified_id, is_bundle_id,
+    make_bundle_id, matches_layout_path, relative_key,
 };
 
 /// Sorts debug symbols into the right structure for symbolicator.
-#[derive(PartialEq, Eq, PartialOrd, Ord, StructOpt, Debug)]
+#[derive(PartialEq, Eq, StructOpt, Debug)]
+#[structopt(
+    setting = AppSettings::SubcommandsNegateReqs,
+    setting = AppSettings::ArgsNegateSubcommands,
+    group = ArgGroup::with_name("sort").required(true)
+)]
 struct Cli {
     /// Path to the output folder structure
-    #[structopt(long = "output", short = "o", value_name = "PATH")]
-    pub output: PathBuf,
+    // Required through the `sort` group, unless a subcommand with its own arguments is given.
+    #[structopt(long = "output", short = "o", value_name = "PATH", group = "sort")]
+    pub output: Option<PathBuf>,
 
     /// The prefix to use.
     #[structopt(long = "prefix", short = "p", value_name = "PREFIX")]
@@ -62,9 +76,56 @@ struct Cli {
     #[structopt(long = "quiet", short = "q")]
     pub quiet: bool,
 
+    /// Write a JSON report of written files, conflicts and bundle changes to this path.
+    #[structopt(long = "report", value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Upload the output to the `s3`, `gcs` or `filesystem` source defined in this JSON file.
+    #[structopt(long = "upload", value_name = "PATH")]
+    pub upload: Option<PathBuf>,
+
+    /// The maximum number of concurrent uploads.
+    #[structopt(long = "upload-concurrency", value_name = "NUM", default_value = "16")]
+    pub upload_concurrency: usize,
+
+    /// Do not upload files that already exist in the upload destination.
+    #[structopt(long = "skip-existing", requires = "upload")]
+    pub skip_existing: bool,
+
+    /// List the files that would be uploaded without uploading them.
+    #[structopt(long = "dry-run", requires = "upload")]
+    pub dry_run: bool,
+
+    /// Import the input folders from a symbol server with this directory layout.
+    ///
+    /// One of `native`, `symstore`, `symstore_index2`, `ssqp`, `debuginfod` or `unified`.
+    #[structopt(
+        long = "import-layout",
+        value_name = "LAYOUT",
+        parse(try_from_str = parse_layout_type)
+    )]
+    pub import_layout: Option<DirectoryLayoutType>,
+
     /// Path to input files.
     #[structopt(index = 1)]
     pub input: Vec<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Commands other than sorting files.  This is not a doc comment, since structopt would use it
+// as description of the entire application.
+#[derive(PartialEq, Eq, StructOpt, Debug)]
+enum Command {
+    /// Removes old bundles and all debug files that are no longer referenced by any bundle.
+    Prune(PruneCli),
+}
+
+/// Parses a directory layout type by the name used in source configs.
+fn parse_layout_type(name: &str) -> Result<DirectoryLayoutType> {
+    serde_json::from_value(serde_json::Value::String(name.to_owned()))
+        .map_err(|_| anyhow!("unknown directory layout {}", name))
 }
 
 #[derive(Serialize)]
@@ -74,15 +135,44 @@ pub struct DebugIdMeta {
     pub file_format: Option<FileFormat>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct BundleMeta {
     pub name: String,
     pub timestamp: DateTime<Utc>,
     pub debug_ids: Vec<String>,
 }
 
+/// State shared by all files sorted in a run.
+#[derive(Debug)]
+struct SortState {
+    manifest: Mutex<Manifest>,
+    report: Mutex<RunReport>,
+    /// Files in the output referenced by this run, which are uploaded afterwards.
+    ///
+    /// This includes files that were sorted in an earlier run, so that an upload that failed or
+    /// was skipped is completed by the next run.
+    referenced: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl SortState {
+    fn new(manifest: Manifest) -> Self {
+        Self {
+            manifest: Mutex::new(manifest),
+            report: Mutex::new(RunReport::default()),
+            referenced: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Marks files in the output as referenced by this run, skipping files that do not exist.
+    fn reference(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        let paths = paths.into_iter().filter(|path| path.exists());
+        self.referenced.lock().unwrap().extend(paths);
+    }
+}
+
 fn process_file(
     sort_config: &SortConfig,
+    state: &SortState,
     bv: ByteView<'static>,
     filename: String,
 ) -> Result<Vec<(String, ObjectKind)>> {
@@ -123,14 +213,65 @@ fn process_file(
 
     for obj in archive.objects() {
         let obj = maybe_ignore_error!(obj.map_err(|e| anyhow!(e)));
-        let new_filename = root.join(maybe_ignore_error!(get_target_filename(&obj)));
+        let target_filename = maybe_ignore_error!(get_target_filename(&obj));
+        let new_filename = root.join(&target_filename);
+        let unified_id = maybe_ignore_error!(get_unified_id(&obj));
+        let kind = target_filename.file_name().unwrap().to_string_lossy();
 
         fs::create_dir_all(new_filename.parent().unwrap())?;
 
         if let Some(bundle_id) = &sort_config.bundle_id {
             let refs_path = new_filename.parent().unwrap().join("refs");
-            fs::create_dir_all(&refs_path)?;
-            fs::write(&refs_path.join(bundle_id), b"")?;
+            let ref_filename = refs_path.join(bundle_id);
+            if !ref_filename.exists() {
+                fs::create_dir_all(&refs_path)?;
+                fs::write(&ref_filename, b"")?;
+            }
+            state.reference([ref_filename]);
+        }
+
+        let hash = content_hash(obj.data());
+        let status = {
+            let mut manifest = state.manifest.lock().unwrap();
+            let status = manifest.check(&unified_id, &kind, &hash);
+            if status == FileStatus::New {
+                // Record the file right away, so that duplicates within this run are detected.
+                manifest.record(&unified_id, &kind, hash.clone());
+            }
+            status
+        };
+
+        let meta_filename = new_filename.parent().unwrap().join("meta");
+
+        match status {
+            FileStatus::Unchanged if new_filename.exists() => {
+                state.report.lock().unwrap().files_unchanged += 1;
+                state.reference([meta_filename, new_filename]);
+                rv.push((unified_id, obj.kind()));
+                continue;
+            }
+            FileStatus::Conflict { existing_hash } => {
+                eprintln!(
+                    "{}: {} conflicts with already sorted {} ({})",
+                    style("warning").yellow().bold(),
+                    style(&filename).cyan(),
+                    style(new_filename.display()).cyan(),
+                    style(&unified_id).dim(),
+                );
+                state.report.lock().unwrap().conflicts.push(Conflict {
+                    debug_id: unified_id.clone(),
+                    kind: kind.into_owned(),
+                    name: filename.clone(),
+                    existing_hash,
+                    new_hash: hash,
+                });
+                // Keep the existing file, but still list the debug id in the bundle.
+                state.reference([meta_filename, new_filename]);
+                rv.push((unified_id, obj.kind()));
+                continue;
+            }
+            // Files that were removed from the output since the last run are written again.
+            _ => {}
         }
 
         let meta = DebugIdMeta {
@@ -139,10 +280,7 @@ fn process_file(
             file_format: Some(obj.file_format()),
         };
 
-        fs::write(
-            &new_filename.parent().unwrap().join("meta"),
-            &serde_json::to_vec(&meta)?,
-        )?;
+        fs::write(&meta_filename, &serde_json::to_vec(&meta)?)?;
 
         log!(
             "{} ({}, {}) -> {}",
@@ -158,14 +296,58 @@ fn process_file(
         } else {
             io::copy(&mut obj.data(), &mut out)?;
         }
-        let unified_id = maybe_ignore_error!(get_unified_id(&obj));
+        state.report.lock().unwrap().files_written += 1;
+        state.reference([meta_filename, new_filename]);
         rv.push((unified_id, obj.kind()));
     }
 
     Ok(rv)
 }
 
-fn sort_files(sort_config: &SortConfig, paths: Vec<PathBuf>) -> Result<(usize, usize)> {
+/// Reports a file imported from a symbol server that is not stored under its identifiers.
+fn check_layout_path(
+    state: &SortState,
+    layout_type: DirectoryLayoutType,
+    key: &str,
+    path_id: &ObjectId,
+    bv: &ByteView,
+) {
+    // Broken archives are reported when they are processed.
+    let archive = match Archive::parse(bv) {
+        Ok(archive) => archive,
+        Err(_) => return,
+    };
+
+    // Fat archives are stored under the identifiers of one of the contained objects.
+    let objects: Vec<_> = archive.objects().filter_map(Result::ok).collect();
+    if objects
+        .iter()
+        .any(|obj| matches_layout_path(obj, layout_type, key, path_id))
+    {
+        return;
+    }
+
+    let found: Vec<_> = objects
+        .iter()
+        .filter_map(|obj| get_unified_id(obj).ok())
+        .collect();
+    eprintln!(
+        "{}: {} does not match its identifiers ({})",
+        style("warning").yellow().bold(),
+        style(key).cyan(),
+        style(found.join(", ")).dim(),
+    );
+    state.report.lock().unwrap().mismatches.push(Mismatch {
+        path: key.to_owned(),
+        found,
+    });
+}
+
+fn sort_files(
+    sort_config: &SortConfig,
+    state: &SortState,
+    paths: Vec<PathBuf>,
+) -> Result<(usize, usize)> {
     let mut source_bundles_created = 0;
     let source_candidates = Mutex::new(HashMap::<String, Option<PathBuf>>::new());
     let debug_ids = Mutex::new(Vec::new());
@@ -174,12 +356,32 @@ fn sort_files(sort_config: &SortConfig, paths: Vec<PathBuf>) -> Result<(usize, u
 
     paths
         .into_iter()
-        .flat_map(WalkDir::new)
-        .filter_map(Result::ok)
-        .filter(|entry| entry.metadata().ok().map_or(false, |x| x.is_file()))
+        .flat_map(|root| {
+            WalkDir::new(&root)
+                .into_iter()
+                .filter_map(Result::ok)
+                .map(move |entry| (root.clone(), entry))
+        })
+        .filter(|(_, entry)| entry.metadata().ok().is_some_and(|x| x.is_file()))
         .par_bridge()
-        .map(|entry| {
+        .map(|(root, entry)| {
             let path = entry.path();
+
+            // When importing from a symbol server, only files stored in its layout are sorted.
+            let layout_path = match sort_config.import_layout {
+                Some(layout_type) => {
+                    let key = relative_key(&root, path)?;
+                    match parse_directory_path(layout_type, &key) {
+                        Some((_, path_id)) => Some((layout_type, key, path_id)),
+                        None => {
+                            log!("{} (skipped, not a debug file)", style(&key).dim());
+                            return Ok(());
+                        }
+                    }
+                }
+                None => None,
+            };
+
             let bv = match ByteView::open(path) {
                 Ok(bv) => bv,
                 Err(_) => return Ok(()),
@@ -194,17 +396,50 @@ fn sort_files(sort_config: &SortConfig, paths: Vec<PathBuf>) -> Result<(usize, u
                     let bv = ByteView::read(zip_file)?;
                     if Archive::peek(&bv) != FileFormat::Unknown {
                         debug_ids.lock().unwrap().extend(
-                            process_file(sort_config, bv, name)?
+                            process_file(sort_config, state, bv, name)?
                                 .into_iter()
                                 .map(|x| x.0),
                         );
                     }
                 }
 
+            // CAB compressed file from a Microsoft symbol server
+            } else if bv.get(..4) == Some(b"MSCF") {
+                let (bv, name) = match decompress_cab(path) {
+                    Ok(decompressed) => decompressed,
+                    Err(err) if RunConfig::get().ignore_errors => {
+                        eprintln!(
+                            "{}: ignored error {} ({})",
+                            style("error").red().bold(),
+                            err,
+                            style(path.display()).cyan(),
+                        );
+                        return Ok(());
+                    }
+                    Err(err) => {
+                        return Err(err)
+                            .context(format!("failed to process file {}", path.display()))
+                    }
+                };
+                if Archive::peek(&bv) != FileFormat::Unknown {
+                    if let Some((layout_type, key, path_id)) = &layout_path {
+                        check_layout_path(state, *layout_type, key, path_id, &bv);
+                    }
+                    debug_ids.lock().unwrap().extend(
+                        process_file(sort_config, state, bv, name)?
+                            .into_iter()
+                            .map(|x| x.0),
+                    );
+                }
+
             // object file directly
             } else if Archive::peek(&bv) != FileFormat::Unknown {
+                if let Some((layout_type, key, path_id)) = &layout_path {
+                    check_layout_path(state, *layout_type, key, path_id, &bv);
+                }
                 for (unified_id, object_kind) in process_file(
                     sort_config,
+                    state,
                     bv,
                     path.file_name().unwrap().to_string_lossy().to_string(),
                 )? {
@@ -228,11 +463,19 @@ fn sort_files(sort_config: &SortConfig, paths: Vec<PathBuf>) -> Result<(usize, u
 
     if sort_config.with_sources {
         log!("{}", style("Creating source bundles").bold());
-        source_bundles_created = source_candidates
+        let manifest = state.manifest.lock().unwrap();
+        let source_candidates: Vec<_> = source_candidates
             .into_inner()
             .unwrap()
-            .into_par_iter()
+            .into_iter()
             .filter_map(|(id, path)| Some((id, path?)))
+            // Source bundles are not reproducible, so never create them twice.
+            .filter(|(id, _)| !manifest.contains(id, "sourcebundle"))
+            .collect();
+        drop(manifest);
+
+        source_bundles_created = source_candidates
+            .into_par_iter()
             .map(|(unified_id, path)| -> Result<usize> {
                 let source_bundle = match create_source_bundle(&path, &unified_id)? {
                     Some(source_bundle) => source_bundle,
@@ -241,6 +484,7 @@ fn sort_files(sort_config: &SortConfig, paths: Vec<PathBuf>) -> Result<(usize, u
 
                 let processed_objects = process_file(
                     sort_config,
+                    state,
                     source_bundle,
                     path.file_name().unwrap().to_string_lossy().to_string(),
                 )?;
@@ -255,21 +499,42 @@ fn sort_files(sort_config: &SortConfig, paths: Vec<PathBuf>) -> Result<(usize, u
             .reduce(|| Ok(0), |sum, res| Ok(sum? + res?))?
     }
 
-    let debug_ids = debug_ids.into_inner().unwrap();
+    let mut debug_ids = debug_ids.into_inner().unwrap();
     let num_debug_ids = debug_ids.len();
 
+    // Executables and their debug files share a unified id, so list each id only once.
+    debug_ids.sort();
+    debug_ids.dedup();
+
     if let Some(bundle_id) = &sort_config.bundle_id {
         log!("{}", style("Writing bundle meta data").bold());
 
+        let bundle_meta_filename = RunConfig::get().output.join("bundles").join(bundle_id);
+        let mut previous_debug_ids = match fs::read(&bundle_meta_filename) {
+            Ok(data) => {
+                serde_json::from_slice::<BundleMeta>(&data)
+                    .with_context(|| format!("failed to parse bundle meta for {}", bundle_id))?
+                    .debug_ids
+            }
+            Err(_) => Vec::new(),
+        };
+        previous_debug_ids.sort();
+        previous_debug_ids.dedup();
+
+        state.report.lock().unwrap().bundles.insert(
+            bundle_id.clone(),
+            BundleDiff::new(&previous_debug_ids, &debug_ids),
+        );
+
         let bundle_meta = BundleMeta {
             name: bundle_id.clone(),
             timestamp: Utc::now(),
             debug_ids,
         };
 
-        let bundle_meta_filename = RunConfig::get().output.join("bundles").join(bundle_id);
         fs::create_dir_all(bundle_meta_filename.parent().unwrap())?;
         fs::write(&bundle_meta_filename, serde_json::to_vec(&bundle_meta)?)?;
+        state.reference([bundle_meta_filename]);
     }
 
     Ok((num_debug_ids, source_bundles_created))
@@ -277,13 +542,19 @@ fn sort_files(sort_config: &SortConfig, paths: Vec<PathBuf>) -> Result<(usize, u
 
 fn execute() -> Result<()> {
     let cli = Cli::from_args();
+    let output = match cli.command {
+        Some(Command::Prune(prune_cli)) => return prune::execute(prune_cli),
+        // Without a subcommand, clap ensures that the output is given.
+        None => cli.output.context("missing output path")?,
+    };
+
     RunConfig::configure(|cfg| {
         cfg.ignore_errors = cli.ignore_errors;
         cfg.quiet = cli.quiet;
         cfg.output = if let Some(ref prefix) = cli.prefix {
-            cli.output.join(prefix)
+            output.join(prefix)
         } else {
-            cli.output.clone()
+            output.clone()
         };
     });
 
@@ -296,12 +567,18 @@ fn execute() -> Result<()> {
         log!();
     }
 
+    // Load the destination before sorting, so that a broken definition fails early.
+    let destination = cli.upload.as_deref().map(load_destination).transpose()?;
+
+    let state = SortState::new(Manifest::load(&RunConfig::get().output)?);
+
     let mut debug_files = 0;
     let mut source_bundles = 0;
     let mut sort_config = SortConfig {
         bundle_id: None,
         with_sources: cli.with_sources,
         compression_level: cli.compression_level,
+        import_layout: cli.import_layout,
     };
 
     if cli.multiple_bundles {
@@ -310,7 +587,7 @@ fn execute() -> Result<()> {
             log!("[bundle: {}]", style(&bundle_id).dim());
             sort_config.bundle_id = Some(bundle_id);
             let (debug_files_sorted, source_bundles_created) =
-                sort_files(&sort_config, vec![path])?;
+                sort_files(&sort_config, &state, vec![path])?;
             debug_files += debug_files_sorted;
             source_bundles *= source_bundles_created;
         }
@@ -319,20 +596,86 @@ fn execute() -> Result<()> {
             anyhow::ensure!(is_bundle_id(&bundle_id), "Invalid bundle id");
             sort_config.bundle_id = Some(bundle_id);
         }
-        let (debug_files_sorted, source_bundles_created) = sort_files(&sort_config, cli.input)?;
+        let (debug_files_sorted, source_bundles_created) =
+            sort_files(&sort_config, &state, cli.input)?;
         debug_files += debug_files_sorted;
         source_bundles *= source_bundles_created;
     }
 
+    state
+        .manifest
+        .into_inner()
+        .unwrap()
+        .save(&RunConfig::get().output)?;
+    let report = state.report.into_inner().unwrap();
+
     log!();
     log!("{}", style("Done.").bold());
     log!("Sorted {} debug files", style(debug_files).yellow().bold());
+    log!(
+        "Wrote {} files, skipped {} unchanged files",
+        style(report.files_written).yellow().bold(),
+        style(report.files_unchanged).yellow().bold()
+    );
     if cli.with_sources {
         log!(
             "Created {} source bundles",
             style(source_bundles).yellow().bold()
         );
     }
+    for (bundle_id, diff) in &report.bundles {
+        log!(
+            "Bundle {}: {} debug ids added, {} removed",
+            style(bundle_id).cyan(),
+            style(diff.added.len()).yellow().bold(),
+            style(diff.removed.len()).yellow().bold()
+        );
+    }
+    if !report.conflicts.is_empty() {
+        log!(
+            "{}: {} files conflict with already sorted files",
+            style("WARNING").bold().red(),
+            report.conflicts.len()
+        );
+    }
+
+    if !report.mismatches.is_empty() {
+        log!(
+            "{}: {} imported files are stored under the wrong identifiers",
+            style("WARNING").bold().red(),
+            report.mismatches.len()
+        );
+    }
+
+    if let Some(report_path) = cli.report {
+        fs::write(&report_path, serde_json::to_vec_pretty(&report)?)
+            .with_context(|| format!("failed to write report {}", report_path.display()))?;
+    }
+
+    if let Some(destination) = destination {
+        log!();
+        log!("{}", style("Uploading debug information files").bold());
+
+        let upload_config = UploadConfig {
+            concurrency: cli.upload_concurrency,
+            skip_existing: cli.skip_existing,
+            dry_run: cli.dry_run,
+        };
+        let referenced = state.referenced.into_inner().unwrap();
+        let stats = upload_files(destination, &output, referenced, &upload_config)?;
+
+        let verb = if cli.dry_run {
+            "Would upload"
+        } else {
+            "Uploaded"
+        };
+        log!(
+            "{} {} files, skipped {} existing files",
+            verb,
+            style(stats.uploaded).yellow().bold(),
+            style(stats.skipped).yellow().bold()
+        );
+    }
 
     Ok(())
 }
@@ -352,3 +695,94 @@ pub fn main() -> ! {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use symbolicator_sources::SourceConfig;
+
+    use crate::utils::relative_key;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_sort_args() {
+        let cli = Cli::from_iter_safe(["symsorter", "-o", "out", "-b", "bundle", "prune"]).unwrap();
+        assert_eq!(cli.output, Some(PathBuf::from("out")));
+        // Subcommands are only recognized as first argument.
+        assert_eq!(cli.input, [PathBuf::from("prune")]);
+        assert_eq!(cli.command, None);
+
+        assert!(Cli::from_iter_safe(["symsorter", "input"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_prune_args() {
+        let cli =
+            Cli::from_iter_safe(["symsorter", "prune", "-o", "out", "--keep-last", "3"]).unwrap();
+        let prune_cli = match cli.command {
+            Some(Command::Prune(prune_cli)) => prune_cli,
+            None => panic!("expected the prune command"),
+        };
+        assert_eq!(prune_cli.output, PathBuf::from("out"));
+        assert_eq!(prune_cli.keep_last, Some(3));
+    }
+
+    /// Sorts the fixture into the configured output and returns the referenced files.
+    fn sort_fixture() -> BTreeSet<PathBuf> {
+        let output = RunConfig::get().output.clone();
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../tests/fixtures/symbols/7f/883fcdc55336d0a809b0150f09500b.debug");
+        let sort_config = SortConfig {
+            bundle_id: Some("my-bundle".into()),
+            with_sources: false,
+            compression_level: 0,
+            import_layout: None,
+        };
+
+        let state = SortState::new(Manifest::load(&output).unwrap());
+        sort_files(&sort_config, &state, vec![fixture]).unwrap();
+        state.manifest.into_inner().unwrap().save(&output).unwrap();
+        state.referenced.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_upload_after_failed_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("output");
+        RunConfig::configure(|cfg| {
+            cfg.output = output.clone();
+            cfg.quiet = true;
+        });
+
+        let destination: SourceConfig = serde_json::from_value(serde_json::json!({
+            "type": "filesystem",
+            "id": "local",
+            "path": dir.path().join("bucket"),
+        }))
+        .unwrap();
+        let upload_config = UploadConfig {
+            concurrency: 2,
+            skip_existing: true,
+            dry_run: false,
+        };
+
+        // The first run is not uploaded, for instance because the upload failed.
+        let first_run = sort_fixture();
+
+        // The second run does not write any files, but still uploads all of them.
+        let second_run = sort_fixture();
+        assert_eq!(second_run, first_run);
+        let stats = upload_files(destination.clone(), &output, second_run, &upload_config).unwrap();
+        assert_eq!(stats.uploaded, first_run.len());
+
+        for path in &first_run {
+            let key = relative_key(&output, path).unwrap();
+            assert!(dir.path().join("bucket").join(key).exists());
+        }
+
+        // Files that exist in the destination are not uploaded again, except bundle meta data.
+        let stats = upload_files(destination, &output, sort_fixture(), &upload_config).unwrap();
+        assert_eq!(stats.uploaded, 1);
+        assert_eq!(stats.skipped, first_run.len() - 1);
+    }
+}
@@ -1,6 +1,7 @@
 use lazy_static::lazy_static;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use symbolicator_sources::DirectoryLayoutType;
 
 lazy_static! {
     static ref CONFIG: Mutex<Arc<RunConfig>> = Mutex::new(Arc::new(Default::default()));
@@ -34,7 +35,7 @@ impl RunConfig {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(PartialEq, Eq, Debug)]
 pub struct SortConfig {
     /// The bundle ID of this task.
     pub bundle_id: Option<String>,
@@ -45,4 +46,7 @@ pub struct SortConfig {
     /// If enabled debug symbols will be zstd compressed
     /// (repeat to increase compression)
     pub compression_level: usize,
+
+    /// The layout of a symbol server the input is imported from.
+    pub import_layout: Option<DirectoryLayoutType>,
 }
@@ -12,6 +12,9 @@ mod utils;
 
 mod app;
 mod config;
+mod manifest;
+mod prune;
+mod upload;
 
 fn main() {
     app::main();
@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+/// The name of the manifest file in the output root.
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// A persistent record of all files written into an output root.
+///
+/// The manifest allows subsequent runs to skip files that were already sorted and to detect
+/// conflicting files with the same debug id.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Manifest {
+    /// Content hashes of the written files, keyed by unified id and file kind.
+    pub debug_ids: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// The status of a file compared to the manifest.
+#[derive(PartialEq, Eq, Debug)]
+pub enum FileStatus {
+    /// The file has not been sorted before.
+    New,
+    /// A file with identical contents has already been sorted.
+    Unchanged,
+    /// A file with the same id but different contents has already been sorted.
+    Conflict {
+        /// The content hash of the existing file.
+        existing_hash: String,
+    },
+}
+
+impl Manifest {
+    /// Loads the manifest from the given output root.
+    ///
+    /// Returns an empty manifest if the output root has not been written to before.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(MANIFEST_FILENAME);
+        match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("failed to parse manifest {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).context("failed to read manifest"),
+        }
+    }
+
+    /// Writes the manifest into the given output root.
+    pub fn save(&self, root: &Path) -> Result<()> {
+        fs::create_dir_all(root)?;
+        // Write to a temporary file first, so that an interrupted run does not leave a broken
+        // manifest behind.
+        let path = root.join(MANIFEST_FILENAME);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Compares a file with the given content hash to the manifest.
+    pub fn check(&self, unified_id: &str, kind: &str, hash: &str) -> FileStatus {
+        match self
+            .debug_ids
+            .get(unified_id)
+            .and_then(|files| files.get(kind))
+        {
+            None => FileStatus::New,
+            Some(existing) if existing == hash => FileStatus::Unchanged,
+            Some(existing) => FileStatus::Conflict {
+                existing_hash: existing.clone(),
+            },
+        }
+    }
+
+    /// Records a written file in the manifest.
+    pub fn record(&mut self, unified_id: &str, kind: &str, hash: String) {
+        self.debug_ids
+            .entry(unified_id.to_owned())
+            .or_default()
+            .insert(kind.to_owned(), hash);
+    }
+
+    /// Removes all files of the unified id from the manifest.
+    pub fn remove(&mut self, unified_id: &str) {
+        self.debug_ids.remove(unified_id);
+    }
+
+    /// Returns `true` if a file of the given kind has been recorded for the unified id.
+    pub fn contains(&self, unified_id: &str, kind: &str) -> bool {
+        self.debug_ids
+            .get(unified_id)
+            .is_some_and(|files| files.contains_key(kind))
+    }
+}
+
+/// Computes the content hash of a file as recorded in the manifest.
+///
+/// This hashes the uncompressed contents, so that the hash does not depend on the compression
+/// level of a run.
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+/// A file whose debug id was already sorted with different contents.
+#[derive(Serialize, Debug)]
+pub struct Conflict {
+    /// The unified id of the file.
+    pub debug_id: String,
+    /// The kind of the file, such as `debuginfo` or `executable`.
+    pub kind: String,
+    /// The name of the input file.
+    pub name: String,
+    /// The content hash of the file that was already sorted and is kept.
+    pub existing_hash: String,
+    /// The content hash of the conflicting input file.
+    pub new_hash: String,
+}
+
+/// A file imported from a symbol server whose path does not match its identifiers.
+#[derive(Serialize, Debug)]
+pub struct Mismatch {
+    /// The path of the file relative to the root of the symbol server.
+    pub path: String,
+    /// The unified ids of the objects contained in the file.
+    pub found: Vec<String>,
+}
+
+/// The changes to the debug ids of a bundle compared to the previous run.
+#[derive(Serialize, Default, Debug)]
+pub struct BundleDiff {
+    /// Debug ids which were not part of the bundle before.
+    pub added: Vec<String>,
+    /// Debug ids which were part of the bundle before, but are no longer.
+    pub removed: Vec<String>,
+}
+
+impl BundleDiff {
+    /// Computes the difference between two sorted lists of debug ids.
+    pub fn new(previous: &[String], current: &[String]) -> Self {
+        Self {
+            added: current
+                .iter()
+                .filter(|id| previous.binary_search(*id).is_err())
+                .cloned()
+                .collect(),
+            removed: previous
+                .iter()
+                .filter(|id| current.binary_search(*id).is_err())
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// A summary of the changes made by a symsorter run.
+#[derive(Serialize, Default, Debug)]
+pub struct RunReport {
+    /// The number of files written into the output.
+    pub files_written: usize,
+    /// The number of files skipped because they were already sorted.
+    pub files_unchanged: usize,
+    /// Files conflicting with previously sorted files.
+    pub conflicts: Vec<Conflict>,
+    /// Imported files stored under a path that does not match their identifiers.
+    pub mismatches: Vec<Mismatch>,
+    /// The changes to each bundle, keyed by bundle id.
+    pub bundles: BTreeMap<String, BundleDiff>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_check_files() {
+        let mut manifest = Manifest::default();
+        let hash = content_hash(b"debug file");
+        assert_eq!(manifest.check("abcd", "debuginfo", &hash), FileStatus::New);
+
+        manifest.record("abcd", "debuginfo", hash.clone());
+        assert_eq!(
+            manifest.check("abcd", "debuginfo", &hash),
+            FileStatus::Unchanged
+        );
+        assert!(manifest.contains("abcd", "debuginfo"));
+
+        // Other kinds of the same debug id are tracked separately.
+        assert_eq!(manifest.check("abcd", "executable", &hash), FileStatus::New);
+        assert!(!manifest.contains("abcd", "executable"));
+
+        let other_hash = content_hash(b"other debug file");
+        assert_eq!(
+            manifest.check("abcd", "debuginfo", &other_hash),
+            FileStatus::Conflict {
+                existing_hash: hash
+            }
+        );
+
+        manifest.remove("abcd");
+        assert_eq!(
+            manifest.check("abcd", "debuginfo", &other_hash),
+            FileStatus::New
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(Manifest::load(root.path()).unwrap().debug_ids.is_empty());
+
+        let mut manifest = Manifest::default();
+        let hash = content_hash(b"debug file");
+        manifest.record("abcd", "debuginfo", hash.clone());
+        manifest.save(root.path()).unwrap();
+
+        let manifest = Manifest::load(root.path()).unwrap();
+        assert_eq!(
+            manifest.check("abcd", "debuginfo", &hash),
+            FileStatus::Unchanged
+        );
+    }
+
+    #[test]
+    fn test_load_broken() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join(MANIFEST_FILENAME), b"{").unwrap();
+        assert!(Manifest::load(root.path()).is_err());
+    }
+
+    #[test]
+    fn test_bundle_diff() {
+        let previous = ids(&["a", "b", "c"]);
+        let current = ids(&["b", "c", "d", "e"]);
+
+        let diff = BundleDiff::new(&previous, &current);
+        assert_eq!(diff.added, ids(&["d", "e"]));
+        assert_eq!(diff.removed, ids(&["a"]));
+
+        let diff = BundleDiff::new(&[], &current);
+        assert_eq!(diff.added, current);
+        assert!(diff.removed.is_empty());
+
+        let diff = BundleDiff::new(&current, &current);
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+//! Removal of old bundles and the debug files only they reference.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use console::style;
+use structopt::StructOpt;
+
+use crate::app::BundleMeta;
+use crate::config::RunConfig;
+use crate::manifest::Manifest;
+
+/// Removes old bundles and all debug files that are no longer referenced by any bundle.
+#[derive(PartialEq, Eq, StructOpt, Debug)]
+pub struct PruneCli {
+    /// Path to the output folder structure
+    #[structopt(long = "output", short = "o", value_name = "PATH")]
+    pub output: PathBuf,
+
+    /// Only prune below this prefix instead of all prefixes.
+    #[structopt(long = "prefix", short = "p", value_name = "PREFIX")]
+    pub prefix: Option<String>,
+
+    /// Remove bundles imported more than this number of days ago.
+    #[structopt(long = "older-than", value_name = "DAYS")]
+    pub older_than: Option<u32>,
+
+    /// Keep only this number of most recently imported bundles per prefix.
+    #[structopt(long = "keep-last", value_name = "NUM")]
+    pub keep_last: Option<usize>,
+
+    /// List the bundles and debug ids that would be removed without removing them.
+    #[structopt(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// If enabled output will be suppressed
+    #[structopt(long = "quiet", short = "q")]
+    pub quiet: bool,
+}
+
+/// The retention rules of a prune run.
+#[derive(Debug)]
+struct Retention {
+    older_than: Option<Duration>,
+    keep_last: Option<usize>,
+    dry_run: bool,
+}
+
+/// The number of bundles and debug ids removed from a prefix.
+#[derive(Debug, Default)]
+struct PruneStats {
+    bundles: usize,
+    debug_ids: usize,
+}
+
+/// Loads the meta data of all bundles below a prefix, most recent first.
+fn load_bundles(root: &Path) -> Result<Vec<BundleMeta>> {
+    let mut bundles = Vec::new();
+    for entry in fs::read_dir(root.join("bundles"))? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let data = fs::read(&path)?;
+        let meta: BundleMeta = serde_json::from_slice(&data)
+            .with_context(|| format!("failed to parse bundle meta {}", path.display()))?;
+        bundles.push(meta);
+    }
+
+    bundles.sort_by_key(|bundle| std::cmp::Reverse(bundle.timestamp));
+    Ok(bundles)
+}
+
+/// Returns the directory of a debug id in the unified layout.
+fn debug_id_dir(root: &Path, unified_id: &str) -> Option<PathBuf> {
+    Some(root.join(unified_id.get(..2)?).join(unified_id.get(2..)?))
+}
+
+/// Removes a file, ignoring files that are already gone.
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn prune_prefix(root: &Path, retention: &Retention) -> Result<PruneStats> {
+    let mut stats = PruneStats::default();
+    let cutoff = retention.older_than.map(|age| Utc::now() - age);
+
+    let (removed, kept): (Vec<_>, Vec<_>) =
+        load_bundles(root)?
+            .into_iter()
+            .enumerate()
+            .partition(|(index, bundle)| {
+                cutoff.is_some_and(|cutoff| bundle.timestamp < cutoff)
+                    || retention.keep_last.is_some_and(|keep| *index >= keep)
+            });
+
+    if removed.is_empty() {
+        return Ok(stats);
+    }
+
+    let referenced: BTreeSet<&str> = kept
+        .iter()
+        .flat_map(|(_, bundle)| bundle.debug_ids.iter().map(String::as_str))
+        .collect();
+    let mut unreferenced = BTreeSet::new();
+
+    for (_, bundle) in &removed {
+        log!(
+            "{} bundle {} ({})",
+            if retention.dry_run {
+                "would remove"
+            } else {
+                "removing"
+            },
+            style(&bundle.name).cyan(),
+            style(bundle.timestamp).dim()
+        );
+        stats.bundles += 1;
+
+        for unified_id in &bundle.debug_ids {
+            if !referenced.contains(unified_id.as_str()) {
+                unreferenced.insert(unified_id.as_str());
+            } else if !retention.dry_run {
+                if let Some(dir) = debug_id_dir(root, unified_id) {
+                    remove_file(&dir.join("refs").join(&bundle.name))?;
+                }
+            }
+        }
+
+        if !retention.dry_run {
+            remove_file(&root.join("bundles").join(&bundle.name))?;
+        }
+    }
+
+    let mut manifest = Manifest::load(root)?;
+    for unified_id in unreferenced {
+        let dir = match debug_id_dir(root, unified_id) {
+            Some(dir) => dir,
+            None => continue,
+        };
+
+        if retention.dry_run {
+            log!("would remove {}", style(dir.display()).cyan());
+        } else {
+            log!("removing {}", style(dir.display()).cyan());
+            match fs::remove_dir_all(&dir) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(err).with_context(|| format!("failed to remove {}", dir.display()))
+                }
+                _ => {}
+            }
+            // Remove the parent folder of the id prefix as well, once it is empty.
+            fs::remove_dir(dir.parent().unwrap()).ok();
+            manifest.remove(unified_id);
+        }
+        stats.debug_ids += 1;
+    }
+
+    if !retention.dry_run {
+        manifest.save(root)?;
+    }
+
+    Ok(stats)
+}
+
+/// Returns the output roots to prune, which are all folders with bundles.
+fn find_roots(output: &Path, prefix: Option<&str>) -> Result<Vec<PathBuf>> {
+    if let Some(prefix) = prefix {
+        return Ok(vec![output.join(prefix)]);
+    }
+
+    // Files sorted without a prefix are stored directly in the output folder.
+    let mut roots = vec![output.to_path_buf()];
+    for entry in fs::read_dir(output)
+        .with_context(|| format!("failed to read output {}", output.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            roots.push(path);
+        }
+    }
+
+    roots.retain(|root| root.join("bundles").is_dir());
+    Ok(roots)
+}
+
+/// Runs the `prune` command with the given arguments, starting with `prune`.
+pub fn execute(cli: PruneCli) -> Result<()> {
+    RunConfig::configure(|cfg| {
+        cfg.quiet = cli.quiet;
+        cfg.output = cli.output.clone();
+    });
+
+    anyhow::ensure!(
+        cli.older_than.is_some() || cli.keep_last.is_some(),
+        "either --older-than or --keep-last is required"
+    );
+
+    let retention = Retention {
+        older_than: cli.older_than.map(|days| Duration::days(days.into())),
+        keep_last: cli.keep_last,
+        dry_run: cli.dry_run,
+    };
+
+    let mut stats = PruneStats::default();
+    for root in find_roots(&cli.output, cli.prefix.as_deref())? {
+        log!("[prefix: {}]", style(root.display()).dim());
+        let prefix_stats = prune_prefix(&root, &retention)?;
+        stats.bundles += prefix_stats.bundles;
+        stats.debug_ids += prefix_stats.debug_ids;
+    }
+
+    let verb = if cli.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    log!();
+    log!("{}", style("Done.").bold());
+    log!(
+        "{} {} bundles and {} debug ids",
+        verb,
+        style(stats.bundles).yellow().bold(),
+        style(stats.debug_ids).yellow().bold()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::{content_hash, MANIFEST_FILENAME};
+
+    use super::*;
+
+    const SHARED_ID: &str = "0123456789abcdef0123456789abcdef";
+    const OLD_ID: &str = "fedcba9876543210fedcba9876543210";
+    const NEW_ID: &str = "00112233445566778899aabbccddeeff";
+
+    /// Writes a bundle with the given age in days that references the given debug ids.
+    fn write_bundle(root: &Path, manifest: &mut Manifest, name: &str, age: i64, ids: &[&str]) {
+        let meta = BundleMeta {
+            name: name.into(),
+            timestamp: Utc::now() - Duration::days(age),
+            debug_ids: ids.iter().map(|&id| id.to_owned()).collect(),
+        };
+        fs::create_dir_all(root.join("bundles")).unwrap();
+        fs::write(
+            root.join("bundles").join(name),
+            serde_json::to_vec(&meta).unwrap(),
+        )
+        .unwrap();
+
+        for id in ids {
+            let dir = debug_id_dir(root, id).unwrap();
+            fs::create_dir_all(dir.join("refs")).unwrap();
+            fs::write(dir.join("debuginfo"), id).unwrap();
+            fs::write(dir.join("refs").join(name), "").unwrap();
+            manifest.record(id, "debuginfo", content_hash(id.as_bytes()));
+        }
+    }
+
+    /// Creates an output root with an old, a middle and a new bundle.
+    ///
+    /// The old bundle shares a debug id with the middle bundle.
+    fn setup() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let mut manifest = Manifest::default();
+        write_bundle(root, &mut manifest, "old", 30, &[SHARED_ID, OLD_ID]);
+        write_bundle(root, &mut manifest, "middle", 10, &[SHARED_ID]);
+        write_bundle(root, &mut manifest, "new", 1, &[NEW_ID]);
+        manifest.save(root).unwrap();
+
+        dir
+    }
+
+    fn retention(older_than: Option<i64>, keep_last: Option<usize>, dry_run: bool) -> Retention {
+        Retention {
+            older_than: older_than.map(Duration::days),
+            keep_last,
+            dry_run,
+        }
+    }
+
+    fn bundle_names(root: &Path) -> Vec<String> {
+        let mut names: Vec<_> = load_bundles(root)
+            .unwrap()
+            .into_iter()
+            .map(|bundle| bundle.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_prune_older_than() {
+        let dir = setup();
+        let root = dir.path();
+
+        let stats = prune_prefix(root, &retention(Some(20), None, false)).unwrap();
+        assert_eq!(stats.bundles, 1);
+        assert_eq!(stats.debug_ids, 1);
+
+        assert_eq!(bundle_names(root), ["middle", "new"]);
+        assert!(!debug_id_dir(root, OLD_ID).unwrap().exists());
+        assert!(!root.join(&OLD_ID[..2]).exists());
+
+        let manifest = Manifest::load(root).unwrap();
+        assert!(!manifest.contains(OLD_ID, "debuginfo"));
+        assert!(manifest.contains(SHARED_ID, "debuginfo"));
+        assert!(manifest.contains(NEW_ID, "debuginfo"));
+    }
+
+    #[test]
+    fn test_prune_keep_last() {
+        let dir = setup();
+        let root = dir.path();
+
+        let stats = prune_prefix(root, &retention(None, Some(1), false)).unwrap();
+        assert_eq!(stats.bundles, 2);
+        assert_eq!(stats.debug_ids, 2);
+
+        assert_eq!(bundle_names(root), ["new"]);
+        assert!(!debug_id_dir(root, OLD_ID).unwrap().exists());
+        assert!(!debug_id_dir(root, SHARED_ID).unwrap().exists());
+        assert!(debug_id_dir(root, NEW_ID)
+            .unwrap()
+            .join("refs/new")
+            .is_file());
+
+        let manifest = Manifest::load(root).unwrap();
+        assert_eq!(manifest.debug_ids.keys().collect::<Vec<_>>(), [NEW_ID]);
+    }
+
+    #[test]
+    fn test_prune_dry_run() {
+        let dir = setup();
+        let root = dir.path();
+        let manifest_before = fs::read(root.join(MANIFEST_FILENAME)).unwrap();
+
+        let stats = prune_prefix(root, &retention(None, Some(1), true)).unwrap();
+        assert_eq!(stats.bundles, 2);
+        assert_eq!(stats.debug_ids, 2);
+
+        assert_eq!(bundle_names(root), ["middle", "new", "old"]);
+        for id in [SHARED_ID, OLD_ID, NEW_ID] {
+            assert!(debug_id_dir(root, id).unwrap().join("debuginfo").is_file());
+        }
+        assert!(debug_id_dir(root, SHARED_ID)
+            .unwrap()
+            .join("refs/old")
+            .is_file());
+        assert_eq!(
+            fs::read(root.join(MANIFEST_FILENAME)).unwrap(),
+            manifest_before
+        );
+    }
+
+    #[test]
+    fn test_prune_shared_debug_id() {
+        let dir = setup();
+        let root = dir.path();
+
+        prune_prefix(root, &retention(Some(20), None, false)).unwrap();
+
+        // The debug id is still referenced by the middle bundle, so only the removed bundle's
+        // reference is dropped.
+        let shared_dir = debug_id_dir(root, SHARED_ID).unwrap();
+        assert!(shared_dir.join("debuginfo").is_file());
+        assert!(!shared_dir.join("refs/old").exists());
+        assert!(shared_dir.join("refs/middle").is_file());
+    }
+}
@@ -0,0 +1,417 @@
+//! Uploading of the sorted output into symbol buckets.
+//!
+//! The destination is described with the same source definitions that symbolicator uses to read
+//! from these buckets, so that a source config can be shared between both.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use console::style;
+use futures::{StreamExt, TryStreamExt};
+use rusoto_core::{HttpClient, Region, RusotoError};
+use rusoto_s3::{HeadObjectError, S3Client, S3};
+use tokio::sync::Mutex;
+use url::Url;
+
+use symbolicator_sources::gcs::{object_url, request_new_token, GcsAccess, GcsToken};
+use symbolicator_sources::{
+    AwsCredentialsProvider, FilesystemSourceConfig, GcsSourceConfig, GcsSourceKey, S3SourceConfig,
+    SourceConfig,
+};
+
+use crate::config::RunConfig;
+use crate::utils::relative_key;
+
+/// Options controlling an upload.
+#[derive(Debug)]
+pub struct UploadConfig {
+    /// The maximum number of concurrent uploads.
+    pub concurrency: usize,
+    /// Do not upload files that already exist in the destination.
+    pub skip_existing: bool,
+    /// Only list the files that would be uploaded.
+    pub dry_run: bool,
+}
+
+/// The number of files processed by an upload.
+#[derive(Debug, Default)]
+pub struct UploadStats {
+    /// Files uploaded into the destination, or that would be uploaded in a dry run.
+    pub uploaded: usize,
+    /// Files skipped because they already exist in the destination.
+    pub skipped: usize,
+}
+
+/// A destination to upload sorted files to.
+enum Destination {
+    Filesystem(Arc<FilesystemSourceConfig>),
+    S3(Arc<S3SourceConfig>, S3Client),
+    Gcs(Arc<GcsSourceConfig>, GcsClient),
+}
+
+impl Destination {
+    fn new(source: SourceConfig) -> Result<Self> {
+        Ok(match source {
+            SourceConfig::Filesystem(source) => Destination::Filesystem(source),
+            SourceConfig::S3(source) => {
+                let client = create_s3_client(&source)?;
+                Destination::S3(source, client)
+            }
+            SourceConfig::Gcs(source) => Destination::Gcs(source, GcsClient::new()),
+            other => bail!("cannot upload to {} sources", other.type_name()),
+        })
+    }
+
+    /// Returns a human readable location of the given key.
+    fn display(&self, key: &str) -> String {
+        match self {
+            Destination::Filesystem(source) => source.path.join(key).display().to_string(),
+            Destination::S3(source, _) => {
+                format!("s3://{}/{}", source.bucket, join_key(&source.prefix, key))
+            }
+            Destination::Gcs(source, _) => {
+                format!("gs://{}/{}", source.bucket, join_key(&source.prefix, key))
+            }
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self {
+            Destination::Filesystem(source) => Ok(source.path.join(key).exists()),
+            Destination::S3(source, client) => {
+                let request = rusoto_s3::HeadObjectRequest {
+                    bucket: source.bucket.clone(),
+                    key: join_key(&source.prefix, key),
+                    ..Default::default()
+                };
+                match client.head_object(request).await {
+                    Ok(_) => Ok(true),
+                    Err(RusotoError::Service(HeadObjectError::NoSuchKey(_))) => Ok(false),
+                    // HEAD responses have no body, so a missing key is usually not parsed into
+                    // a service error.
+                    Err(RusotoError::Unknown(response)) if response.status.as_u16() == 404 => {
+                        Ok(false)
+                    }
+                    Err(err) => Err(anyhow!(err)),
+                }
+            }
+            Destination::Gcs(source, client) => client.exists(source, key).await,
+        }
+    }
+
+    async fn upload(&self, key: &str, path: &Path) -> Result<()> {
+        match self {
+            Destination::Filesystem(source) => {
+                let target = source.path.join(key);
+                tokio::fs::create_dir_all(target.parent().unwrap()).await?;
+                tokio::fs::copy(path, target).await?;
+                Ok(())
+            }
+            Destination::S3(source, client) => {
+                let request = rusoto_s3::PutObjectRequest {
+                    bucket: source.bucket.clone(),
+                    key: join_key(&source.prefix, key),
+                    body: Some(tokio::fs::read(path).await?.into()),
+                    ..Default::default()
+                };
+                client.put_object(request).await?;
+                Ok(())
+            }
+            Destination::Gcs(source, client) => {
+                client
+                    .upload(source, key, tokio::fs::read(path).await?)
+                    .await
+            }
+        }
+    }
+}
+
+/// Joins a key to the prefix of a bucket, in the same way symbolicator resolves files.
+fn join_key(prefix: &str, key: &str) -> String {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}/{}", trimmed, key)
+    }
+}
+
+fn create_s3_client(source: &S3SourceConfig) -> Result<S3Client> {
+    let http_client = HttpClient::new()?;
+    let key = &source.source_key;
+    let region: Region = key.region.clone();
+
+    Ok(match key.aws_credentials_provider {
+        AwsCredentialsProvider::Container => {
+            let provider = rusoto_credential::AutoRefreshingProvider::new(
+                rusoto_credential::ContainerProvider::new(),
+            )?;
+            S3Client::new_with(http_client, provider, region)
+        }
+        AwsCredentialsProvider::Static => {
+            let provider = rusoto_credential::StaticProvider::new_minimal(
+                key.access_key.clone(),
+                key.secret_key.clone(),
+            );
+            S3Client::new_with(http_client, provider, region)
+        }
+    })
+}
+
+/// A minimal client for the GCS JSON API with write access.
+struct GcsClient {
+    client: reqwest::Client,
+    token: Mutex<Option<GcsToken>>,
+}
+
+impl GcsClient {
+    fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Returns a bearer token for the given key, requesting a new one if needed.
+    async fn bearer_token(&self, source_key: &GcsSourceKey) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(ref token) = *token {
+            if !token.is_expired() {
+                return Ok(token.bearer_token());
+            }
+        }
+
+        let new_token = request_new_token(&self.client, source_key, GcsAccess::ReadWrite)
+            .await
+            .context("failed to authenticate against GCS")?;
+        let bearer_token = new_token.bearer_token();
+        *token = Some(new_token);
+        Ok(bearer_token)
+    }
+
+    async fn exists(&self, source: &GcsSourceConfig, key: &str) -> Result<bool> {
+        let url = object_url(&source.bucket, &join_key(&source.prefix, key))?;
+
+        let response = self
+            .client
+            .get(url)
+            .header(
+                "authorization",
+                self.bearer_token(&source.source_key).await?,
+            )
+            .send()
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Ok(false),
+            _ => response
+                .error_for_status()
+                .map(|_| true)
+                .map_err(Into::into),
+        }
+    }
+
+    async fn upload(&self, source: &GcsSourceConfig, key: &str, data: Vec<u8>) -> Result<()> {
+        let mut url = Url::parse("https://storage.googleapis.com/upload/storage/v1")?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("invalid GCS url"))?
+            .extend(&["b", source.bucket.as_str(), "o"]);
+        url.query_pairs_mut()
+            .append_pair("uploadType", "media")
+            .append_pair("name", &join_key(&source.prefix, key));
+
+        self.client
+            .post(url)
+            .header(
+                "authorization",
+                self.bearer_token(&source.source_key).await?,
+            )
+            .header("content-type", "application/octet-stream")
+            .body(data)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Loads the destination source definition from a JSON file.
+pub fn load_destination(path: &Path) -> Result<SourceConfig> {
+    let data = fs::read(path)
+        .with_context(|| format!("failed to read upload destination {}", path.display()))?;
+    serde_json::from_slice(&data)
+        .with_context(|| format!("invalid upload destination {}", path.display()))
+}
+
+/// Returns `true` if existing files at the given key should be replaced.
+///
+/// Bundle meta data is updated when a bundle is imported again, all other files are identified
+/// by their debug id and never change.
+fn always_replace(key: &str) -> bool {
+    key.split('/').rev().nth(1) == Some("bundles")
+}
+
+/// Uploads the given files to the destination.
+///
+/// Keys are the paths relative to `root`, so that the prefix of the run is preserved.
+pub fn upload_files(
+    source: SourceConfig,
+    root: &Path,
+    files: impl IntoIterator<Item = PathBuf>,
+    config: &UploadConfig,
+) -> Result<UploadStats> {
+    let destination = Destination::new(source)?;
+
+    let files: Vec<(String, PathBuf)> = files
+        .into_iter()
+        .map(|path| Ok((relative_key(root, &path)?, path)))
+        .collect::<Result<_>>()?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async {
+        futures::stream::iter(files)
+            .map(|(key, path)| {
+                let destination = &destination;
+                async move {
+                    if config.skip_existing
+                        && !always_replace(&key)
+                        && destination.exists(&key).await?
+                    {
+                        return Ok(false);
+                    }
+
+                    if config.dry_run {
+                        log!("would upload {}", style(destination.display(&key)).cyan());
+                    } else {
+                        destination
+                            .upload(&key, &path)
+                            .await
+                            .with_context(|| format!("failed to upload {}", key))?;
+                        log!(
+                            "{} -> {}",
+                            style(path.display()).dim(),
+                            style(destination.display(&key)).cyan()
+                        );
+                    }
+                    Ok::<_, anyhow::Error>(true)
+                }
+            })
+            .buffer_unordered(config.concurrency.max(1))
+            .try_fold(UploadStats::default(), |mut stats, uploaded| async move {
+                if uploaded {
+                    stats.uploaded += 1;
+                } else {
+                    stats.skipped += 1;
+                }
+                Ok(stats)
+            })
+            .await
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filesystem_destination(dir: &Path) -> SourceConfig {
+        let path = dir.join("destination.json");
+        let definition = serde_json::json!({
+            "type": "filesystem",
+            "id": "local",
+            "path": dir.join("bucket"),
+        });
+        fs::write(&path, definition.to_string()).unwrap();
+        load_destination(&path).unwrap()
+    }
+
+    fn write_file(path: &Path, contents: &[u8]) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        path.to_owned()
+    }
+
+    fn upload_config(skip_existing: bool) -> UploadConfig {
+        UploadConfig {
+            concurrency: 2,
+            skip_existing,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_join_key() {
+        assert_eq!(join_key("", "ab/cdef/debuginfo"), "ab/cdef/debuginfo");
+        assert_eq!(join_key("/", "ab/cdef/debuginfo"), "ab/cdef/debuginfo");
+        assert_eq!(
+            join_key("/symbols/", "ab/cdef/debuginfo"),
+            "symbols/ab/cdef/debuginfo"
+        );
+    }
+
+    #[test]
+    fn test_always_replace() {
+        assert!(always_replace("bundles/my-bundle"));
+        assert!(always_replace("prefix/bundles/my-bundle"));
+        assert!(!always_replace("ab/cdef/debuginfo"));
+        assert!(!always_replace("ab/cdef/refs/my-bundle"));
+    }
+
+    #[test]
+    fn test_upload_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("output");
+        let destination = filesystem_destination(dir.path());
+
+        let debug_file = write_file(&root.join("prefix/ab/cdef/debuginfo"), b"debug");
+        // Files from earlier runs are not passed in and must not be uploaded.
+        write_file(&root.join("prefix/ab/cdef/executable"), b"executable");
+
+        let stats =
+            upload_files(destination, &root, vec![debug_file], &upload_config(false)).unwrap();
+        assert_eq!(stats.uploaded, 1);
+        assert_eq!(stats.skipped, 0);
+
+        let bucket = dir.path().join("bucket");
+        assert_eq!(
+            fs::read(bucket.join("prefix/ab/cdef/debuginfo")).unwrap(),
+            b"debug"
+        );
+        assert!(!bucket.join("prefix/ab/cdef/executable").exists());
+    }
+
+    #[test]
+    fn test_upload_skip_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("output");
+        let bucket = dir.path().join("bucket");
+        let destination = filesystem_destination(dir.path());
+
+        write_file(&bucket.join("ab/cdef/debuginfo"), b"uploaded");
+        write_file(&bucket.join("bundles/my-bundle"), b"old bundle");
+
+        let files = vec![
+            write_file(&root.join("ab/cdef/debuginfo"), b"debug"),
+            write_file(&root.join("bundles/my-bundle"), b"new bundle"),
+        ];
+
+        let stats = upload_files(destination, &root, files, &upload_config(true)).unwrap();
+        assert_eq!(stats.uploaded, 1);
+        assert_eq!(stats.skipped, 1);
+
+        // Existing debug files are kept, but bundle meta data is always replaced.
+        assert_eq!(
+            fs::read(bucket.join("ab/cdef/debuginfo")).unwrap(),
+            b"uploaded"
+        );
+        assert_eq!(
+            fs::read(bucket.join("bundles/my-bundle")).unwrap(),
+            b"new bundle"
+        );
+    }
+}
@@ -1,12 +1,16 @@
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 use symbolic::common::ByteView;
 use symbolic::debuginfo::sourcebundle::SourceBundleWriter;
 use symbolic::debuginfo::{Archive, FileFormat, Object, ObjectKind};
+use symbolicator_sources::{
+    get_directory_paths, DirectoryLayout, DirectoryLayoutType, FileType, ObjectId, ObjectType,
+};
 
 lazy_static! {
     static ref BAD_CHARS_RE: Regex = Regex::new(r"[^a-zA-Z0-9.,-]+").unwrap();
@@ -25,31 +29,200 @@ pub fn is_bundle_id(input: &str) -> bool {
     make_bundle_id(input) == input
 }
 
-/// Gets the unified ID from an object.
-pub fn get_unified_id(obj: &Object) -> Result<String> {
-    if obj.file_format() == FileFormat::Pe || obj.code_id().is_none() {
-        let debug_id = obj.debug_id();
-        if debug_id.is_nil() {
-            Err(anyhow!("failed to generate debug identifier"))
-        } else {
-            Ok(debug_id.breakpad().to_string().to_lowercase())
+/// Returns the file type under which an object is stored in the unified layout.
+pub fn get_file_type(obj: &Object) -> Option<FileType> {
+    let is_code = matches!(
+        obj.kind(),
+        ObjectKind::Relocatable | ObjectKind::Library | ObjectKind::Executable
+    );
+
+    Some(match obj.file_format() {
+        FileFormat::Breakpad => FileType::Breakpad,
+        FileFormat::SourceBundle => FileType::SourceBundle,
+        FileFormat::Pdb => FileType::Pdb,
+        FileFormat::PortablePdb => FileType::PortablePdb,
+        FileFormat::Pe if is_code => FileType::Pe,
+        FileFormat::Elf if is_code => FileType::ElfCode,
+        FileFormat::Elf if obj.kind() == ObjectKind::Debug => FileType::ElfDebug,
+        FileFormat::MachO if is_code => FileType::MachCode,
+        FileFormat::MachO if obj.kind() == ObjectKind::Debug => FileType::MachDebug,
+        FileFormat::Wasm if is_code => FileType::WasmCode,
+        FileFormat::Wasm if obj.kind() == ObjectKind::Debug => FileType::WasmDebug,
+        _ => return None,
+    })
+}
+
+/// Returns the platform of the object a Breakpad file was created for.
+///
+/// This is read from the `MODULE` record in the first line of the file.
+fn get_breakpad_object_type(obj: &Object) -> ObjectType {
+    let first_line = obj.data().split(|b| *b == b'\n').next().unwrap_or_default();
+    let os = String::from_utf8_lossy(first_line)
+        .split_whitespace()
+        .nth(1)
+        .map(str::to_lowercase);
+
+    match os.as_deref() {
+        Some("windows") => ObjectType::Pe,
+        Some("mac") | Some("ios") => ObjectType::Macho,
+        Some("linux") | Some("android") => ObjectType::Elf,
+        _ => ObjectType::Unknown,
+    }
+}
+
+/// Guesses the type of the object described by the identifiers of a file.
+///
+/// This is used for files which are not an object file themselves, like source bundles.
+fn guess_object_type(obj: &Object) -> ObjectType {
+    let debug_id = obj.debug_id();
+    match obj.code_id() {
+        // Mach-O and WASM code ids are the UUID of the debug id.
+        Some(code_id) if code_id.as_str() == debug_id.uuid().as_simple().to_string() => {
+            ObjectType::Macho
         }
-    } else {
-        Ok(obj.code_id().as_ref().unwrap().as_str().to_string())
+        // ELF debug ids are derived from the build id and never have an age.
+        Some(_) if debug_id.appendix() == 0 => ObjectType::Elf,
+        _ => ObjectType::Pe,
     }
 }
 
-/// Returns the intended target filename for an object.
+/// Returns the identifiers of an object, as used by symbolicator to look it up.
+pub fn get_object_id(obj: &Object) -> ObjectId {
+    let object_type = match obj.file_format() {
+        FileFormat::Elf => ObjectType::Elf,
+        FileFormat::MachO => ObjectType::Macho,
+        FileFormat::Pe | FileFormat::Pdb => ObjectType::Pe,
+        FileFormat::PortablePdb => ObjectType::PeDotnet,
+        FileFormat::Wasm => ObjectType::Wasm,
+        FileFormat::Breakpad => match get_breakpad_object_type(obj) {
+            ObjectType::Unknown => guess_object_type(obj),
+            object_type => object_type,
+        },
+        _ => guess_object_type(obj),
+    };
+
+    let debug_id = obj.debug_id();
+    ObjectId {
+        code_id: obj.code_id(),
+        code_file: None,
+        debug_id: if debug_id.is_nil() {
+            None
+        } else {
+            Some(debug_id)
+        },
+        debug_file: None,
+        object_type,
+    }
+}
+
+/// Returns the intended target filename for an object in the unified layout.
 pub fn get_target_filename(obj: &Object) -> Result<PathBuf> {
-    let id = get_unified_id(obj)?;
-    // match the unified format here.
-    let suffix = match obj.kind() {
-        ObjectKind::Debug => "debuginfo",
-        ObjectKind::Sources if obj.file_format() == FileFormat::SourceBundle => "sourcebundle",
-        ObjectKind::Relocatable | ObjectKind::Library | ObjectKind::Executable => "executable",
-        _ => bail!("unsupported file"),
+    let file_type = match get_file_type(obj) {
+        Some(file_type) => file_type,
+        None => bail!("unsupported file"),
+    };
+
+    let layout = DirectoryLayout {
+        ty: DirectoryLayoutType::Unified,
+        ..DirectoryLayout::default()
+    };
+
+    // PE and PDB files can also be looked up compressed, which uses a different filename.
+    get_directory_paths(layout, file_type, &get_object_id(obj))
+        .into_iter()
+        .find(|path| !path.ends_with('_'))
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("failed to generate debug identifier"))
+}
+
+/// Checks whether an object is stored at the given path of a symbol server layout.
+///
+/// The file names which some layouts encode in the path are taken from `path_id`, all other
+/// identifiers are read from the object itself.
+pub fn matches_layout_path(
+    obj: &Object,
+    layout_type: DirectoryLayoutType,
+    path: &str,
+    path_id: &ObjectId,
+) -> bool {
+    let file_type = match get_file_type(obj) {
+        Some(file_type) => file_type,
+        None => return false,
+    };
+
+    let object_id = ObjectId {
+        code_file: path_id.code_file.clone(),
+        debug_file: path_id.debug_file.clone(),
+        ..get_object_id(obj)
+    };
+    let layout = DirectoryLayout {
+        ty: layout_type,
+        ..DirectoryLayout::default()
     };
-    Ok(format!("{}/{}/{}", &id[..2], &id[2..], suffix).into())
+
+    // Symbol servers are commonly hosted on case insensitive file systems.
+    get_directory_paths(layout, file_type, &object_id)
+        .iter()
+        .any(|candidate| candidate.eq_ignore_ascii_case(path))
+}
+
+/// Gets the unified ID from an object.
+///
+/// This is the identifier that the object is stored under in the unified layout.
+pub fn get_unified_id(obj: &Object) -> Result<String> {
+    let path = get_target_filename(obj)?;
+    let mut components = path.iter().map(|c| c.to_string_lossy());
+    let prefix = components.next().unwrap_or_default();
+    let rest = components.next().unwrap_or_default();
+    Ok(format!("{}{}", prefix, rest))
+}
+
+/// Decompresses a CAB compressed file, as found on Microsoft symbol servers.
+///
+/// This requires `cabextract` to be installed.  Returns the decompressed contents and the
+/// original filename, which is derived by replacing the trailing underscore of the name, for
+/// instance `foo.pd_` becomes `foo.pdb`.
+pub fn decompress_cab(path: &Path) -> Result<(ByteView<'static>, String)> {
+    let output = Command::new("cabextract")
+        .arg("-sfqp")
+        .arg(path)
+        .output()
+        .context("failed to run cabextract")?;
+
+    if !output.status.success() {
+        bail!(
+            "failed to decompress cab file: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut name = path.file_name().unwrap().to_string_lossy().into_owned();
+    if let Some(stripped) = name.strip_suffix('_') {
+        let replacement = match stripped
+            .rsplit('.')
+            .next()
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("pd") => 'b',
+            Some("dl") => 'l',
+            Some("ex") => 'e',
+            _ => '_',
+        };
+        name = format!("{}{}", stripped, replacement);
+    }
+
+    Ok((ByteView::from_vec(output.stdout), name))
+}
+
+/// Returns the path of a file relative to `root`, with components separated by `/`.
+pub fn relative_key(root: &Path, path: &Path) -> Result<String> {
+    let relative = path.strip_prefix(root)?;
+    Ok(relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
 }
 
 /// Creates a source bundle from a path.
@@ -82,3 +255,187 @@ macro_rules! log {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The GUID of the synthetic PE and PDB files in little endian byte order.
+    const GUID: [u8; 16] = [
+        0x9d, 0xd9, 0x49, 0x32, 0x40, 0x0c, 0x31, 0x49, 0x86, 0x10, 0xf4, 0xe4, 0xfb, 0x0b, 0x69,
+        0x36,
+    ];
+
+    fn fixture(path: &str) -> ByteView<'static> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../tests/fixtures/symbols")
+            .join(path);
+        ByteView::open(path).unwrap()
+    }
+
+    fn target_filename(data: &[u8]) -> String {
+        let obj = Object::parse(data).unwrap();
+        let path = get_target_filename(&obj).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Builds a minimal 64-bit PE executable with a CodeView record pointing to `crash.pdb`.
+    fn build_pe() -> Vec<u8> {
+        let mut data = vec![0; 0x400];
+
+        // DOS header
+        put(&mut data, 0x00, b"MZ");
+        put(&mut data, 0x3c, &0x40u32.to_le_bytes());
+
+        // COFF header: x86_64, one section and 240 bytes of optional header
+        put(&mut data, 0x40, b"PE\0\0");
+        put(&mut data, 0x44, &0x8664u16.to_le_bytes());
+        put(&mut data, 0x46, &1u16.to_le_bytes());
+        put(&mut data, 0x48, &0x5ab3_8077u32.to_le_bytes());
+        put(&mut data, 0x54, &240u16.to_le_bytes());
+        put(&mut data, 0x56, &0x22u16.to_le_bytes());
+
+        // Optional header (PE32+) with the debug directory in the only section
+        put(&mut data, 0x58, &0x20bu16.to_le_bytes());
+        put(&mut data, 0x70, &0x1_4000_0000u64.to_le_bytes()); // image base
+        put(&mut data, 0x78, &0x1000u32.to_le_bytes()); // section alignment
+        put(&mut data, 0x7c, &0x200u32.to_le_bytes()); // file alignment
+        put(&mut data, 0x90, &0x2000u32.to_le_bytes()); // size of image
+        put(&mut data, 0x94, &0x200u32.to_le_bytes()); // size of headers
+        put(&mut data, 0x9c, &3u16.to_le_bytes()); // subsystem
+        put(&mut data, 0xc4, &16u32.to_le_bytes()); // number of data directories
+        put(&mut data, 0xf8, &0x1000u32.to_le_bytes()); // debug directory
+        put(&mut data, 0xfc, &28u32.to_le_bytes());
+
+        // Section header
+        put(&mut data, 0x148, b".rdata\0\0");
+        put(&mut data, 0x150, &0x200u32.to_le_bytes()); // virtual size
+        put(&mut data, 0x154, &0x1000u32.to_le_bytes()); // virtual address
+        put(&mut data, 0x158, &0x200u32.to_le_bytes()); // size of raw data
+        put(&mut data, 0x15c, &0x200u32.to_le_bytes()); // pointer to raw data
+        put(&mut data, 0x16c, &0x4000_0040u32.to_le_bytes());
+
+        // Debug directory with a single CodeView entry
+        let codeview = [&b"RSDS"[..], &GUID, &1u32.to_le_bytes(), b"crash.pdb\0"].concat();
+        put(&mut data, 0x20c, &2u32.to_le_bytes()); // type: CodeView
+        put(&mut data, 0x210, &(codeview.len() as u32).to_le_bytes());
+        put(&mut data, 0x214, &0x101cu32.to_le_bytes());
+        put(&mut data, 0x218, &0x21cu32.to_le_bytes());
+        put(&mut data, 0x21c, &codeview);
+
+        data
+    }
+
+    /// Builds a minimal PDB with the same debug id as [`build_pe`].
+    ///
+    /// The MSF container has pages of `0x200` bytes: the stream directory is listed on page 3 and
+    /// stored on page 4, followed by the PDB information stream and the DBI stream.
+    fn build_pdb() -> Vec<u8> {
+        let mut data = vec![0; 0x1000];
+
+        put(&mut data, 0, b"Microsoft C/C++ MSF 7.00\r\n\x1aDS\0\0\0");
+        put(&mut data, 0x20, &0x200u32.to_le_bytes()); // page size
+        put(&mut data, 0x24, &1u32.to_le_bytes()); // free page map
+        put(&mut data, 0x28, &8u32.to_le_bytes()); // number of pages
+        put(&mut data, 0x2c, &32u32.to_le_bytes()); // directory size
+        put(&mut data, 0x34, &3u32.to_le_bytes()); // directory page list
+
+        put(&mut data, 0x600, &4u32.to_le_bytes());
+
+        // Five streams of which the PDB information (1) and DBI (3) streams have contents. The
+        // empty stream 4 holds the symbol records.
+        let directory: Vec<u8> = [5, 0, 32, 0, 64, 0, 5, 6]
+            .iter()
+            .flat_map(|n: &u32| n.to_le_bytes())
+            .collect();
+        put(&mut data, 0x800, &directory);
+
+        put(&mut data, 0xa00, &20000404u32.to_le_bytes()); // version
+        put(&mut data, 0xa08, &1u32.to_le_bytes()); // age
+        put(&mut data, 0xa0c, &GUID);
+
+        put(&mut data, 0xc00, &u32::MAX.to_le_bytes()); // signature
+        put(&mut data, 0xc04, &19990903u32.to_le_bytes()); // version
+        put(&mut data, 0xc08, &1u32.to_le_bytes()); // age
+        put(&mut data, 0xc0c, &u16::MAX.to_le_bytes()); // global symbols
+        put(&mut data, 0xc10, &u16::MAX.to_le_bytes()); // public symbols
+        put(&mut data, 0xc14, &4u16.to_le_bytes()); // symbol records
+        put(&mut data, 0xc3a, &0x8664u16.to_le_bytes()); // machine type
+
+        data
+    }
+
+    #[test]
+    fn test_target_filename_elf() {
+        let data = fixture("7f/883fcdc55336d0a809b0150f09500b.debug");
+        assert_eq!(
+            target_filename(&data),
+            "7f/883fcdc55336d0a809b0150f09500b/debuginfo"
+        );
+    }
+
+    #[test]
+    fn test_target_filename_source_bundle() {
+        let data = fixture("7f/883fcdc55336d0a809b0150f09500b.src.zip");
+        assert_eq!(
+            target_filename(&data),
+            "7f/883fcdc55336d0a809b0150f09500b/sourcebundle"
+        );
+    }
+
+    #[test]
+    fn test_target_filename_macho() {
+        let data = fixture("2d10c42f-591d-3265-b147-78ba0868073f.dwarf-hidden");
+        assert_eq!(
+            target_filename(&data),
+            "2d/10c42f591d3265b14778ba0868073f/debuginfo"
+        );
+    }
+
+    #[test]
+    fn test_target_filename_pe() {
+        assert_eq!(
+            target_filename(&build_pe()),
+            "32/49d99d0c4049318610f4e4fb0b69361/executable"
+        );
+    }
+
+    #[test]
+    fn test_target_filename_pdb() {
+        assert_eq!(
+            target_filename(&build_pdb()),
+            "32/49d99d0c4049318610f4e4fb0b69361/debuginfo"
+        );
+    }
+
+    #[test]
+    fn test_target_filename_portable_pdb() {
+        let data =
+            fixture("integration.pdb/0C1033F78632492E91C6C314B72E1920ffffffff/integration.pdb");
+        assert_eq!(
+            target_filename(&data),
+            "0c/1033f78632492e91c6c314b72e1920e60b819d/debuginfo"
+        );
+    }
+
+    #[test]
+    fn test_target_filename_breakpad() {
+        // Breakpad files are stored next to the files of the object they were created from.
+        let data = fixture("crash.pdb/3249D99D0C4049318610F4E4FB0B69361/crash.pdb");
+        assert_eq!(
+            target_filename(&data),
+            "32/49d99d0c4049318610f4e4fb0b69361/breakpad"
+        );
+
+        let data = b"MODULE Linux x86_64 C0BCC3F19827FE653058404B2831D9E60 libfoo.so\n\
+            INFO CODE_ID F1C3BCC0279865FE3058404B2831D9E64135386C\n";
+        assert_eq!(
+            target_filename(data),
+            "f1/c3bcc0279865fe3058404b2831d9e64135386c/breakpad"
+        );
+    }
+}
@@ -7,13 +7,19 @@
     clippy::all
 )]
 
+use anyhow::{anyhow, bail};
+use serde::Serialize;
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
+use symbolic::common::CodeId;
+use symbolicator_sources::{
+    get_directory_paths, DirectoryLayout, DirectoryLayoutType, FileType, ObjectId, ObjectType,
+};
 use uuid::Uuid;
 use wasmbin::builtins::Blob;
-use wasmbin::io::Encode;
+use wasmbin::io::{Decode, Encode};
 use wasmbin::sections::{CustomSection, RawCustomSection, Section};
 use wasmbin::Module;
 
@@ -27,20 +33,36 @@ use wasmbin::Module;
 /// calculate offsets.
 ///
 /// This prints the embedded build_id in hexadecimal format to stdout.
+/// When multiple files are processed, each build_id is followed by the
+/// path of its input file.
 #[derive(Debug, StructOpt)]
 pub struct Cli {
-    /// path to the wasm file
-    input: PathBuf,
+    /// paths to the wasm files
+    ///
+    /// Multiple files can only be processed together with `--output-dir`.
+    #[structopt(required = true, min_values = 1)]
+    input: Vec<PathBuf>,
     /// path to the output wasm file.
     ///
     /// If not provided the same file is modified in place.
-    #[structopt(short, long)]
+    #[structopt(short, long, conflicts_with = "output-dir")]
     out: Option<PathBuf>,
     /// path to the output debug wasm file.
     ///
     /// If not provided the debug data stays in the input file.
-    #[structopt(short, long)]
+    #[structopt(short, long, conflicts_with = "output-dir")]
     debug_out: Option<PathBuf>,
+    /// path to a symbol source directory to write all files into.
+    ///
+    /// Each file and its debug companion are stored by build_id in the
+    /// directory layout given by `--layout`, so that the directory can be
+    /// used as a filesystem source in symbolicator.  The input files are not
+    /// modified.
+    #[structopt(long)]
+    output_dir: Option<PathBuf>,
+    /// the directory layout of `--output-dir`: `unified` or `native`.
+    #[structopt(long, default_value = "unified", parse(try_from_str = parse_layout_type))]
+    layout: DirectoryLayoutType,
     /// strip the file of debug info.
     #[structopt(long)]
     strip: bool,
@@ -56,6 +78,46 @@ pub struct Cli {
     /// URL for browsers to fetch the separate dwarf debug symbol file
     #[structopt(long)]
     external_dwarf_url: Option<String>,
+    /// URL of a source map for the wasm file
+    ///
+    /// This is written into the `sourceMappingURL` section.
+    #[structopt(long)]
+    source_mapping_url: Option<String>,
+    /// print the build id, custom sections and debug section sizes as JSON.
+    ///
+    /// The file is not modified in this mode.  Multiple files are printed
+    /// as a JSON array.
+    #[structopt(long)]
+    print_info: bool,
+}
+
+/// Information about a wasm file, as printed by `--print-info`.
+#[derive(Debug, Serialize)]
+struct ModuleInfo {
+    /// The path of the input file.
+    path: PathBuf,
+    /// The embedded build id in hexadecimal format.
+    build_id: Option<String>,
+    /// The URL of the external debug file.
+    external_debug_info: Option<String>,
+    /// The URL of the source map.
+    source_mapping_url: Option<String>,
+    /// All custom sections in the order of the file.
+    custom_sections: Vec<SectionInfo>,
+    /// The combined size of all debug sections.
+    debug_size: usize,
+}
+
+/// Parses a directory layout type by the name used in source configs.
+fn parse_layout_type(name: &str) -> Result<DirectoryLayoutType, anyhow::Error> {
+    serde_json::from_value(serde_json::Value::String(name.to_owned()))
+        .map_err(|_| anyhow!("unknown directory layout {}", name))
+}
+
+#[derive(Debug, Serialize)]
+struct SectionInfo {
+    name: String,
+    size: usize,
 }
 
 fn load_custom_section(section: &Section) -> Option<(&str, &[u8])> {
@@ -68,27 +130,124 @@ fn load_custom_section(section: &Section) -> Option<(&str, &[u8])> {
     }
 }
 
+fn custom_section_name(section: &Section) -> Option<&str> {
+    Some(match section.try_as()?.try_contents().ok()? {
+        CustomSection::Name(_) => "name",
+        CustomSection::Producers(_) => "producers",
+        CustomSection::Other(s) => &s.name,
+    })
+}
+
+/// Returns `true` if this is a DWARF debug section.
+fn is_debug_section(name: &str) -> bool {
+    name.starts_with(".debug_")
+}
+
 /// Returns `true` if this section should be stripped.
 fn is_strippable_section(section: &Section, strip_names: bool) -> bool {
-    fn custom_section_name(section: &Section) -> Option<&str> {
-        Some(match section.try_as()?.try_contents().ok()? {
-            CustomSection::Name(_) => "name",
-            CustomSection::Producers(_) => "producers",
-            CustomSection::Other(s) => &s.name,
-        })
-    }
-
     match custom_section_name(section) {
         Some("name") => strip_names,
-        Some(other) if other.starts_with(".debug_") => true,
+        Some(other) if is_debug_section(other) => true,
         _ => false,
     }
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    let cli = Cli::from_args();
+/// Adds a custom section containing a URL, replacing an existing section of the same name.
+fn set_url_section(module: &mut Module, name: &str, url: &str) {
+    // From the wasm spec, the URL is encoded as bytes, and prefixed with a varint encoding of a u32 size
+    // https://github.com/WebAssembly/tool-conventions/blob/08bacbed/Debugging.md#external-dwarf
+    // Emscripten: https://github.com/emscripten-core/emscripten/blob/4eefe273/tools/building.py#L1200
+    // We use the `wasmbin::io::Encode` trait, as it will handle serializing a string as a Vec<u8> with
+    // an LEB128 prefix.
+    let mut data = Vec::new();
+    url.encode(&mut data).unwrap();
+
+    module
+        .sections
+        .retain(|section| custom_section_name(section) != Some(name));
+    module
+        .sections
+        .push(Section::Custom(Blob::from(CustomSection::Other(
+            RawCustomSection {
+                name: name.to_string(),
+                data,
+            },
+        ))));
+}
+
+/// Collects the information printed by `--print-info`.
+fn module_info(input: &Path) -> Result<ModuleInfo, anyhow::Error> {
+    let module = Module::decode_from(BufReader::new(File::open(input)?))?;
+    let mut info = ModuleInfo {
+        path: input.to_path_buf(),
+        build_id: None,
+        external_debug_info: None,
+        source_mapping_url: None,
+        custom_sections: Vec::new(),
+        debug_size: 0,
+    };
+
+    for section in &module.sections {
+        let name = match custom_section_name(section) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let mut encoded = Vec::new();
+        section.encode(&mut encoded)?;
+        let size = encoded.len();
+        if is_debug_section(name) {
+            info.debug_size += size;
+        }
+        info.custom_sections.push(SectionInfo {
+            name: name.to_string(),
+            size,
+        });
+
+        if let Some((name, data)) = load_custom_section(section) {
+            match name {
+                "build_id" => info.build_id = Some(hex::encode(data)),
+                "external_debug_info" => {
+                    info.external_debug_info = Some(String::decode(&mut &data[..])?)
+                }
+                "sourceMappingURL" => {
+                    info.source_mapping_url = Some(String::decode(&mut &data[..])?)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+/// Returns the paths of a wasm file and its debug companion in a symbol source layout.
+fn layout_paths(
+    layout: DirectoryLayoutType,
+    build_id: &[u8],
+) -> Result<(PathBuf, PathBuf), anyhow::Error> {
+    let identifier = ObjectId {
+        code_id: Some(CodeId::new(hex::encode(build_id))),
+        object_type: ObjectType::Wasm,
+        ..ObjectId::default()
+    };
+    let layout = DirectoryLayout {
+        ty: layout,
+        ..DirectoryLayout::default()
+    };
+
+    let code_path = get_directory_paths(layout, FileType::WasmCode, &identifier).pop();
+    let debug_path = get_directory_paths(layout, FileType::WasmDebug, &identifier).pop();
+    match (code_path, debug_path) {
+        (Some(code_path), Some(debug_path)) => Ok((code_path.into(), debug_path.into())),
+        _ => bail!("the {:?} layout does not support wasm files", layout.ty),
+    }
+}
+
+/// Processes a single wasm file and returns its build id.
+fn process_file(cli: &Cli, input: &Path) -> Result<Vec<u8>, anyhow::Error> {
+    let mut module = Module::decode_from(BufReader::new(File::open(input)?))?;
 
-    let mut module = Module::decode_from(BufReader::new(File::open(&cli.input)?))?;
     let mut build_id = None;
     let mut should_write_main_module = false;
 
@@ -123,8 +282,26 @@ fn main() -> Result<(), anyhow::Error> {
         new_id
     });
 
+    // in batch mode, both files are written into the output directory.
+    let (output, debug_out) = match cli.output_dir {
+        Some(ref output_dir) => {
+            let (code_path, debug_path) = layout_paths(cli.layout, &build_id)?;
+            let output = output_dir.join(code_path);
+            let debug_output = output_dir.join(debug_path);
+            for path in [&output, &debug_output] {
+                std::fs::create_dir_all(path.parent().unwrap())?;
+            }
+            should_write_main_module = true;
+            (output, Some(debug_output))
+        }
+        None => (
+            cli.out.clone().unwrap_or_else(|| input.to_path_buf()),
+            cli.debug_out.clone(),
+        ),
+    };
+
     // split dwarf data out if needed into a separate file.
-    if let Some(debug_output) = cli.debug_out.as_ref() {
+    if let Some(debug_output) = debug_out.as_ref() {
         // note that this actually copies the entire original file over after
         // adding the build ID.  The reason for this is that we can only deal
         // with WASM files if the code section offset can be calculated.  This
@@ -146,8 +323,7 @@ fn main() -> Result<(), anyhow::Error> {
     }
 
     // If the debug file path is set, resolve the filename (ie. /some/path/to/foo.debug.wasm -> foo.debug.wasm)
-    let debug_file_name = cli
-        .debug_out
+    let debug_file_name = debug_out
         .as_ref()
         .and_then(|name| name.file_name())
         .and_then(|name| name.to_str())
@@ -156,38 +332,71 @@ fn main() -> Result<(), anyhow::Error> {
     // Use the command line flag if set, but fallback to the debug file name if that is set.
     // This is a reasonable default, as the filename on its own will resolve as a path relative to the main wasm file.
     // Emscripten falls back in the same way: https://github.com/emscripten-core/emscripten/pull/12549
-    let resolved_external_dwarf_url = cli.external_dwarf_url.or(debug_file_name);
+    let resolved_external_dwarf_url = cli.external_dwarf_url.clone().or(debug_file_name);
 
     if let Some(external_dwarf_url) = resolved_external_dwarf_url {
         should_write_main_module = true;
+        set_url_section(&mut module, "external_debug_info", &external_dwarf_url);
+    }
 
-        // From the wasm spec, the URL is encoded as bytes, and prefixed with a varint encoding of a u32 size
-        // https://github.com/WebAssembly/tool-conventions/blob/08bacbed/Debugging.md#external-dwarf
-        // Emscripten: https://github.com/emscripten-core/emscripten/blob/4eefe273/tools/building.py#L1200
-        // We use the `wasmbin::io::Encode` trait, as it will handle serializing a string as a Vec<u8> with
-        // an LEB128 prefix.
-        let data_vec = &mut Vec::new();
-        external_dwarf_url.encode(data_vec).unwrap();
-
-        module
-            .sections
-            .push(Section::Custom(Blob::from(CustomSection::Other(
-                RawCustomSection {
-                    name: "external_debug_info".to_string(),
-                    data: data_vec.to_vec(),
-                },
-            ))));
+    // The source map URL uses the same encoding as the external debug info.
+    // https://github.com/WebAssembly/tool-conventions/blob/08bacbed/Debugging.md#source-maps
+    if let Some(source_mapping_url) = cli.source_mapping_url.as_deref() {
+        should_write_main_module = true;
+        set_url_section(&mut module, "sourceMappingURL", source_mapping_url);
     }
 
     // main module
     if should_write_main_module {
-        let output = cli.out.as_ref().unwrap_or(&cli.input);
         module.encode_into(BufWriter::new(File::create(output)?))?;
     }
 
-    // always print the build id.
-    if !cli.quiet {
-        println!("{}", hex::encode(&build_id));
+    Ok(build_id)
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::from_args();
+    let batch = cli.input.len() > 1;
+
+    if batch && cli.output_dir.is_none() && !cli.print_info {
+        bail!("multiple input files require --output-dir");
+    }
+    // these values are specific to a single file.
+    if batch
+        && (cli.build_id.is_some()
+            || cli.external_dwarf_url.is_some()
+            || cli.source_mapping_url.is_some())
+    {
+        bail!("--build-id and the URL options can only be used with a single input file");
+    }
+
+    if cli.print_info {
+        let infos = cli
+            .input
+            .iter()
+            .map(|input| module_info(input))
+            .collect::<Result<Vec<_>, _>>()?;
+        // a single file is printed as object, multiple files as array.
+        let json = if batch {
+            serde_json::to_string_pretty(&infos)?
+        } else {
+            serde_json::to_string_pretty(&infos[0])?
+        };
+        println!("{}", json);
+        return Ok(());
+    }
+
+    for input in &cli.input {
+        let build_id = process_file(&cli, input)?;
+
+        // always print the build id.
+        if cli.quiet {
+            continue;
+        } else if batch {
+            println!("{} {}", hex::encode(&build_id), input.display());
+        } else {
+            println!("{}", hex::encode(&build_id));
+        }
     }
 
     Ok(())